@@ -36,6 +36,22 @@ impl<R: Read> BitReader<R> {
 		}
 	}
 
+	/// Returns the number of bits that have been read so far.
+	pub fn global_bit_pos(&self) -> usize {
+		self.global_bit_pos
+	}
+
+	/// Returns a reference to the underlying reader.
+	pub fn get_ref(&self) -> &R {
+		self.inner.get_ref()
+	}
+
+	/// Returns the underlying reader. Bytes that have been buffered ahead of the current bit
+	/// are lost.
+	pub fn into_inner(self) -> R {
+		self.inner.into_inner()
+	}
+
 	fn read_exact(&mut self, mut buf: &mut [u8]) -> io::Result<()> {
 		while !buf.is_empty() {
 			match self.inner.read(buf) {
@@ -46,7 +62,7 @@ impl<R: Read> BitReader<R> {
 			}
 		}
 		if !buf.is_empty() {
-			Err(io::Error::new(ErrorKind::Other, "EOF"))
+			Err(io::Error::new(ErrorKind::UnexpectedEof, "EOF"))
 		} else {
 			Ok(())
 		}
@@ -75,7 +91,7 @@ impl<R: Read> BitReader<R> {
 				self.global_bit_pos += 8;
 				Ok(byte)
 			},
-			(_, _, Err(e)) => if e.description() == "EOF" {
+			(_, _, Err(e)) => if e.kind() == ErrorKind::UnexpectedEof {
 				Err(BitReaderError::EOF)
 			} else {
 				Err(BitReaderError::Unspecified)
@@ -98,7 +114,7 @@ impl<R: Read> BitReader<R> {
 					self.current_byte = Some(buf[0]);
 					Ok(buf[0] & 0x0f)
 				},
-				Err(e) => if e.description() == "EOF" {
+				Err(e) => if e.kind() == ErrorKind::UnexpectedEof {
 					Err(BitReaderError::EOF)
 				} else {
 					Err(BitReaderError::Unspecified)
@@ -123,7 +139,7 @@ impl<R: Read> BitReader<R> {
 						self.current_byte = Some(buf[0]);
 						Ok(((byte >> (bit_pos)) | (buf[0] << (8 - bit_pos))) & 0x0f)
 					},
-					Err(e) => if e.description() == "EOF" {
+					Err(e) => if e.kind() == ErrorKind::UnexpectedEof {
 						Err(BitReaderError::EOF)
 					} else {
 						Err(BitReaderError::Unspecified)
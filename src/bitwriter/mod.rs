@@ -0,0 +1,175 @@
+/// Buffer for a bit-oriented output stream, the counterpart to the BitReader.
+///
+/// Bits are packed least significant bit first, which is the order the Brotli
+/// format, and hence the BitReader, expects. Completed bytes can be taken out
+/// of the buffer at any time, while a partially filled byte stays behind.
+///
+/// # Examples
+///
+/// let mut bw = BitWriter::new();
+/// bw.write_bit(true);
+/// bw.write_u32_to_n_bits(0b10, 2);
+/// bw.write_u8_to_byte_tail(0);
+/// assert_eq!(vec![0b101], bw.take_bytes());
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitWriter {
+	buf: Vec<u8>,
+	bit_pos: u8,
+	current_byte: u8,
+	global_bit_pos: usize,
+}
+
+impl BitWriter {
+	/// Creates an empty BitWriter.
+	pub fn new() -> BitWriter {
+		BitWriter{
+			buf: Vec::new(),
+			bit_pos: 0,
+			current_byte: 0,
+			global_bit_pos: 0,
+		}
+	}
+
+	/// Returns the number of bits that have been written so far.
+	pub fn global_bit_pos(&self) -> usize {
+		self.global_bit_pos
+	}
+
+	/// Returns true, if the next bit would start a new byte.
+	pub fn is_byte_aligned(&self) -> bool {
+		self.bit_pos == 0
+	}
+
+	/// Writes one bit to the stream.
+	pub fn write_bit(&mut self, bit: bool) {
+		if bit {
+			self.current_byte |= 1 << self.bit_pos;
+		}
+
+		self.bit_pos = (self.bit_pos + 1) % 8;
+		self.global_bit_pos += 1;
+
+		if self.bit_pos == 0 {
+			self.buf.push(self.current_byte);
+			self.current_byte = 0;
+		}
+	}
+
+	/// Writes the n least significant bits of a u32.
+	/// Only supports little endian, i.e. the least significant bit goes first into the stream.
+	pub fn write_u32_to_n_bits(&mut self, my_u32: u32, n: usize) {
+		assert!(n <= 32);
+
+		for i in 0..n {
+			self.write_bit((my_u32 >> i) & 1 == 1);
+		}
+	}
+
+	/// Writes the n least significant nibbles (4 bits) of a u32.
+	/// Only supports little endian, i.e. the least significant nibble goes first into the stream.
+	pub fn write_u32_to_n_nibbles(&mut self, my_u32: u32, n: usize) {
+		assert!(n <= 8);
+
+		for i in 0..n {
+			self.write_u32_to_n_bits((my_u32 >> (4 * i)) & 0x0f, 4);
+		}
+	}
+
+	/// Writes a code as it is looked up in a Huffman tree, i.e. the first bit in
+	/// the slice goes first into the stream.
+	pub fn write_bit_string(&mut self, bits: &[bool]) {
+		for &bit in bits {
+			self.write_bit(bit);
+		}
+	}
+
+	/// Writes bits up to the next byte boundary, taken from the least significant bits of my_u8.
+	/// Does nothing, if the stream is already aligned to a byte boundary.
+	pub fn write_u8_to_byte_tail(&mut self, my_u8: u8) {
+		if self.bit_pos > 0 {
+			let n = 8 - self.bit_pos as usize;
+
+			self.write_u32_to_n_bits(my_u8 as u32, n);
+		}
+	}
+
+	/// Writes a slice of u8, each byte taking up exactly 8 bits.
+	pub fn write_fixed_length_string(&mut self, my_string: &[u8]) {
+		if self.bit_pos == 0 {
+			self.buf.extend_from_slice(my_string);
+			self.global_bit_pos += 8 * my_string.len();
+		} else {
+			for &byte in my_string {
+				self.write_u32_to_n_bits(byte as u32, 8);
+			}
+		}
+	}
+
+	/// Removes and returns all completed bytes from the buffer.
+	/// Bits of an incomplete last byte stay in the writer.
+	pub fn take_bytes(&mut self) -> Vec<u8> {
+		::std::mem::take(&mut self.buf)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_write_some_bits() {
+		use super::*;
+
+		let mut bw = BitWriter::new();
+		for &bit in &[false, true, true, false, false, false, false, true, true, false] {
+			bw.write_bit(bit);
+		}
+		bw.write_u8_to_byte_tail(0);
+
+		assert_eq!(vec![134, 1], bw.take_bytes());
+		assert_eq!(16, bw.global_bit_pos());
+	}
+
+	#[test]
+	fn should_keep_incomplete_byte() {
+		use super::*;
+
+		let mut bw = BitWriter::new();
+		bw.write_u32_to_n_bits(0x1ff, 9);
+
+		assert_eq!(vec![0xff], bw.take_bytes());
+		assert!(!bw.is_byte_aligned());
+
+		bw.write_u8_to_byte_tail(0);
+
+		assert!(bw.is_byte_aligned());
+
+		assert_eq!(vec![0x01], bw.take_bytes());
+	}
+
+	#[test]
+	fn should_read_what_was_written() {
+		use super::*;
+		use ::bitreader::BitReader;
+		use std::io::Cursor;
+
+		let mut bw = BitWriter::new();
+		bw.write_bit(true);
+		bw.write_u32_to_n_bits(1736, 11);
+		bw.write_u32_to_n_nibbles(524527, 5);
+		bw.write_u8_to_byte_tail(0);
+		bw.write_fixed_length_string(b"xxxxxyyyyy.txt");
+		bw.write_bit(false);
+		bw.write_fixed_length_string(b"xyzzy");
+		bw.write_u8_to_byte_tail(0);
+
+		let mut br = BitReader::new(Cursor::new(bw.take_bytes()));
+
+		assert_eq!(Ok(true), br.read_bit());
+		assert_eq!(Ok(1736), br.read_u16_from_n_bits(11));
+		assert_eq!(Ok(524527), br.read_u32_from_n_nibbles(5));
+		assert_eq!(Ok(0), br.read_u8_from_byte_tail());
+		assert_eq!(Ok(b"xxxxxyyyyy.txt".to_vec()), br.read_fixed_length_string(14));
+		assert_eq!(Ok(false), br.read_bit());
+		assert_eq!(Ok(b"xyzzy".to_vec()), br.read_fixed_length_string(5));
+		assert_eq!(Ok(0), br.read_u8_from_byte_tail());
+	}
+}
@@ -0,0 +1,293 @@
+use ::{ Decompressor, State };
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::Read;
+
+/// The category of symbols, which a block switch command changes the block type for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockCategory {
+	/// Literals, whose block type selects the context map entries.
+	Literals,
+	/// Insert-and-copy length codes, whose block type selects the prefix code.
+	InsertAndCopyLengths,
+	/// Distance codes, whose block type selects the context map entries.
+	Distances,
+}
+
+/// A command of a stream, as it has been decoded, in the order of the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+	/// Start of a meta-block with data.
+	MetaBlockStart{
+		/// number of bytes the meta-block decompresses to
+		mlen: usize,
+		/// whether the meta-block holds the bytes as they are
+		uncompressed: bool,
+	},
+	/// Literals, which are inserted into the output as they are. The literals of an insert
+	/// are split, where a block switch for literals interrupts them.
+	Insert(Vec<u8>),
+	/// Backward reference into the output, or into the raw dictionary beyond it.
+	Copy{
+		/// number of bytes back from the end of the output
+		distance: usize,
+		/// number of bytes to copy
+		len: usize,
+	},
+	/// Reference to the static dictionary.
+	DictionaryRef{
+		/// index of the word among the words of length len
+		word_id: usize,
+		/// id of the transform, that has been applied to the word
+		transform_id: usize,
+		/// length of the word, before the transform
+		len: usize,
+	},
+	/// Switch to another block type.
+	BlockSwitch{
+		/// category of symbols, that the block type applies to
+		category: BlockCategory,
+		/// the new block type
+		block_type: usize,
+	},
+	/// Content of a metadata meta-block.
+	Metadata(Vec<u8>),
+}
+
+/// Commands, which a Decompressor collects while it decodes a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Recorder {
+	commands: VecDeque<Command>,
+	/// number of literals of the current command, that have been recorded already
+	literals: usize,
+}
+
+impl<R: Read> Decompressor<R> {
+	/// Records a command, unless commands are not recorded.
+	pub(crate) fn record(&mut self, command: Command) {
+		if let Some(ref mut recorder) = self.recorder {
+			recorder.commands.push_back(command);
+		}
+	}
+
+	/// Records the literals of the current command from the recorded ones up to the given index.
+	pub(crate) fn record_literals(&mut self, literals: &[u8], to: usize) {
+		if let Some(ref mut recorder) = self.recorder {
+			if to > recorder.literals {
+				recorder.commands.push_back(Command::Insert(literals[recorder.literals..to].to_vec()));
+			}
+
+			recorder.literals = to;
+		}
+	}
+
+	/// Records the command, if any, that the decompressor has decoded to enter a state.
+	pub(crate) fn record_state(&mut self, state: &State) {
+		let command = match *state {
+			State::MLen(m_len) if self.meta_block.header.is_last.unwrap() => Command::MetaBlockStart{
+				mlen: m_len as usize,
+				uncompressed: false,
+			},
+			State::IsUncompressed(is_uncompressed) => Command::MetaBlockStart{
+				mlen: self.meta_block.header.m_len.unwrap() as usize,
+				uncompressed: is_uncompressed,
+			},
+			State::MLenLiterals(ref literals) => Command::Insert(literals.clone()),
+			State::InsertLiterals(ref literals) => {
+				let len = literals.len();
+
+				self.record_literals(literals, len);
+
+				if let Some(ref mut recorder) = self.recorder {
+					recorder.literals = 0;
+				}
+
+				return;
+			},
+			State::CopyLiterals(_) => {
+				let len = self.meta_block.copy_length.unwrap() as usize;
+
+				match self.dictionary_reference() {
+					Some((word_id, transform_id)) => Command::DictionaryRef{
+						word_id,
+						transform_id,
+						len,
+					},
+					None => Command::Copy{
+						distance: self.meta_block.distance.unwrap() as usize,
+						len,
+					},
+				}
+			},
+			_ => return,
+		};
+
+		self.record(command);
+	}
+}
+
+/// Wraps an input stream, and iterates over the commands that it decodes to, instead of
+/// the bytes: literals, backward references, references to the static dictionary, block
+/// switches, meta-block boundaries and metadata. Iteration ends after the first error.
+///
+/// # Examples
+/// ```
+/// use brotli::{ Command, Commands };
+///
+/// let commands = Commands::new(&[0x0b, 0x00, 0x80, 0x58, 0x03][..]).collect::<Result<Vec<_>, _>>().unwrap();
+///
+/// assert_eq!(vec![
+///     Command::MetaBlockStart{ mlen: 1, uncompressed: true },
+///     Command::Insert(b"X".to_vec()),
+/// ], commands);
+/// ```
+#[derive(Debug)]
+pub struct Commands<R: Read> {
+	decompressor: Decompressor<R>,
+	buf: Vec<u8>,
+	error: Option<io::Error>,
+	done: bool,
+}
+
+impl<R: Read> Commands<R> {
+	/// Creates Commands from Read.
+	pub fn new(r: R) -> Commands<R> {
+		Commands::from_decompressor(Decompressor::new(r))
+	}
+
+	/// Creates Commands from Read, for a stream that has been compressed with a raw prefix
+	/// dictionary, as with Decompressor::with_dictionary().
+	pub fn with_dictionary(r: R, dictionary: &[u8]) -> Commands<R> {
+		Commands::from_decompressor(Decompressor::with_dictionary(r, dictionary))
+	}
+
+	fn from_decompressor(mut decompressor: Decompressor<R>) -> Commands<R> {
+		decompressor.recorder = Some(Recorder{
+			commands: VecDeque::new(),
+			literals: 0,
+		});
+
+		Commands{
+			decompressor,
+			buf: vec![0; 4096],
+			error: None,
+			done: false,
+		}
+	}
+}
+
+impl<R: Read> Iterator for Commands<R> {
+	type Item = io::Result<Command>;
+
+	fn next(&mut self) -> Option<io::Result<Command>> {
+		loop {
+			if let Some(command) = self.decompressor.recorder.as_mut().unwrap().commands.pop_front() {
+				return Some(Ok(command));
+			}
+
+			if self.done {
+				return self.error.take().map(Err);
+			}
+
+			match self.decompressor.read(&mut self.buf) {
+				Ok(0) => self.done = true,
+				Ok(_) => {},
+				Err(e) => {
+					self.error = Some(e);
+					self.done = true;
+				},
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_reconstruct_output_from_commands() {
+		use super::{ Command, Commands };
+		use ::compressor::Compressor;
+		use std::io::Write;
+
+		let input = (0..2000).map(|i| format!("{} bottles of beer on the wall, ", i % 99)).collect::<String>().into_bytes();
+
+		let mut compressor = Compressor::new(Vec::new());
+		compressor.write_all(&input).unwrap();
+		let compressed = compressor.finish().unwrap();
+
+		let mut output = Vec::new();
+		let mut mlen = 0;
+
+		for command in Commands::new(&compressed[..]) {
+			match command.unwrap() {
+				Command::MetaBlockStart{ mlen: m, .. } => mlen += m,
+				Command::Insert(literals) => output.extend_from_slice(&literals),
+				Command::Copy{ distance, len } => for _ in 0..len {
+					let literal = output[output.len() - distance];
+					output.push(literal);
+				},
+				command => panic!("unexpected {:?}", command),
+			}
+		}
+
+		assert_eq!(input.len(), mlen);
+		assert_eq!(input, output);
+	}
+
+	#[test]
+	fn should_yield_dictionary_references() {
+		use super::{ Command, Commands };
+		use std::fs::File;
+
+		let commands = Commands::new(File::open("data/quickfox_repeated.compressed").unwrap()).collect::<Result<Vec<_>, _>>().unwrap();
+
+		assert_eq!(Command::MetaBlockStart{ mlen: 176128, uncompressed: false }, commands[0]);
+		assert_eq!(Command::Insert(b"The ".to_vec()), commands[1]);
+		assert_eq!(Command::DictionaryRef{ word_id: 107, transform_id: 0, len: 5 }, commands[2]);
+		assert_eq!(Some(&Command::Copy{ distance: 43, len: 176085 }), commands.last());
+	}
+
+	#[test]
+	fn should_split_inserts_at_block_switches() {
+		use super::{ BlockCategory, Command, Commands };
+		use std::fs::File;
+
+		let commands = Commands::new(File::open("data/alice29.txt.compressed").unwrap()).collect::<Result<Vec<_>, _>>().unwrap();
+
+		let literals = commands.iter().fold(0, |acc, command| match *command {
+			Command::Insert(ref literals) => acc + literals.len(),
+			_ => acc,
+		});
+
+		assert!(literals > 0);
+		assert!(commands.iter().any(|command| matches!(*command, Command::BlockSwitch{ category: BlockCategory::Literals, .. })));
+		assert!(commands.iter().all(|command| *command != Command::Insert(Vec::new())));
+	}
+
+	#[test]
+	fn should_yield_metadata_and_errors() {
+		use super::{ Command, Commands };
+		use ::compressor::Compressor;
+		use std::io::Write;
+
+		let mut compressor = Compressor::with_dictionary(Vec::new(), b"dictionary");
+		compressor.write_all(b"dictionary").unwrap();
+		let mut compressed = compressor.finish().unwrap();
+
+		let commands = Commands::with_dictionary(&compressed[..], b"dictionary").collect::<Result<Vec<_>, _>>().unwrap();
+
+		assert!(commands.iter().any(|command| match *command {
+			Command::Metadata(ref metadata) => metadata.starts_with(b"BRPD"),
+			_ => false,
+		}));
+
+		let len = compressed.len();
+		compressed.truncate(len - 1);
+
+		let results = Commands::with_dictionary(&compressed[..], b"dictionary").collect::<Vec<_>>();
+
+		assert!(results[..(results.len() - 1)].iter().all(|result| result.is_ok()));
+		assert!(results[results.len() - 1].is_err());
+	}
+}
@@ -0,0 +1,309 @@
+use ::bitwriter::BitWriter;
+use super::prefixcode::PrefixCode;
+
+/// Estimates the number of bits needed to code the symbols of a histogram with
+/// a prefix code of its own, including a rough allowance for describing that prefix code.
+pub fn histogram_cost(histogram: &[usize]) -> f64 {
+	let total = histogram.iter().sum::<usize>() as f64;
+	let mut bits = 0f64;
+	let mut n_symbols = 0;
+
+	for &f in histogram {
+		if f > 0 {
+			bits += f as f64 * (total / f as f64).log2();
+			n_symbols += 1;
+		}
+	}
+
+	bits + match n_symbols {
+		0..=1 => 12f64,
+		n => 20f64 + 4f64 * n as f64,
+	}
+}
+
+fn merged(h0: &[usize], h1: &[usize]) -> Vec<usize> {
+	h0.iter().zip(h1.iter()).map(|(&f0, &f1)| f0 + f1).collect::<Vec<_>>()
+}
+
+/// Merges histograms greedily, for as long as a shared prefix code is estimated to be cheaper
+/// than separate ones, or while there are more than max_clusters of them.
+/// Returns the merged histograms, and the context map that assigns each of the
+/// original histograms to one of them. Empty histograms are assigned to the cluster
+/// of their predecessor, which makes for long runs in the context map.
+pub fn cluster_histograms(histograms: &[Vec<usize>], max_clusters: usize) -> (Vec<Vec<usize>>, Vec<u8>) {
+	let mut clusters = histograms.iter()
+		.filter(|h| h.iter().any(|&f| f > 0))
+		.map(|h| Some(h.clone()))
+		.collect::<Vec<_>>();
+	let mut costs = clusters.iter()
+		.map(|h| histogram_cost(h.as_ref().unwrap()))
+		.collect::<Vec<_>>();
+	let n = clusters.len();
+
+	// deltas[i][j] (for i < j) is the change in cost when merging clusters i and j
+	let mut deltas = vec![vec![0f64; n]; n];
+	for i in 0..n {
+		for j in (i + 1)..n {
+			let h = merged(clusters[i].as_ref().unwrap(), clusters[j].as_ref().unwrap());
+			deltas[i][j] = histogram_cost(&h) - costs[i] - costs[j];
+		}
+	}
+
+	let mut members = (0..n).map(|i| vec![i]).collect::<Vec<_>>();
+	let mut n_active = n;
+
+	loop {
+		let mut best = None;
+
+		for i in 0..n {
+			if clusters[i].is_none() {
+				continue;
+			}
+
+			for j in (i + 1)..n {
+				if clusters[j].is_none() {
+					continue;
+				}
+
+				best = match best {
+					Some((_, _, delta)) if delta <= deltas[i][j] => best,
+					_ => Some((i, j, deltas[i][j])),
+				};
+			}
+		}
+
+		let (i, j) = match best {
+			Some((i, j, delta)) if delta < 0f64 || n_active > max_clusters => (i, j),
+			_ => break,
+		};
+
+		let h = merged(clusters[i].as_ref().unwrap(), clusters[j].as_ref().unwrap());
+		costs[i] = histogram_cost(&h);
+		clusters[i] = Some(h);
+		clusters[j] = None;
+		n_active -= 1;
+
+		let moved = ::std::mem::take(&mut members[j]);
+		members[i].extend(moved);
+
+		for k in 0..n {
+			if k == i || clusters[k].is_none() {
+				continue;
+			}
+
+			let (lo, hi) = if k < i { (k, i) } else { (i, k) };
+			let h = merged(clusters[lo].as_ref().unwrap(), clusters[hi].as_ref().unwrap());
+			deltas[lo][hi] = histogram_cost(&h) - costs[lo] - costs[hi];
+		}
+	}
+
+	// cluster index by non-empty histogram index
+	let mut cluster_of = vec![0; n];
+	for (c, m) in members.iter().enumerate() {
+		for &i in m {
+			cluster_of[i] = c;
+		}
+	}
+
+	// assign clusters, renumbered by first appearance
+	let mut renumbered = vec![None; n];
+	let mut result = Vec::new();
+	let mut context_map = Vec::with_capacity(histograms.len());
+	let mut non_empty = 0;
+	let mut last = 0;
+
+	for h in histograms {
+		if h.iter().any(|&f| f > 0) {
+			let c = cluster_of[non_empty];
+			non_empty += 1;
+
+			last = match renumbered[c] {
+				Some(id) => id,
+				None => {
+					renumbered[c] = Some(result.len() as u8);
+					result.push(clusters[c].take().unwrap());
+					result.len() as u8 - 1
+				},
+			};
+		}
+
+		context_map.push(last);
+	}
+
+	if result.is_empty() {
+		result.push(vec![0; histograms.first().map(|h| h.len()).unwrap_or(0)]);
+	}
+
+	(result, context_map)
+}
+
+fn move_to_front_transform(v: &mut [u8]) {
+	let mut mtf: Vec<u8> = (0usize..256).map(|x| x as u8).collect();
+
+	for item in v.iter_mut() {
+		let value = *item;
+		let index = mtf.iter().position(|&x| x == value).unwrap();
+
+		for j in (1..index + 1).rev() {
+			mtf[j] = mtf[j - 1];
+		}
+		mtf[0] = value;
+		*item = index as u8;
+	}
+}
+
+fn floor_log2(n: usize) -> usize {
+	(0usize.leading_zeros() - n.leading_zeros() - 1) as usize
+}
+
+/// Run-length encodes the zeros in a context map, returns (symbol, extra bits, number of extra bits).
+fn run_length_encode(values: &[u8], rlemax: usize) -> Vec<(usize, u32, usize)> {
+	let mut symbols = Vec::with_capacity(values.len());
+	let mut i = 0;
+
+	while i < values.len() {
+		if values[i] > 0 {
+			symbols.push((values[i] as usize + rlemax, 0, 0));
+			i += 1;
+			continue;
+		}
+
+		let mut run = values[i..].iter().take_while(|&&v| v == 0).count();
+		i += run;
+
+		while run > 0 {
+			if rlemax == 0 || run == 1 {
+				symbols.push((0, 0, 0));
+				run -= 1;
+			} else {
+				let code = ::std::cmp::min(floor_log2(run), rlemax);
+				let repeat = ::std::cmp::min(run, (2 << code) - 1);
+
+				symbols.push((code, (repeat - (1 << code)) as u32, code));
+				run -= repeat;
+			}
+		}
+	}
+
+	symbols
+}
+
+fn write_context_map_with(out_stream: &mut BitWriter, values: &[u8], n_trees: usize, rlemax: usize, imtf: bool) {
+	if rlemax > 0 {
+		out_stream.write_bit(true);
+		out_stream.write_u32_to_n_bits(rlemax as u32 - 1, 4);
+	} else {
+		out_stream.write_bit(false);
+	}
+
+	let symbols = run_length_encode(values, rlemax);
+	let alphabet_size = rlemax + n_trees;
+	let mut histogram = vec![0; alphabet_size];
+	for &(symbol, _, _) in &symbols {
+		histogram[symbol] += 1;
+	}
+
+	let prefix_code = PrefixCode::write(out_stream, &histogram, alphabet_size);
+
+	for &(symbol, extra, n_extra) in &symbols {
+		prefix_code.write_symbol(out_stream, symbol);
+		out_stream.write_u32_to_n_bits(extra, n_extra);
+	}
+
+	out_stream.write_bit(imtf);
+}
+
+/// Writes a context map for n_trees prefix codes, in the most compact of its possible
+/// encodings with regards to RLEMAX and the move-to-front transform.
+pub fn write_context_map(out_stream: &mut BitWriter, context_map: &[u8], n_trees: usize) {
+	let mut mtf_values = context_map.to_vec();
+	move_to_front_transform(&mut mtf_values);
+
+	let mut best = None;
+
+	for &(values, imtf) in &[(context_map, false), (&mtf_values[..], true)] {
+		let mut longest_run = 0;
+		let mut run = 0;
+		for &v in values {
+			run = if v == 0 { run + 1 } else { 0 };
+			longest_run = ::std::cmp::max(run, longest_run);
+		}
+
+		let max_rlemax = if longest_run < 2 { 0 } else { ::std::cmp::min(floor_log2(longest_run), 16) };
+
+		for rlemax in 0..(max_rlemax + 1) {
+			let mut trial = BitWriter::new();
+			write_context_map_with(&mut trial, values, n_trees, rlemax, imtf);
+
+			best = match best {
+				Some((bits, _, _, _)) if bits <= trial.global_bit_pos() => best,
+				_ => Some((trial.global_bit_pos(), values, rlemax, imtf)),
+			};
+		}
+	}
+
+	let (_, values, rlemax, imtf) = best.unwrap();
+
+	write_context_map_with(out_stream, values, n_trees, rlemax, imtf);
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_round_trip_context_maps() {
+		use super::write_context_map;
+		use ::bitwriter::BitWriter;
+		use ::Decompressor;
+		use std::io::Cursor;
+
+		let context_maps = vec![
+			(vec![0, 1], 2),
+			(vec![0, 0, 0, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3], 4),
+			((0..64).map(|i| (i % 5) as u8).collect::<Vec<_>>(), 5),
+			((0..64 * 256).map(|i| ((i / 64) % 256) as u8).collect::<Vec<_>>(), 256),
+			((0..64 * 256).map(|i| if i % 1000 == 999 { 1 } else { 0 }).collect::<Vec<_>>(), 2),
+			((0..4 * 256).map(|i| ((i * 7919) % 256) as u8).collect::<Vec<_>>(), 256),
+		];
+
+		for (context_map, n_trees) in context_maps {
+			let mut bw = BitWriter::new();
+			write_context_map(&mut bw, &context_map, n_trees);
+			bw.write_u8_to_byte_tail(0);
+
+			let mut decompressor = Decompressor::new(Cursor::new(bw.take_bytes()));
+
+			assert_eq!(Ok(context_map.clone()), decompressor.parse_context_map(n_trees as u16, context_map.len()));
+		}
+	}
+
+	#[test]
+	fn should_cluster_similar_histograms() {
+		use super::cluster_histograms;
+
+		let a = (0..256).map(|i| if i < 128 { 100 } else { 0 }).collect::<Vec<_>>();
+		let b = (0..256).map(|i| if i >= 128 { 100 } else { 0 }).collect::<Vec<_>>();
+		let empty = vec![0; 256];
+
+		let histograms = vec![a.clone(), b.clone(), empty.clone(), a.clone(), b.clone(), a.clone()];
+		let (clusters, context_map) = cluster_histograms(&histograms, 256);
+
+		assert_eq!(2, clusters.len());
+		assert_eq!(vec![0, 1, 1, 0, 1, 0], context_map);
+		assert_eq!(300, clusters[0][0]);
+	}
+
+	#[test]
+	fn should_limit_number_of_clusters() {
+		use super::cluster_histograms;
+
+		let histograms = (0..64).map(|i| {
+			let mut h = vec![0; 256];
+			h[i] = 1000;
+			h
+		}).collect::<Vec<_>>();
+		let (clusters, context_map) = cluster_histograms(&histograms, 3);
+
+		assert_eq!(3, clusters.len());
+		assert!(context_map.iter().all(|&c| c < 3));
+	}
+}
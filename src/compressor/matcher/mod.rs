@@ -0,0 +1,272 @@
+use super::{ update_distance_cache, Command };
+
+use std::cmp;
+
+const HASH_BITS: usize = 15;
+const MAX_CHAIN_LENGTH: usize = 32;
+const MIN_MATCH_LENGTH: usize = 4;
+
+/// Finds backward references with hash chains over the positions of 4-byte sequences.
+///
+/// All positions are absolute positions in the uncompressed stream. Callers hand in the
+/// buffer holding the current window, along with the absolute position of its first byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matcher {
+	/// most recent position + 1 by hash value, 0 for none
+	head: Vec<usize>,
+	/// previous position + 1 with the same hash value by position, 0 for none
+	prev: Vec<usize>,
+	mask: usize,
+	window_size: usize,
+	/// absolute position of the end of a raw dictionary, 0 for none
+	dictionary_end: usize,
+}
+
+fn hash(bytes: &[u8]) -> usize {
+	let v = (bytes[0] as u32) | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24;
+
+	(v.wrapping_mul(0x1e35a7bd) >> (32 - HASH_BITS)) as usize
+}
+
+impl Matcher {
+	/// Creates a Matcher for a sliding window of (1 << wbits) - 16 bytes.
+	pub fn new(wbits: u8) -> Matcher {
+		Matcher{
+			head: vec![0; 1 << HASH_BITS],
+			prev: vec![0; 1 << wbits],
+			mask: (1 << wbits) - 1,
+			window_size: (1 << wbits) - 16,
+			dictionary_end: 0,
+		}
+	}
+
+	fn insert(&mut self, buf: &[u8], buf_offset: usize, pos: usize) {
+		let i = pos - buf_offset;
+
+		if i + 4 > buf.len() {
+			return;
+		}
+
+		let h = hash(&buf[i..i + 4]);
+		self.prev[pos & self.mask] = self.head[h];
+		self.head[h] = pos + 1;
+	}
+
+	/// Inserts the positions from buf_offset to end into the hash chains, without looking for matches,
+	/// so that later matches can refer to them. Positions within 3 bytes of the end of buf are skipped.
+	pub fn insert_all(&mut self, buf: &[u8], buf_offset: usize, end: usize) {
+		for pos in buf_offset..end {
+			self.insert(buf, buf_offset, pos);
+		}
+	}
+
+	/// Inserts a raw dictionary, which takes up the positions before the input, into the hash
+	/// chains. The decoder refers to the dictionary apart from the output, so matches in it end
+	/// with it.
+	pub fn insert_dictionary(&mut self, dictionary: &[u8]) {
+		self.insert_all(dictionary, 0, dictionary.len());
+		self.dictionary_end = dictionary.len();
+	}
+
+	/// Returns (length, distance) of the longest match for the bytes at pos, not longer than max_length.
+	/// Matches at one of the last distances are preferred, unless a match found through the hash
+	/// chains is at least two bytes longer, because their distances can be coded much shorter.
+	fn longest_match(&self, buf: &[u8], buf_offset: usize, pos: usize, max_length: usize, distance_cache: &[usize; 4]) -> Option<(usize, usize)> {
+		let i = pos - buf_offset;
+
+		if max_length < MIN_MATCH_LENGTH || i + 4 > buf.len() {
+			return None;
+		}
+
+		let match_length = |distance: usize| {
+			let j = i - distance;
+			let max_length = if pos - distance < self.dictionary_end {
+				cmp::min(max_length, self.dictionary_end - (pos - distance))
+			} else {
+				max_length
+			};
+
+			buf[j..].iter().zip(buf[i..(i + max_length)].iter()).take_while(|&(a, b)| a == b).count()
+		};
+
+		let mut cached = None;
+		let mut cached_length = MIN_MATCH_LENGTH - 1;
+
+		for &distance in distance_cache {
+			if distance == 0 || distance > i || distance > self.window_size {
+				continue;
+			}
+
+			let length = match_length(distance);
+
+			if length > cached_length {
+				cached_length = length;
+				cached = Some((length, distance));
+			}
+		}
+
+		let mut candidate = self.head[hash(&buf[i..i + 4])];
+		let mut best = None;
+		let mut best_length = cached_length + 1;
+
+		for _ in 0..MAX_CHAIN_LENGTH {
+			if candidate == 0 || best_length >= max_length {
+				break;
+			}
+
+			let c = candidate - 1;
+			let distance = pos - c;
+
+			if distance > self.window_size || c < buf_offset {
+				break;
+			}
+
+			let j = c - buf_offset;
+
+			if buf[j + best_length] == buf[i + best_length] {
+				let length = match_length(distance);
+
+				if length > best_length {
+					best_length = length;
+					best = Some((length, distance));
+
+					if length == max_length {
+						break;
+					}
+				}
+			}
+
+			candidate = self.prev[c & self.mask];
+		}
+
+		match best {
+			Some(_) => best,
+			None => cached,
+		}
+	}
+
+	/// Splits the bytes from start to end into commands, using greedy matching with
+	/// one step of lazy evaluation. The distance cache is updated with the distances
+	/// of the commands, in the same way the decoder updates its last distances.
+	pub fn find_commands(&mut self, buf: &[u8], buf_offset: usize, start: usize, end: usize, distance_cache: &mut [usize; 4]) -> Vec<Command> {
+		let mut commands = Vec::new();
+		let mut insert_length = 0;
+		let mut pos = start;
+
+		while pos < end {
+			let found = self.longest_match(buf, buf_offset, pos, end - pos, distance_cache);
+
+			self.insert(buf, buf_offset, pos);
+
+			let (copy_length, distance) = match found {
+				Some((length, distance)) => match self.longest_match(buf, buf_offset, pos + 1, end - pos - 1, distance_cache) {
+					Some((next_length, _)) if next_length > length => {
+						insert_length += 1;
+						pos += 1;
+						continue;
+					},
+					_ => (length, distance),
+				},
+				None => {
+					insert_length += 1;
+					pos += 1;
+					continue;
+				},
+			};
+
+			commands.push(Command{
+				insert_length,
+				copy_length,
+				distance,
+			});
+			update_distance_cache(distance_cache, distance);
+
+			for p in (pos + 1)..(pos + copy_length) {
+				self.insert(buf, buf_offset, p);
+			}
+
+			insert_length = 0;
+			pos += copy_length;
+		}
+
+		if insert_length > 0 {
+			commands.push(Command{
+				insert_length,
+				copy_length: 0,
+				distance: 0,
+			});
+		}
+
+		commands
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_find_repetition() {
+		use super::Matcher;
+		use super::super::{ Command, INITIAL_DISTANCE_CACHE };
+
+		let buf = b"abcdefgh abcdefgh abcdefgh!".to_vec();
+		let mut matcher = Matcher::new(16);
+
+		assert_eq!(vec![
+			Command{ insert_length: 9, copy_length: 17, distance: 9 },
+			Command{ insert_length: 1, copy_length: 0, distance: 0 },
+		], matcher.find_commands(&buf, 0, 0, buf.len(), &mut INITIAL_DISTANCE_CACHE.clone()));
+	}
+
+	#[test]
+	fn should_cover_all_bytes() {
+		use super::Matcher;
+		use super::super::INITIAL_DISTANCE_CACHE;
+
+		let buf = (0..10000).map(|i| ((i * i) % 251 % 13) as u8).collect::<Vec<_>>();
+		let mut matcher = Matcher::new(16);
+		let commands = matcher.find_commands(&buf, 0, 0, buf.len(), &mut INITIAL_DISTANCE_CACHE.clone());
+
+		let mut pos = 0;
+		for command in &commands {
+			pos += command.insert_length;
+			assert!(command.distance <= pos);
+			pos += command.copy_length;
+		}
+
+		assert_eq!(buf.len(), pos);
+	}
+
+	#[test]
+	fn should_end_matches_with_dictionary() {
+		use super::Matcher;
+		use super::super::{ Command, INITIAL_DISTANCE_CACHE };
+
+		// the input continues the dictionary, but the first match must not run into the input
+		let buf = b"abcdefghefghefgh".to_vec();
+		let mut matcher = Matcher::new(16);
+		matcher.insert_dictionary(&buf[..8]);
+
+		assert_eq!(vec![
+			Command{ insert_length: 0, copy_length: 4, distance: 4 },
+			Command{ insert_length: 0, copy_length: 4, distance: 4 },
+		], matcher.find_commands(&buf, 0, 8, buf.len(), &mut INITIAL_DISTANCE_CACHE.clone()));
+	}
+
+	#[test]
+	fn should_prefer_last_distances() {
+		use super::Matcher;
+		use super::super::{ Command, INITIAL_DISTANCE_CACHE };
+
+		// the second "1234567" also occurs 11 bytes back, at one of the initial last distances
+		let buf = b"1234567abcd1234567xyz1234567".to_vec();
+		let mut matcher = Matcher::new(16);
+		let mut distance_cache = INITIAL_DISTANCE_CACHE;
+		let commands = matcher.find_commands(&buf, 0, 0, buf.len(), &mut distance_cache);
+
+		assert_eq!(vec![
+			Command{ insert_length: 11, copy_length: 7, distance: 11 },
+			Command{ insert_length: 3, copy_length: 7, distance: 10 },
+		], commands);
+		assert_eq!([10, 11, 4, 11], distance_cache);
+	}
+}
@@ -0,0 +1,868 @@
+/// contextmap clusters histograms by context, and writes the resulting context maps.
+mod contextmap;
+/// matcher finds backward references in the uncompressed stream.
+mod matcher;
+/// prefixcode builds prefix codes from histograms, and writes their descriptions.
+mod prefixcode;
+
+use ::bitwriter::BitWriter;
+use ::lookuptable::{ LUT_0, LUT_1, LUT_2, INSERT_LENGTH_CODES, COPY_LENGTH_CODES };
+use ::sha256::sha256;
+use ::{ Header, WBits, NPostfix, NDirect, ContextMode, DICTIONARY_ID_MAGIC };
+
+use self::contextmap::{ cluster_histograms, histogram_cost, write_context_map };
+use self::matcher::Matcher;
+use self::prefixcode::PrefixCode;
+
+use std::cmp;
+use std::io;
+use std::io::Write;
+
+const DEFAULT_WBITS: WBits = 22;
+const META_BLOCK_SIZE: usize = 1 << 18;
+
+/// The last four distances at the start of a stream, the last distance first.
+const INITIAL_DISTANCE_CACHE: [usize; 4] = [4, 11, 15, 16];
+
+/// Fixed variable length code for NBLTYPES and NTREES, as (base value, number of extra bits, code),
+/// equivalent to Header::bltype_codes.
+const BLTYPE_CODES: [(usize, usize, &[bool]); 9] = [
+	(  1, 0, &[false]),
+	(  2, 0, &[true, false, false, false]),
+	(  3, 1, &[true, true, false, false]),
+	(  5, 2, &[true, false, true, false]),
+	(  9, 3, &[true, true, true, false]),
+	( 17, 4, &[true, false, false, true]),
+	( 33, 5, &[true, true, false, true]),
+	( 65, 6, &[true, false, true, true]),
+	(129, 7, &[true, true, true, true]),
+];
+
+/// One command of a meta-block: insert_length literals, followed by copy_length bytes,
+/// copied from distance bytes back. Only the last command of a meta-block may have
+/// a copy_length of 0, in which case its distance is meaningless.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Command {
+	insert_length: usize,
+	copy_length: usize,
+	distance: usize,
+}
+
+/// The commands of a compressed meta-block, together with the distance codes that refer to
+/// the last distances, and the distance parameters for all other distances.
+struct MetaBlockCommands<'a> {
+	commands: &'a [Command],
+	short_codes: &'a [Option<usize>],
+	n_postfix: NPostfix,
+	n_direct: NDirect,
+}
+
+/// Wraps an output stream and compresses everything that is written to it.
+///
+/// Input is collected into meta-blocks, which are compressed and written to the underlying
+/// stream once they are full. The stream needs to be completed with finish().
+///
+/// flush() ends the current meta-block early, and makes all input so far decodable
+/// from the output, at the cost of some compression.
+///
+/// # Examples
+/// ```
+/// use std::io::{ Read, Write };
+/// use brotli::{ Compressor, Decompressor };
+///
+/// let mut compressor = Compressor::new(Vec::new());
+/// compressor.write_all(b"Hello, World! Hello, World!").unwrap();
+/// let brotli_stream = compressor.finish().unwrap();
+///
+/// let mut decompressed = &mut Vec::new();
+/// let _ = Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed);
+///
+/// assert_eq!(b"Hello, World! Hello, World!".to_vec(), *decompressed);
+/// ```
+#[derive(Debug)]
+pub struct Compressor<W: Write> {
+	inner: W,
+	out_stream: BitWriter,
+	window_size: usize,
+	/// uncompressed bytes, the window up to pos followed by pending input
+	buf: Vec<u8>,
+	/// absolute position of buf[0] in the uncompressed stream
+	buf_offset: usize,
+	/// absolute position of the first byte that has not been compressed yet
+	pos: usize,
+	/// absolute position before which the literal context is 0: the first byte of input, unless
+	/// the dictionary is the output before a catable stream
+	context_start: usize,
+	matcher: Matcher,
+	/// last four distances after the compressed part of the stream, the last distance first,
+	/// where 0 stands for a distance that is unknown to the compressor
+	distance_cache: [usize; 4],
+	/// true, if the stream is going to be concatenated with other streams
+	catable: bool,
+}
+
+impl<W: Write> Compressor<W> {
+	/// Creates Compressor from Write.
+	pub fn new(w: W) -> Compressor<W> {
+		Compressor::with_options(w, false, &[], false)
+	}
+
+	/// Creates Compressor from Write, with a raw dictionary, which backward references can
+	/// reach into, as into a compound dictionary of the reference implementation, e.g. for
+	/// `brotli -D`. The stream can only be decompressed with the same dictionary, see
+	/// Decompressor::with_dictionary().
+	///
+	/// The stream identifies the dictionary by its SHA-256 digest, in a metadata meta-block.
+	///
+	/// # Examples
+	/// ```
+	/// use std::io::{ Read, Write };
+	/// use brotli::{ Compressor, Decompressor };
+	///
+	/// let dictionary = b"{\"id\": 0, \"name\": \"\", \"tags\": []}";
+	///
+	/// let mut compressor = Compressor::with_dictionary(Vec::new(), dictionary);
+	/// compressor.write_all(b"{\"id\": 17, \"name\": \"x\", \"tags\": []}").unwrap();
+	/// let brotli_stream = compressor.finish().unwrap();
+	///
+	/// let mut decompressed = &mut Vec::new();
+	/// let _ = Decompressor::with_dictionary(&brotli_stream[..], dictionary).read_to_end(&mut decompressed);
+	///
+	/// assert_eq!(b"{\"id\": 17, \"name\": \"x\", \"tags\": []}".to_vec(), *decompressed);
+	/// ```
+	pub fn with_dictionary(w: W, dictionary: &[u8]) -> Compressor<W> {
+		Compressor::with_options(w, false, dictionary, !dictionary.is_empty())
+	}
+
+	/// Creates Compressor from Write, for a catable stream. A catable stream can be joined
+	/// with other catable streams by brotli::concat(), which also completes it. It is not
+	/// a complete Brotli stream by itself, since it lacks the last meta-block.
+	///
+	/// A catable stream starts and ends on a byte boundary, and does not depend on
+	/// the data, the last distances, or the literal context from before its start.
+	pub fn new_catable(w: W) -> Compressor<W> {
+		Compressor::with_options(w, true, &[], false)
+	}
+
+	/// Creates Compressor from Write, with a dictionary that may be empty. If identify_dictionary
+	/// is true, the stream starts with a metadata meta-block, which identifies the dictionary.
+	///
+	/// The dictionary of a catable stream is the output that precedes the stream, once it has
+	/// been joined, so that matches may run from the dictionary into the input. Any other stream
+	/// gets a raw dictionary, which the decoder refers to apart from the output.
+	pub(crate) fn with_options(w: W, catable: bool, dictionary: &[u8], identify_dictionary: bool) -> Compressor<W> {
+		let wbits = DEFAULT_WBITS;
+		let mut out_stream = BitWriter::new();
+
+		out_stream.write_bit_string(&Header::new().wbits_codes.bit_string_for_symbol(wbits as u16).unwrap());
+
+		if catable {
+			write_empty_metadata_meta_block(&mut out_stream);
+		}
+
+		if identify_dictionary {
+			let mut dictionary_id = DICTIONARY_ID_MAGIC.to_vec();
+			dictionary_id.extend_from_slice(&sha256(dictionary));
+
+			write_metadata_meta_block(&mut out_stream, &dictionary_id);
+		}
+
+		// The dictionary takes up the positions before the first byte of input.
+		let mut matcher = Matcher::new(wbits);
+
+		if catable {
+			matcher.insert_all(dictionary, 0, dictionary.len());
+		} else {
+			matcher.insert_dictionary(dictionary);
+		}
+
+		Compressor{
+			inner: w,
+			out_stream,
+			window_size: (1 << wbits) - 16,
+			buf: dictionary.to_vec(),
+			buf_offset: 0,
+			pos: dictionary.len(),
+			context_start: if catable { 0 } else { dictionary.len() },
+			matcher,
+			distance_cache: if catable { [0; 4] } else { INITIAL_DISTANCE_CACHE },
+			catable,
+		}
+	}
+
+	fn write_out(&mut self) -> io::Result<()> {
+		let bytes = self.out_stream.take_bytes();
+
+		self.inner.write_all(&bytes)
+	}
+
+	fn compress_meta_block(&mut self, len: usize, is_last: bool) -> io::Result<()> {
+		let (start, end) = (self.pos, self.pos + len);
+
+		if self.catable && start < 2 {
+			// The literal context of the first two bytes of a catable stream is only known after
+			// concatenation, but an uncompressed meta-block does not depend on it.
+			let head_end = cmp::min(2, end);
+
+			self.write_uncompressed_meta_block(start, head_end, false);
+			self.pos = head_end;
+
+			return if head_end < end {
+				self.compress_meta_block(end - head_end, is_last)
+			} else {
+				self.write_out()
+			};
+		}
+		let distance_cache = self.distance_cache;
+		let commands = self.matcher.find_commands(&self.buf, self.buf_offset, start, end, &mut self.distance_cache);
+		let short_codes = short_distance_codes(&commands, distance_cache);
+		let (n_postfix, n_direct) = choose_distance_parameters(&commands, &short_codes);
+
+		let saved_out_stream = self.out_stream.clone();
+		let bit_pos = self.out_stream.global_bit_pos();
+
+		self.write_compressed_meta_block(start, end, MetaBlockCommands{
+			commands: &commands,
+			short_codes: &short_codes,
+			n_postfix,
+			n_direct,
+		}, is_last);
+
+		if self.out_stream.global_bit_pos() - bit_pos > uncompressed_meta_block_bits(bit_pos, len, is_last) {
+			// The decoder does not touch the last distances in an uncompressed meta-block.
+			self.out_stream = saved_out_stream;
+			self.distance_cache = distance_cache;
+			self.write_uncompressed_meta_block(start, end, is_last);
+		}
+
+		self.pos = end;
+
+		if self.pos > self.buf_offset + self.window_size + META_BLOCK_SIZE {
+			let obsolete = self.pos - self.window_size - self.buf_offset;
+
+			self.buf.drain(0..obsolete);
+			self.buf_offset += obsolete;
+		}
+
+		self.write_out()
+	}
+
+	/// Writes the bytes from start to end as an uncompressed meta-block. Since an uncompressed
+	/// meta-block can not be the last one, the stream gets completed with an empty last
+	/// meta-block, if is_last is true.
+	fn write_uncompressed_meta_block(&mut self, start: usize, end: usize, is_last: bool) {
+		write_meta_block_header(&mut self.out_stream, end - start, false, true);
+		self.out_stream.write_u8_to_byte_tail(0);
+		self.out_stream.write_fixed_length_string(&self.buf[(start - self.buf_offset)..(end - self.buf_offset)]);
+
+		if is_last {
+			// ISLAST, ISLASTEMPTY
+			self.out_stream.write_bit(true);
+			self.out_stream.write_bit(true);
+		}
+	}
+
+	/// Returns the byte at the absolute position pos, or 0 for positions before the start of the
+	/// literal context, since a raw dictionary does not count for the literal context.
+	fn byte_at(&self, pos: isize) -> u8 {
+		if pos < self.context_start as isize {
+			0
+		} else {
+			self.buf[pos as usize - self.buf_offset]
+		}
+	}
+
+	/// Writes a meta-block for the commands, where short_codes holds the distance codes
+	/// for those commands, whose distances can be coded with reference to the last distances.
+	fn write_compressed_meta_block(&mut self, start: usize, end: usize, block: MetaBlockCommands, is_last: bool) {
+		let MetaBlockCommands{ commands, short_codes, n_postfix, n_direct } = block;
+		// literal positions by context id, for all four context modes
+		let mut literal_histograms = vec![vec![vec![0usize; 256]; 64]; 4];
+		let mut distance_histograms = vec![vec![0usize; 16 + n_direct as usize + (48 << n_postfix)]; 4];
+		let mut insert_and_copy_length_histogram = vec![0usize; 704];
+		let mut pos = start;
+
+		for (command, &short_code) in commands.iter().zip(short_codes.iter()) {
+			let (insert_code, _, _) = length_code(&INSERT_LENGTH_CODES, command.insert_length);
+			let (copy_code, _, _) = length_code(&COPY_LENGTH_CODES, command.copy_length);
+			let implicit_distance = short_code == Some(0) && insert_code < 8 && copy_code < 16;
+
+			insert_and_copy_length_histogram[insert_and_copy_length_symbol(insert_code, copy_code, implicit_distance)] += 1;
+
+			for p in pos..(pos + command.insert_length) {
+				let (p1, p2) = (self.byte_at(p as isize - 1), self.byte_at(p as isize - 2));
+				let literal = self.byte_at(p as isize) as usize;
+
+				for (mode, histograms) in literal_histograms.iter_mut().enumerate() {
+					histograms[context_id(mode as ContextMode, p1, p2)][literal] += 1;
+				}
+			}
+
+			if command.copy_length > 0 && !implicit_distance {
+				let code = match short_code {
+					Some(code) => code,
+					None => distance_code(command.distance, n_postfix, n_direct).0,
+				};
+
+				distance_histograms[distance_context_id(command.copy_length)][code] += 1;
+			}
+
+			pos += command.insert_length + command.copy_length;
+		}
+
+		let context_mode = (0..4).map(|mode| {
+			(literal_histograms[mode].iter().fold(0f64, |acc, h| acc + histogram_cost(h)), mode)
+		}).fold(None, |best, (cost, mode)| match best {
+			Some((best_cost, _)) if best_cost <= cost => best,
+			_ => Some((cost, mode)),
+		}).unwrap().1;
+
+		let (literal_clusters, c_map_l) = cluster_histograms(&literal_histograms[context_mode], 256);
+		let (distance_clusters, c_map_d) = cluster_histograms(&distance_histograms, 256);
+
+		let out_stream = &mut self.out_stream;
+
+		write_meta_block_header(out_stream, end - start, is_last, false);
+
+		// NBLTYPESL, NBLTYPESI, NBLTYPESD
+		write_n_bltypes(out_stream, 1);
+		write_n_bltypes(out_stream, 1);
+		write_n_bltypes(out_stream, 1);
+
+		out_stream.write_u32_to_n_bits(n_postfix as u32, 2);
+		out_stream.write_u32_to_n_bits((n_direct >> n_postfix) as u32, 4);
+		out_stream.write_u32_to_n_bits(context_mode as u32, 2);
+
+		write_n_bltypes(out_stream, literal_clusters.len());
+		if literal_clusters.len() >= 2 {
+			write_context_map(out_stream, &c_map_l, literal_clusters.len());
+		}
+
+		write_n_bltypes(out_stream, distance_clusters.len());
+		if distance_clusters.len() >= 2 {
+			write_context_map(out_stream, &c_map_d, distance_clusters.len());
+		}
+
+		let prefix_codes_literals = literal_clusters.iter()
+			.map(|h| PrefixCode::write(out_stream, h, 256))
+			.collect::<Vec<_>>();
+		let prefix_code_insert_and_copy_lengths = PrefixCode::write(out_stream, &insert_and_copy_length_histogram, 704);
+		let prefix_codes_distances = distance_clusters.iter()
+			.map(|h| PrefixCode::write(out_stream, h, h.len()))
+			.collect::<Vec<_>>();
+
+		let mut pos = start;
+
+		for (command, &short_code) in commands.iter().zip(short_codes.iter()) {
+			let (insert_code, insert_extra, insert_n_extra) = length_code(&INSERT_LENGTH_CODES, command.insert_length);
+			let (copy_code, copy_extra, copy_n_extra) = length_code(&COPY_LENGTH_CODES, command.copy_length);
+			let implicit_distance = short_code == Some(0) && insert_code < 8 && copy_code < 16;
+
+			prefix_code_insert_and_copy_lengths.write_symbol(out_stream, insert_and_copy_length_symbol(insert_code, copy_code, implicit_distance));
+			out_stream.write_u32_to_n_bits(insert_extra, insert_n_extra);
+			out_stream.write_u32_to_n_bits(copy_extra, copy_n_extra);
+
+			for p in pos..(pos + command.insert_length) {
+				let i = p - self.buf_offset;
+				let p1 = if p > self.context_start { self.buf[i - 1] } else { 0 };
+				let p2 = if p >= self.context_start + 2 { self.buf[i - 2] } else { 0 };
+				let index = c_map_l[context_id(context_mode as ContextMode, p1, p2)] as usize;
+
+				prefix_codes_literals[index].write_symbol(out_stream, self.buf[i] as usize);
+			}
+
+			if command.copy_length > 0 && !implicit_distance {
+				let (code, extra, n_extra) = match short_code {
+					Some(code) => (code, 0, 0),
+					None => distance_code(command.distance, n_postfix, n_direct),
+				};
+				let index = c_map_d[distance_context_id(command.copy_length)] as usize;
+
+				prefix_codes_distances[index].write_symbol(out_stream, code);
+				out_stream.write_u32_to_n_bits(extra, n_extra);
+			}
+
+			pos += command.insert_length + command.copy_length;
+		}
+	}
+
+	/// Compresses all pending input, completes the stream, and returns the underlying writer.
+	/// A catable stream is only aligned to a byte boundary, it gets completed by brotli::concat().
+	pub fn finish(mut self) -> io::Result<W> {
+		let pending = self.buf_offset + self.buf.len() - self.pos;
+
+		if pending > 0 {
+			match self.compress_meta_block(pending, !self.catable) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		} else if !self.catable {
+			// ISLAST, ISLASTEMPTY
+			self.out_stream.write_bit(true);
+			self.out_stream.write_bit(true);
+		}
+
+		if self.catable && !self.out_stream.is_byte_aligned() {
+			write_empty_metadata_meta_block(&mut self.out_stream);
+		}
+
+		self.out_stream.write_u8_to_byte_tail(0);
+
+		match self.write_out() {
+			Ok(()) => {},
+			Err(e) => return Err(e),
+		}
+
+		match self.inner.flush() {
+			Ok(()) => Ok(self.inner),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+impl<W: Write> Write for Compressor<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.buf.extend_from_slice(buf);
+
+		while self.buf_offset + self.buf.len() - self.pos > META_BLOCK_SIZE {
+			match self.compress_meta_block(META_BLOCK_SIZE, false) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(buf.len())
+	}
+
+	/// Compresses all pending input into a meta-block of its own, and aligns the stream to
+	/// a byte boundary with an empty metadata meta-block. Everything written so far can then
+	/// be decompressed from the output, without waiting for any further data.
+	fn flush(&mut self) -> io::Result<()> {
+		let pending = self.buf_offset + self.buf.len() - self.pos;
+
+		if pending > 0 {
+			match self.compress_meta_block(pending, false) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		if !self.out_stream.is_byte_aligned() {
+			write_empty_metadata_meta_block(&mut self.out_stream);
+		}
+
+		match self.write_out() {
+			Ok(()) => self.inner.flush(),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+/// Returns an upper bound for the size of a Brotli stream that Compressor::new() produces for
+/// input_len bytes of input, if it is not flushed before it is finished.
+///
+/// A meta-block that does not compress is stored uncompressed instead, so the bound is the input
+/// length, plus a few bytes of meta-block header and alignment for every started meta-block,
+/// plus the stream header and the completion of the stream. Compressor::with_dictionary()
+/// writes a metadata meta-block with the id of the dictionary in addition, which takes up
+/// at most 39 more bytes: 36 bytes of metadata, and 3 bytes of header and alignment.
+pub fn max_compressed_size(input_len: usize) -> usize {
+	let n_meta_blocks = input_len.div_ceil(META_BLOCK_SIZE);
+
+	// WBITS, ISLAST and ISLASTEMPTY, and alignment take up at most 2 bytes, every uncompressed
+	// meta-block header along with its alignment at most 31 bits.
+	input_len + 4 * n_meta_blocks + 2
+}
+
+/// Returns the number of bits of an uncompressed meta-block of m_len bytes, starting at bit_pos,
+/// including the empty last meta-block that needs to follow, if is_last is true.
+fn uncompressed_meta_block_bits(bit_pos: usize, m_len: usize, is_last: bool) -> usize {
+	let mut trial = BitWriter::new();
+	trial.write_u32_to_n_bits(0, bit_pos % 8);
+
+	write_meta_block_header(&mut trial, m_len, false, true);
+	trial.write_u8_to_byte_tail(0);
+
+	trial.global_bit_pos() - bit_pos % 8 + 8 * m_len + if is_last { 2 } else { 0 }
+}
+
+fn write_meta_block_header(out_stream: &mut BitWriter, m_len: usize, is_last: bool, is_uncompressed: bool) {
+	out_stream.write_bit(is_last);
+
+	if is_last {
+		// ISLASTEMPTY
+		out_stream.write_bit(false);
+	}
+
+	let m_nibbles = match m_len - 1 {
+		0..=0xffff => 4,
+		0x10000..=0xfffff => 5,
+		_ => 6,
+	};
+
+	out_stream.write_u32_to_n_bits(m_nibbles - 4, 2);
+	out_stream.write_u32_to_n_nibbles(m_len as u32 - 1, m_nibbles as usize);
+
+	if !is_last {
+		out_stream.write_bit(is_uncompressed);
+	}
+}
+
+/// Writes a meta-block that holds no data and no metadata, only to fill up the
+/// current byte.
+pub fn write_empty_metadata_meta_block(out_stream: &mut BitWriter) {
+	write_metadata_meta_block(out_stream, &[]);
+}
+
+/// Writes a meta-block that holds no data, only metadata of at most 1 << 24 bytes, which decoders skip.
+pub fn write_metadata_meta_block(out_stream: &mut BitWriter, metadata: &[u8]) {
+	assert!(metadata.len() <= 1 << 24);
+
+	let m_skip_bytes = match metadata.len() {
+		0 => 0,
+		len => cmp::max(1, (32 - (len as u32 - 1).leading_zeros() as usize).div_ceil(8)),
+	};
+
+	// ISLAST
+	out_stream.write_bit(false);
+	// MNIBBLES, encoded as 3 for 0 nibbles
+	out_stream.write_u32_to_n_bits(3, 2);
+	// reserved
+	out_stream.write_bit(false);
+	// MSKIPBYTES
+	out_stream.write_u32_to_n_bits(m_skip_bytes as u32, 2);
+	// MSKIPLEN - 1
+	if m_skip_bytes > 0 {
+		out_stream.write_u32_to_n_bits(metadata.len() as u32 - 1, 8 * m_skip_bytes);
+	}
+
+	out_stream.write_u8_to_byte_tail(0);
+	out_stream.write_fixed_length_string(metadata);
+}
+
+/// Writes a value from 1 to 256, in the variable length code used for NBLTYPES and NTREES.
+fn write_n_bltypes(out_stream: &mut BitWriter, value: usize) {
+	let &(base, n_extra, bit_string) = BLTYPE_CODES.iter().rev().find(|&&(base, _, _)| base <= value).unwrap();
+
+	out_stream.write_bit_string(bit_string);
+	out_stream.write_u32_to_n_bits((value - base) as u32, n_extra);
+}
+
+/// Returns (code, extra bits, number of extra bits) for an insert length or a copy length.
+/// A copy length of 0 gets the shortest copy length code.
+fn length_code(codes: &[(u32, usize); 24], length: usize) -> (usize, u32, usize) {
+	match codes.iter().rposition(|&(base, _)| base as usize <= length) {
+		Some(code) => (code, length as u32 - codes[code].0, codes[code].1),
+		None => (0, 0, 0),
+	}
+}
+
+/// Returns the insert-and-copy length symbol, either with an explicit distance code,
+/// or with the implicit distance code 0, which requires insert_code < 8 and copy_code < 16.
+fn insert_and_copy_length_symbol(insert_code: usize, copy_code: usize, implicit_distance: bool) -> usize {
+	let base = match (insert_code >> 3, copy_code >> 3) {
+		(0, 0) if implicit_distance => 0,
+		(0, 1) if implicit_distance => 64,
+		(0, 0) => 128,
+		(0, 1) => 192,
+		(1, 0) => 256,
+		(1, 1) => 320,
+		(0, 2) => 384,
+		(2, 0) => 448,
+		(1, 2) => 512,
+		(2, 1) => 576,
+		(2, 2) => 640,
+		_ => unreachable!(), // confirmed unreachable, length codes are always < 24
+	};
+
+	base + ((insert_code & 0x07) << 3) + (copy_code & 0x07)
+}
+
+/// Pushes a distance to the last distances, the way the decoder does it for all distance
+/// codes but 0. Since code 0 is always used for a repetition of the last distance, this
+/// amounts to pushing every distance that differs from the last one.
+fn update_distance_cache(distance_cache: &mut [usize; 4], distance: usize) {
+	if distance != distance_cache[0] {
+		*distance_cache = [distance, distance_cache[0], distance_cache[1], distance_cache[2]];
+	}
+}
+
+/// Returns the distance code from 0 to 15 for a distance, if it can be coded with
+/// reference to the last distances. Unknown last distances are never referred to.
+fn short_distance_code(distance_cache: &[usize; 4], distance: usize) -> Option<usize> {
+	if let Some(code) = distance_cache.iter().position(|&d| d == distance) {
+		return Some(code);
+	}
+
+	// codes 4 to 9 are last distance -1, +1, -2, +2, -3, +3, codes 10 to 15 the same for the second-to-last
+	for (i, &d) in distance_cache[0..2].iter().enumerate().filter(|&(_, &d)| d > 0) {
+		for k in 1..4 {
+			if distance + k == d {
+				return Some(4 + 6 * i + 2 * (k - 1));
+			}
+
+			if distance == d + k {
+				return Some(5 + 6 * i + 2 * (k - 1));
+			}
+		}
+	}
+
+	None
+}
+
+/// Returns the short distance codes for the commands of a meta-block, starting with the given last distances.
+fn short_distance_codes(commands: &[Command], distance_cache: [usize; 4]) -> Vec<Option<usize>> {
+	let mut distance_cache = distance_cache;
+
+	commands.iter().map(|command| {
+		if command.copy_length == 0 {
+			return None;
+		}
+
+		let code = short_distance_code(&distance_cache, command.distance);
+		update_distance_cache(&mut distance_cache, command.distance);
+
+		code
+	}).collect::<Vec<_>>()
+}
+
+/// Returns (distance code, extra bits, number of extra bits) for a distance that
+/// is coded explicitly, i.e. not with reference to the last distances.
+fn distance_code(distance: usize, n_postfix: NPostfix, n_direct: NDirect) -> (usize, u32, usize) {
+	let n_direct = n_direct as usize;
+
+	if distance <= n_direct {
+		return (15 + distance, 0, 0);
+	}
+
+	let n_postfix = n_postfix as usize;
+	let d = (1 << (n_postfix + 2)) + distance - n_direct - 1;
+	let bucket = (0usize.leading_zeros() - d.leading_zeros() - 2) as usize;
+	let postfix = d & ((1 << n_postfix) - 1);
+	let prefix = (d >> bucket) & 1;
+	let offset = (2 + prefix) << bucket;
+	let n_extra = bucket - n_postfix;
+	let code = 16 + n_direct + (((2 * (n_extra - 1)) + prefix) << n_postfix) + postfix;
+
+	(code, ((d - offset) >> n_postfix) as u32, n_extra)
+}
+
+/// Chooses NPOSTFIX and NDIRECT for a meta-block, by estimating the cost of coding
+/// its distances with every valid combination of the two.
+fn choose_distance_parameters(commands: &[Command], short_codes: &[Option<usize>]) -> (NPostfix, NDirect) {
+	let mut best = None;
+
+	for n_postfix in 0..4 {
+		for n_direct_code in 0..16 {
+			let n_direct = n_direct_code << n_postfix;
+			let mut distance_histograms = vec![vec![0usize; 16 + n_direct as usize + (48 << n_postfix)]; 4];
+			let mut extra_bits = 0;
+
+			let explicit = commands.iter().zip(short_codes.iter())
+				.filter(|&(command, short_code)| command.copy_length > 0 && short_code.is_none());
+
+			for (command, _) in explicit {
+				let (code, _, n_extra) = distance_code(command.distance, n_postfix, n_direct);
+
+				distance_histograms[distance_context_id(command.copy_length)][code] += 1;
+				extra_bits += n_extra;
+			}
+
+			let cost = distance_histograms.iter().fold(extra_bits as f64, |acc, h| acc + histogram_cost(h));
+
+			best = match best {
+				Some((best_cost, _, _)) if best_cost <= cost => best,
+				_ => Some((cost, n_postfix, n_direct)),
+			};
+		}
+	}
+
+	let (_, n_postfix, n_direct) = best.unwrap();
+
+	(n_postfix, n_direct)
+}
+
+fn distance_context_id(copy_length: usize) -> usize {
+	match copy_length {
+		2..=4 => copy_length - 2,
+		_ => 3,
+	}
+}
+
+fn context_id(context_mode: ContextMode, p1: u8, p2: u8) -> usize {
+	let (p1, p2) = (p1 as usize, p2 as usize);
+
+	match context_mode {
+		0 => p1 & 0x3f,
+		1 => p1 >> 2,
+		2 => LUT_0[p1] | LUT_1[p2],
+		3 => (LUT_2[p1] << 3) | LUT_2[p2],
+		_ => unreachable!(), // confirmed unreachable, there are only four context modes
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_agree_with_fixed_bltype_code() {
+		use super::BLTYPE_CODES;
+		use ::Header;
+
+		let header = Header::new();
+
+		for &(base, _, bit_string) in BLTYPE_CODES.iter() {
+			assert_eq!(Some(bit_string.to_vec()), header.bltype_codes.bit_string_for_symbol(base as u16));
+		}
+	}
+
+	#[test]
+	fn should_agree_with_insert_and_copy_length_table() {
+		use super::{ insert_and_copy_length_symbol, length_code };
+		use ::lookuptable::{ INSERT_LENGTHS_AND_COPY_LENGTHS, INSERT_LENGTH_CODES, COPY_LENGTH_CODES };
+
+		for (insert_code, &insert_length_code) in INSERT_LENGTH_CODES.iter().enumerate() {
+			for (copy_code, &copy_length_code) in COPY_LENGTH_CODES.iter().enumerate() {
+				let symbol = insert_and_copy_length_symbol(insert_code, copy_code, false);
+
+				assert_eq!((insert_length_code, copy_length_code), INSERT_LENGTHS_AND_COPY_LENGTHS[symbol]);
+
+				if insert_code < 8 && copy_code < 16 {
+					let symbol = insert_and_copy_length_symbol(insert_code, copy_code, true);
+
+					assert!(symbol < 128);
+					assert_eq!((insert_length_code, copy_length_code), INSERT_LENGTHS_AND_COPY_LENGTHS[symbol]);
+				}
+			}
+		}
+
+		assert_eq!((7, 1, 1), length_code(&INSERT_LENGTH_CODES, 9));
+		assert_eq!((23, 1 << 20, 24), length_code(&COPY_LENGTH_CODES, 2118 + (1 << 20)));
+	}
+
+	#[test]
+	fn should_invert_distance_decoding() {
+		use super::distance_code;
+
+		for &(n_postfix, n_direct) in &[(0, 0), (1, 2), (2, 12), (3, 120)] {
+			for distance in 1..100000 {
+				let (dcode, dextra, ndistbits) = distance_code(distance, n_postfix, n_direct);
+
+				if distance <= n_direct as usize {
+					assert_eq!(distance + 15, dcode);
+					continue;
+				}
+
+				// decoding as specified in RFC 7932, section 4
+				let (dcode, n_direct, n_postfix) = (dcode as u32, n_direct as u32, n_postfix as u32);
+				let postfix_mask = (1 << n_postfix) - 1;
+				let hcode = (dcode - n_direct - 16) >> n_postfix;
+				let lcode = (dcode - n_direct - 16) & postfix_mask;
+				let offset = ((2 + (hcode & 1)) << (1 + (hcode >> 1))) - 4;
+
+				assert_eq!(1 + ((dcode - n_direct - 16) >> (n_postfix + 1)), ndistbits as u32);
+				assert_eq!(distance as u32, ((offset + dextra) << n_postfix) + lcode + n_direct + 1);
+			}
+		}
+	}
+
+	#[test]
+	fn should_choose_postfix_for_aligned_distances() {
+		use super::{ choose_distance_parameters, Command };
+
+		let commands = (1..2000).map(|i| Command{
+			insert_length: 1,
+			copy_length: 4 + i % 7,
+			distance: 8 * (1 + (i * 7919) % 500),
+		}).collect::<Vec<_>>();
+
+		assert_eq!(3, choose_distance_parameters(&commands, &vec![None; commands.len()]).0);
+	}
+
+	#[test]
+	fn should_decode_all_distance_parameters() {
+		use super::{ short_distance_codes, Compressor, MetaBlockCommands, INITIAL_DISTANCE_CACHE };
+		use ::Decompressor;
+		use std::io::Read;
+
+		// fixed-width records with a varying field, which makes for many different distances
+		let input = (0..3000).flat_map(|i| format!("{:05}|record|{:03}|", (i * 7919) % 100000, i % 37).into_bytes()).collect::<Vec<_>>();
+
+		for n_postfix in 0..4 {
+			for n_direct_code in 0..16 {
+				let n_direct = n_direct_code << n_postfix;
+				let mut compressor = Compressor::new(Vec::new());
+				compressor.buf = input.clone();
+
+				let commands = compressor.matcher.find_commands(&compressor.buf, 0, 0, input.len(), &mut compressor.distance_cache);
+				let short_codes = short_distance_codes(&commands, INITIAL_DISTANCE_CACHE);
+				compressor.write_compressed_meta_block(0, input.len(), MetaBlockCommands{
+					commands: &commands,
+					short_codes: &short_codes,
+					n_postfix,
+					n_direct,
+				}, false);
+				compressor.pos = input.len();
+
+				let brotli_stream = compressor.finish().unwrap();
+				let mut decompressed = Vec::new();
+				let _ = Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed);
+
+				assert_eq!(input, decompressed);
+			}
+		}
+	}
+
+	#[test]
+	fn should_find_short_distance_codes() {
+		use super::{ short_distance_code, update_distance_cache, INITIAL_DISTANCE_CACHE };
+
+		let mut distance_cache = INITIAL_DISTANCE_CACHE;
+
+		assert_eq!(Some(0), short_distance_code(&distance_cache, 4));
+		assert_eq!(Some(3), short_distance_code(&distance_cache, 16));
+		assert_eq!(Some(4), short_distance_code(&distance_cache, 3));
+		assert_eq!(Some(9), short_distance_code(&distance_cache, 7));
+		assert_eq!(Some(13), short_distance_code(&distance_cache, 13));
+		assert_eq!(None, short_distance_code(&distance_cache, 100));
+
+		update_distance_cache(&mut distance_cache, 100);
+		update_distance_cache(&mut distance_cache, 100);
+
+		assert_eq!([100, 4, 11, 15], distance_cache);
+		assert_eq!(Some(11), short_distance_code(&distance_cache, 5));
+	}
+
+	#[test]
+	fn should_fall_back_to_uncompressed_meta_blocks() {
+		use super::{ max_compressed_size, Compressor, META_BLOCK_SIZE };
+		use ::Decompressor;
+		use std::io::{ Read, Write };
+
+		let mut seed = 1u32;
+		let input = (0..(2 * META_BLOCK_SIZE + 1000)).map(|_| {
+			seed ^= seed << 13;
+			seed ^= seed >> 17;
+			seed ^= seed << 5;
+			(seed >> 24) as u8
+		}).collect::<Vec<_>>();
+
+		for &len in &[1, 100, 5000, META_BLOCK_SIZE, input.len()] {
+			let mut compressor = Compressor::new(Vec::new());
+			compressor.write_all(&input[0..len]).unwrap();
+			let brotli_stream = compressor.finish().unwrap();
+
+			assert!(brotli_stream.len() <= max_compressed_size(len));
+
+			let mut decompressed = Vec::new();
+			let _ = Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed);
+
+			assert_eq!(&input[0..len], &decompressed[..]);
+
+			let mut compressor = Compressor::with_dictionary(Vec::new(), b"dictionary");
+			compressor.write_all(&input[0..len]).unwrap();
+			let brotli_stream = compressor.finish().unwrap();
+
+			assert!(brotli_stream.len() <= max_compressed_size(len) + 39);
+		}
+	}
+}
@@ -0,0 +1,416 @@
+use ::bitwriter::BitWriter;
+use ::huffman;
+
+/// Order in which the code lengths of the code length alphabet appear in a complex prefix code.
+const CODE_LENGTH_CODE_ORDER: [usize; 18] = [1, 2, 3, 4, 0, 5, 17, 6, 16, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// Fixed variable length code for the code lengths of the code length alphabet,
+/// indexed by code length, equivalent to Header::bit_lengths_code.
+const CODE_LENGTH_CODE_LENGTH_BIT_STRINGS: [&[bool]; 6] = [
+	&[false, false],
+	&[true, true, true, false],
+	&[true, true, false],
+	&[false, true],
+	&[true, false],
+	&[true, true, true, true],
+];
+
+/// A prefix code, as it is used by the compressor to write symbols.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefixCode {
+	bit_strings: Vec<Vec<bool>>,
+}
+
+impl PrefixCode {
+	/// Builds a prefix code for the histogram, and writes its description to the stream.
+	/// Symbols with a frequency of zero can not be written with the resulting code.
+	///
+	/// Histograms with up to four used symbols get a simple prefix code, unless
+	/// a complex prefix code turns out to be cheaper.
+	pub fn write(out_stream: &mut BitWriter, histogram: &[usize], alphabet_size: usize) -> PrefixCode {
+		let histogram = &histogram[0..alphabet_size];
+		let symbols = histogram.iter().enumerate()
+			.filter(|&(_, &f)| f > 0)
+			.map(|(symbol, _)| symbol)
+			.collect::<Vec<_>>();
+
+		if symbols.len() < 2 {
+			let symbol = if symbols.is_empty() { 0 } else { symbols[0] };
+
+			write_simple_prefix_code(out_stream, &[symbol], false, alphabet_size);
+
+			return PrefixCode{
+				bit_strings: vec![Vec::new(); alphabet_size],
+			};
+		}
+
+		let complex_lengths = huffman::lengths_from_frequencies(histogram, 15);
+
+		if symbols.len() <= 4 {
+			let (ordered_symbols, tree_select) = simple_prefix_code_symbols(histogram, &symbols);
+			let simple_lengths = simple_prefix_code_lengths(alphabet_size, &ordered_symbols, tree_select);
+
+			let mut trial = BitWriter::new();
+			write_complex_prefix_code(&mut trial, &complex_lengths);
+
+			let simple_cost = 4 + ordered_symbols.len() * alphabet_bit_width(alphabet_size)
+				+ if tree_select { 1 } else { 0 } + symbols_cost(histogram, &simple_lengths);
+			let complex_cost = trial.global_bit_pos() + symbols_cost(histogram, &complex_lengths);
+
+			if simple_cost <= complex_cost {
+				write_simple_prefix_code(out_stream, &ordered_symbols, tree_select, alphabet_size);
+
+				return PrefixCode{
+					bit_strings: huffman::bit_strings_from_lengths(&simple_lengths),
+				};
+			}
+		}
+
+		write_complex_prefix_code(out_stream, &complex_lengths);
+
+		PrefixCode{
+			bit_strings: huffman::bit_strings_from_lengths(&complex_lengths),
+		}
+	}
+
+	/// Writes the code for one symbol to the stream.
+	pub fn write_symbol(&self, out_stream: &mut BitWriter, symbol: usize) {
+		out_stream.write_bit_string(&self.bit_strings[symbol]);
+	}
+}
+
+fn alphabet_bit_width(alphabet_size: usize) -> usize {
+	(0usize.leading_zeros() - (alphabet_size - 1).leading_zeros()) as usize
+}
+
+fn symbols_cost(histogram: &[usize], lengths: &[usize]) -> usize {
+	histogram.iter().zip(lengths.iter()).fold(0, |acc, (&f, &len)| acc + f * len)
+}
+
+/// Orders two to four used symbols in the way a simple prefix code lists them, most frequent first,
+/// and decides on the tree shape for four symbols, i.e. the value of the tree-select bit.
+fn simple_prefix_code_symbols(histogram: &[usize], symbols: &[usize]) -> (Vec<usize>, bool) {
+	let mut ordered_symbols = symbols.to_vec();
+	ordered_symbols.sort_by(|&a, &b| histogram[b].cmp(&histogram[a]).then(a.cmp(&b)));
+
+	let tree_select = ordered_symbols.len() == 4 && {
+		let f = ordered_symbols.iter().map(|&symbol| histogram[symbol]).collect::<Vec<_>>();
+
+		f[0] + 2 * f[1] + 3 * (f[2] + f[3]) < 2 * (f[0] + f[1] + f[2] + f[3])
+	};
+
+	(ordered_symbols, tree_select)
+}
+
+/// Returns the code lengths the decoder assigns to the symbols of a simple prefix code.
+fn simple_prefix_code_lengths(alphabet_size: usize, ordered_symbols: &[usize], tree_select: bool) -> Vec<usize> {
+	let shape: &[usize] = match (ordered_symbols.len(), tree_select) {
+		(2, _) => &[1, 1],
+		(3, _) => &[1, 2, 2],
+		(4, false) => &[2, 2, 2, 2],
+		(4, true) => &[1, 2, 3, 3],
+		_ => unreachable!(), // only called for two to four symbols
+	};
+
+	let mut lengths = vec![0; alphabet_size];
+	for (&symbol, &len) in ordered_symbols.iter().zip(shape.iter()) {
+		lengths[symbol] = len;
+	}
+
+	lengths
+}
+
+fn write_simple_prefix_code(out_stream: &mut BitWriter, symbols: &[usize], tree_select: bool, alphabet_size: usize) {
+	let bit_width = alphabet_bit_width(alphabet_size);
+
+	out_stream.write_u32_to_n_bits(1, 2);
+	out_stream.write_u32_to_n_bits(symbols.len() as u32 - 1, 2);
+
+	for &symbol in symbols {
+		out_stream.write_u32_to_n_bits(symbol as u32, bit_width);
+	}
+
+	if symbols.len() == 4 {
+		out_stream.write_bit(tree_select);
+	}
+}
+
+/// Run-length encodes code lengths with the repeat codes 16 and 17, returns (symbol, extra bits, number of extra bits).
+fn run_length_encode(lengths: &[usize], repeat_non_zero: bool, repeat_zero: bool) -> Vec<(usize, u32, usize)> {
+	let mut symbols = Vec::with_capacity(lengths.len());
+	let mut previous_non_zero = 8;
+	let mut i = 0;
+
+	while i < lengths.len() {
+		let value = lengths[i];
+		let mut run = lengths[i..].iter().take_while(|&&l| l == value).count();
+		i += run;
+
+		if value == 0 && repeat_zero {
+			// 11 zeros would take two repeat codes, but 1 + 10 only one
+			if run == 11 {
+				symbols.push((0, 0, 0));
+				run -= 1;
+			}
+
+			push_repeat_codes(&mut symbols, run, 0, 17, 3);
+		} else if value > 0 && repeat_non_zero {
+			if value != previous_non_zero {
+				symbols.push((value, 0, 0));
+				run -= 1;
+			}
+
+			// 7 repetitions would take two repeat codes, but 1 + 6 only one
+			if run == 7 {
+				symbols.push((value, 0, 0));
+				run -= 1;
+			}
+
+			push_repeat_codes(&mut symbols, run, value, 16, 2);
+		} else {
+			for _ in 0..run {
+				symbols.push((value, 0, 0));
+			}
+		}
+
+		if value > 0 {
+			previous_non_zero = value;
+		}
+	}
+
+	symbols
+}
+
+/// Pushes codes for run repetitions of value, using a chain of repeat codes, if the run is long enough.
+/// Consecutive repeat codes multiply the repetition count of their predecessors by 1 << n_extra.
+fn push_repeat_codes(symbols: &mut Vec<(usize, u32, usize)>, run: usize, value: usize, repeat_code: usize, n_extra: usize) {
+	if run < 3 {
+		for _ in 0..run {
+			symbols.push((value, 0, 0));
+		}
+
+		return;
+	}
+
+	let start = symbols.len();
+	let mut repetitions = run - 3;
+
+	loop {
+		symbols.push((repeat_code, (repetitions & ((1 << n_extra) - 1)) as u32, n_extra));
+		repetitions >>= n_extra;
+
+		if repetitions == 0 {
+			break;
+		}
+
+		repetitions -= 1;
+	}
+
+	symbols[start..].reverse();
+}
+
+fn write_complex_prefix_code_with(out_stream: &mut BitWriter, symbols: &[(usize, u32, usize)]) {
+	let mut code_length_histogram = [0usize; 18];
+	for &(symbol, _, _) in symbols {
+		code_length_histogram[symbol] += 1;
+	}
+
+	let code_length_lengths = huffman::lengths_from_frequencies(&code_length_histogram, 5);
+	let n_non_zero = code_length_lengths.iter().filter(|&&l| l > 0).count();
+
+	// HSKIP, the number of leading code lengths of the code length alphabet that are implicitly zero
+	let h_skip = CODE_LENGTH_CODE_ORDER[0..3].iter().take_while(|&&symbol| code_length_lengths[symbol] == 0).count();
+	let h_skip = if h_skip == 1 { 0 } else { h_skip };
+
+	out_stream.write_u32_to_n_bits(h_skip as u32, 2);
+
+	// With only one code length in use, its code is empty, and all 18 code
+	// lengths of the code length alphabet have to be present in the stream.
+	let n_code_length_lengths = if n_non_zero == 1 {
+		18
+	} else {
+		CODE_LENGTH_CODE_ORDER.iter().rposition(|&symbol| code_length_lengths[symbol] > 0).unwrap() + 1
+	};
+
+	for &symbol in &CODE_LENGTH_CODE_ORDER[h_skip..n_code_length_lengths] {
+		out_stream.write_bit_string(CODE_LENGTH_CODE_LENGTH_BIT_STRINGS[code_length_lengths[symbol]]);
+	}
+
+	let code_length_bit_strings = if n_non_zero == 1 {
+		vec![Vec::new(); 18]
+	} else {
+		huffman::bit_strings_from_lengths(&code_length_lengths)
+	};
+
+	for &(symbol, extra, n_extra) in symbols {
+		out_stream.write_bit_string(&code_length_bit_strings[symbol]);
+		out_stream.write_u32_to_n_bits(extra, n_extra);
+	}
+}
+
+/// Writes a complex prefix code for the code lengths, in the most compact of its possible
+/// encodings with regards to the use of the repeat codes 16 and 17.
+fn write_complex_prefix_code(out_stream: &mut BitWriter, lengths: &[usize]) {
+	// The decoder stops reading code lengths as soon as the code is complete,
+	// so zero lengths after the last used symbol are implicit.
+	let len = lengths.iter().rposition(|&l| l > 0).unwrap() + 1;
+
+	let mut best = None;
+
+	for &(repeat_non_zero, repeat_zero) in &[(false, false), (false, true), (true, false), (true, true)] {
+		let symbols = run_length_encode(&lengths[0..len], repeat_non_zero, repeat_zero);
+		let mut trial = BitWriter::new();
+		write_complex_prefix_code_with(&mut trial, &symbols);
+
+		best = match best {
+			Some((bits, _)) if bits <= trial.global_bit_pos() => best,
+			_ => Some((trial.global_bit_pos(), symbols)),
+		};
+	}
+
+	write_complex_prefix_code_with(out_stream, &best.unwrap().1);
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_agree_with_fixed_code_length_code() {
+		use super::CODE_LENGTH_CODE_LENGTH_BIT_STRINGS;
+		use ::Header;
+
+		let header = Header::new();
+
+		for (length, bit_string) in CODE_LENGTH_CODE_LENGTH_BIT_STRINGS.iter().enumerate() {
+			assert_eq!(Some(bit_string.to_vec()), header.bit_lengths_code.bit_string_for_symbol(length as u16));
+		}
+	}
+
+	#[test]
+	fn should_round_trip_prefix_codes() {
+		use super::PrefixCode;
+		use ::bitwriter::BitWriter;
+		use ::Decompressor;
+		use std::io::Cursor;
+
+		let histograms = vec![
+			(vec![0, 0, 7, 0], 4),
+			(vec![1, 1], 2),
+			(vec![0, 3, 0, 9, 1], 5),
+			(vec![4, 3, 2, 2], 4),
+			(vec![0, 0, 100, 0, 1, 30, 0, 1], 8),
+			(vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1], 704),
+			(vec![5, 0, 0, 9, 1, 1, 0, 30, 2, 2, 2, 0, 0, 0, 0, 0, 1], 26),
+			((0..256).map(|i| i % 7 + 1).collect::<Vec<_>>(), 256),
+			((0..704).map(|i| if i % 3 == 0 { 0 } else { 1 << (i % 20) }).collect::<Vec<_>>(), 704),
+		];
+
+		for (histogram, alphabet_size) in histograms {
+			let mut histogram = histogram;
+			histogram.resize(alphabet_size, 0);
+
+			let mut bw = BitWriter::new();
+			let prefix_code = PrefixCode::write(&mut bw, &histogram, alphabet_size);
+			for (symbol, &f) in histogram.iter().enumerate() {
+				if f > 0 {
+					prefix_code.write_symbol(&mut bw, symbol);
+				}
+			}
+			bw.write_u32_to_n_bits(0, 15);
+			bw.write_u8_to_byte_tail(0);
+
+			let mut decompressor = Decompressor::new(Cursor::new(bw.take_bytes()));
+			let tree = decompressor.parse_prefix_code(alphabet_size).unwrap();
+
+			for (symbol, &f) in histogram.iter().enumerate() {
+				if f > 0 {
+					assert_eq!(Ok(Some(symbol as u16)), tree.lookup_symbol(&mut decompressor.in_stream));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn should_prefer_simple_prefix_codes() {
+		use super::PrefixCode;
+		use ::bitwriter::BitWriter;
+
+		let mut histogram = vec![0; 256];
+		histogram[b'a' as usize] = 100;
+		histogram[b'b' as usize] = 10;
+		histogram[b'c' as usize] = 5;
+		histogram[b'd' as usize] = 5;
+
+		let mut bw = BitWriter::new();
+		let prefix_code = PrefixCode::write(&mut bw, &histogram, 256);
+
+		// HSKIP 1, NSYM - 1, four 8-bit symbols, tree-select
+		assert_eq!(2 + 2 + 4 * 8 + 1, bw.global_bit_pos());
+		assert_eq!(vec![false], prefix_code.bit_strings[b'a' as usize]);
+		assert_eq!(3, prefix_code.bit_strings[b'd' as usize].len());
+	}
+
+	#[test]
+	fn should_accept_all_generated_codes() {
+		use super::PrefixCode;
+		use ::bitwriter::BitWriter;
+		use ::Decompressor;
+		use std::io::Cursor;
+
+		let mut seed = 12345usize;
+		let mut next = || {
+			seed = seed.wrapping_mul(1103515245).wrapping_add(12345) % (1 << 31);
+			seed >> 8
+		};
+
+		for &alphabet_size in &[2, 5, 26, 64, 256, 704] {
+			for round in 0..40 {
+				// alternate between sparse histograms with long zero runs, flat ones,
+				// and ones with widely spread frequencies
+				let histogram = (0..alphabet_size).map(|_| match round % 4 {
+					0 => if next() % 8 == 0 { 1 + next() % 100 } else { 0 },
+					1 => 50 + next() % 3,
+					2 => if next() % 3 == 0 { 0 } else { 1 << (next() % 16) },
+					_ => next() % 5,
+				}).collect::<Vec<_>>();
+
+				let mut bw = BitWriter::new();
+				let prefix_code = PrefixCode::write(&mut bw, &histogram, alphabet_size);
+				for (symbol, &f) in histogram.iter().enumerate() {
+					if f > 0 {
+						prefix_code.write_symbol(&mut bw, symbol);
+					}
+				}
+				bw.write_u32_to_n_bits(0, 15);
+				bw.write_u8_to_byte_tail(0);
+
+				let mut decompressor = Decompressor::new(Cursor::new(bw.take_bytes()));
+				let tree = match decompressor.parse_prefix_code(alphabet_size) {
+					Ok(tree) => tree,
+					Err(e) => panic!("{:?} for histogram {:?}", e, histogram),
+				};
+
+				for (symbol, &f) in histogram.iter().enumerate() {
+					if f > 0 {
+						assert_eq!(Ok(Some(symbol as u16)), tree.lookup_symbol(&mut decompressor.in_stream));
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn should_use_repeat_codes() {
+		use super::{ run_length_encode, PrefixCode };
+		use ::bitwriter::BitWriter;
+
+		assert_eq!(vec![(3, 0, 0), (16, 1, 2), (17, 0, 3), (17, 1, 3), (8, 0, 0)], run_length_encode(&[3, 3, 3, 3, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8], true, true));
+		assert_eq!(vec![(16, 3, 2), (16, 2, 2)], run_length_encode(&[8; 21], true, false));
+		assert_eq!(vec![(0, 0, 0), (17, 7, 3)], run_length_encode(&[0; 11], false, true));
+
+		// a flat histogram has 256 code lengths of 8, all covered by repeat codes
+		let mut bw = BitWriter::new();
+		let _ = PrefixCode::write(&mut bw, &vec![1; 256], 256);
+
+		assert!(bw.global_bit_pos() < 40);
+	}
+}
@@ -0,0 +1,122 @@
+use ::bitreader::BitReader;
+use ::bitwriter::BitWriter;
+use ::compressor::write_empty_metadata_meta_block;
+use ::{ Header, WBits };
+
+use std::cmp;
+use std::io;
+
+/// Joins catable Brotli streams, as they are produced by Compressor::new_catable(), into one
+/// complete Brotli stream, which decompresses to the concatenation of their contents.
+///
+/// The header of every stream is replaced by a single header, with the largest window size
+/// of all the streams, and the joined stream is completed with an empty last meta-block.
+/// Returns an error with kind InvalidInput, if one of the streams is not a catable stream.
+///
+/// # Examples
+/// ```
+/// use std::io::{ Read, Write };
+/// use brotli::{ concat, Compressor, Decompressor };
+///
+/// let mut streams = Vec::new();
+/// for part in &[&b"Hello, "[..], &b"World!"[..]] {
+///     let mut compressor = Compressor::new_catable(Vec::new());
+///     compressor.write_all(part).unwrap();
+///     streams.push(compressor.finish().unwrap());
+/// }
+///
+/// let brotli_stream = concat(&streams).unwrap();
+///
+/// let mut decompressed = &mut Vec::new();
+/// let _ = Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed);
+///
+/// assert_eq!(b"Hello, World!".to_vec(), *decompressed);
+/// ```
+pub fn concat<S: AsRef<[u8]>>(streams: &[S]) -> io::Result<Vec<u8>> {
+	let header = Header::new();
+	let mut wbits = None;
+	let mut bodies = Vec::with_capacity(streams.len());
+
+	for stream in streams {
+		let stream = stream.as_ref();
+		let (stream_wbits, header_len) = match parse_catable_header(&header, stream) {
+			Some(result) => result,
+			None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Stream is not a catable Brotli stream")),
+		};
+
+		wbits = Some(cmp::max(wbits.unwrap_or(stream_wbits), stream_wbits));
+		bodies.push(&stream[header_len..]);
+	}
+
+	let mut out_stream = BitWriter::new();
+	// without any streams, pick the shortest code for WBITS
+	let wbits = wbits.unwrap_or(16);
+	out_stream.write_bit_string(&header.wbits_codes.bit_string_for_symbol(wbits as u16).unwrap());
+
+	if !bodies.is_empty() {
+		write_empty_metadata_meta_block(&mut out_stream);
+	}
+
+	for body in bodies {
+		out_stream.write_fixed_length_string(body);
+	}
+
+	// ISLAST, ISLASTEMPTY
+	out_stream.write_bit(true);
+	out_stream.write_bit(true);
+	out_stream.write_u8_to_byte_tail(0);
+
+	Ok(out_stream.take_bytes())
+}
+
+/// Returns WBITS and the length in bytes of the header of a catable stream, i.e. of
+/// WBITS followed by an empty metadata meta-block that fills up the last byte.
+pub fn parse_catable_header(header: &Header, stream: &[u8]) -> Option<(WBits, usize)> {
+	let mut in_stream = BitReader::new(stream);
+
+	let wbits = match header.wbits_codes.lookup_symbol(&mut in_stream) {
+		Ok(Some(wbits)) => wbits,
+		_ => return None,
+	};
+
+	// ISLAST, MNIBBLES, reserved bit, MSKIPBYTES, fill bits
+	match (in_stream.read_bit(), in_stream.read_u8_from_n_bits(2), in_stream.read_bit(),
+			in_stream.read_u8_from_n_bits(2), in_stream.read_u8_from_byte_tail()) {
+		(Ok(false), Ok(3), Ok(false), Ok(0), Ok(0)) => {},
+		_ => return None,
+	}
+
+	let wbits_len = header.wbits_codes.bit_string_for_symbol(wbits).unwrap().len();
+
+	Some((wbits as WBits, (wbits_len + 6).div_ceil(8)))
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_reject_complete_streams() {
+		use super::concat;
+		use ::Compressor;
+		use std::io::Write;
+
+		let mut compressor = Compressor::new(Vec::new());
+		compressor.write_all(b"not catable").unwrap();
+		let brotli_stream = compressor.finish().unwrap();
+
+		assert!(concat(&[brotli_stream]).is_err());
+	}
+
+	#[test]
+	fn should_concat_no_streams() {
+		use super::concat;
+		use ::Decompressor;
+		use std::io::Read;
+
+		let brotli_stream = concat::<Vec<u8>>(&[]).unwrap();
+		let mut decompressed = Vec::new();
+
+		assert_eq!(vec![0x06], brotli_stream);
+		assert!(Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed).is_ok());
+		assert!(decompressed.is_empty());
+	}
+}
@@ -0,0 +1,87 @@
+/// Reversed polynomial of CRC-32, as used by gzip, zip and png.
+const POLYNOMIAL: u32 = 0xedb88320;
+
+fn table() -> [u32; 256] {
+	let mut table = [0u32; 256];
+
+	for (n, entry) in table.iter_mut().enumerate() {
+		let mut c = n as u32;
+
+		for _ in 0..8 {
+			c = if c & 1 == 1 { POLYNOMIAL ^ (c >> 1) } else { c >> 1 };
+		}
+
+		*entry = c;
+	}
+
+	table
+}
+
+/// Computes a CRC-32 checksum incrementally, over data that arrives in pieces.
+#[derive(Clone)]
+pub struct Crc32 {
+	table: [u32; 256],
+	crc: u32,
+}
+
+impl ::std::fmt::Debug for Crc32 {
+	fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+		write!(fmt, "Crc32 {{ crc: {:08x} }}", self.sum())
+	}
+}
+
+impl Crc32 {
+	/// Creates Crc32 for empty data.
+	pub fn new() -> Crc32 {
+		Crc32{
+			table: table(),
+			crc: 0xffffffff,
+		}
+	}
+
+	/// Adds data to the checksum.
+	pub fn update(&mut self, data: &[u8]) {
+		for &byte in data {
+			self.crc = self.table[((self.crc ^ byte as u32) & 0xff) as usize] ^ (self.crc >> 8);
+		}
+	}
+
+	/// Returns the checksum of all data so far.
+	pub fn sum(&self) -> u32 {
+		self.crc ^ 0xffffffff
+	}
+}
+
+/// Returns the CRC-32 checksum of data.
+pub fn crc32(data: &[u8]) -> u32 {
+	let mut crc = Crc32::new();
+	crc.update(data);
+
+	crc.sum()
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_checksum_check_values() {
+		use super::crc32;
+
+		assert_eq!(0x00000000, crc32(b""));
+		assert_eq!(0xcbf43926, crc32(b"123456789"));
+		assert_eq!(0x414fa339, crc32(b"The quick brown fox jumps over the lazy dog"));
+	}
+
+	#[test]
+	fn should_checksum_data_in_pieces() {
+		use super::{ crc32, Crc32 };
+
+		let data = b"The quick brown fox jumps over the lazy dog";
+		let mut crc = Crc32::new();
+
+		for piece in data.chunks(5) {
+			crc.update(piece);
+		}
+
+		assert_eq!(crc32(data), crc.sum());
+	}
+}
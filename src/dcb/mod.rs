@@ -0,0 +1,228 @@
+use ::compressor::Compressor;
+use ::sha256::sha256;
+use ::Decompressor;
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{ Read, Write };
+
+/// Magic bytes at the start of a Dictionary-Compressed Brotli stream, followed by
+/// the SHA-256 digest of the dictionary.
+const DCB_MAGIC: [u8; 4] = [0xff, b'D', b'C', b'B'];
+
+/// Dictionaries for DcbDecompressor, registered by their SHA-256 digests.
+///
+/// # Examples
+/// ```
+/// use std::io::{ Read, Write };
+/// use brotli::{ DcbCompressor, DcbDecompressor, DcbDictionaries };
+///
+/// let dictionary = b"function f(a, b) { return a + b; }";
+///
+/// let mut compressor = DcbCompressor::new(Vec::new(), dictionary).unwrap();
+/// compressor.write_all(b"function g(a, b) { return a * b; }").unwrap();
+/// let dcb_stream = compressor.finish().unwrap();
+///
+/// let mut dictionaries = DcbDictionaries::new();
+/// dictionaries.register(dictionary);
+///
+/// let mut decompressed = Vec::new();
+/// let _ = DcbDecompressor::new(&dcb_stream[..], &dictionaries).unwrap().read_to_end(&mut decompressed);
+///
+/// assert_eq!(b"function g(a, b) { return a * b; }".to_vec(), decompressed);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DcbDictionaries {
+	by_hash: HashMap<[u8; 32], Vec<u8>>,
+}
+
+impl DcbDictionaries {
+	/// Creates an empty DcbDictionaries.
+	pub fn new() -> DcbDictionaries {
+		DcbDictionaries{
+			by_hash: HashMap::new(),
+		}
+	}
+
+	/// Registers a dictionary, and returns its SHA-256 digest.
+	pub fn register(&mut self, dictionary: &[u8]) -> [u8; 32] {
+		let hash = sha256(dictionary);
+
+		self.by_hash.insert(hash, dictionary.to_vec());
+
+		hash
+	}
+
+	/// Returns the dictionary with the given SHA-256 digest, if it has been registered.
+	pub fn get(&self, hash: &[u8; 32]) -> Option<&[u8]> {
+		self.by_hash.get(hash).map(|dictionary| &dictionary[..])
+	}
+}
+
+/// Wraps an output stream, and writes a Dictionary-Compressed Brotli stream to it, as it is
+/// defined by HTTP Compression Dictionary Transport: the dcb header with the SHA-256 digest
+/// of the dictionary, followed by a Brotli stream that is compressed with the dictionary as a
+/// raw dictionary. The stream needs to be completed with finish().
+#[derive(Debug)]
+pub struct DcbCompressor<W: Write> {
+	compressor: Compressor<W>,
+}
+
+impl<W: Write> DcbCompressor<W> {
+	/// Creates DcbCompressor from Write, and writes the dcb header.
+	pub fn new(mut w: W, dictionary: &[u8]) -> io::Result<DcbCompressor<W>> {
+		match w.write_all(&DCB_MAGIC).and_then(|_| w.write_all(&sha256(dictionary))) {
+			Ok(()) => {},
+			Err(e) => return Err(e),
+		}
+
+		// The dcb header already identifies the dictionary.
+		Ok(DcbCompressor{
+			compressor: Compressor::with_options(w, false, dictionary, false),
+		})
+	}
+
+	/// Compresses all pending input, completes the stream, and returns the underlying writer.
+	pub fn finish(self) -> io::Result<W> {
+		self.compressor.finish()
+	}
+}
+
+impl<W: Write> Write for DcbCompressor<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.compressor.write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.compressor.flush()
+	}
+}
+
+/// Wraps an input stream of Dictionary-Compressed Brotli, and decompresses it with the
+/// dictionary that its dcb header refers to.
+#[derive(Debug)]
+pub struct DcbDecompressor<R: Read> {
+	decompressor: Decompressor<R>,
+}
+
+impl<R: Read> DcbDecompressor<R> {
+	/// Creates DcbDecompressor from Read, and reads the dcb header. Returns an error with kind
+	/// InvalidData, if the stream does not start with a dcb header, or if its dictionary has
+	/// not been registered.
+	pub fn new(r: R, dictionaries: &DcbDictionaries) -> io::Result<DcbDecompressor<R>> {
+		let (r, hash) = match read_header(r) {
+			Ok(result) => result,
+			Err(e) => return Err(e),
+		};
+
+		match dictionaries.get(&hash) {
+			Some(dictionary) => Ok(DcbDecompressor{
+				decompressor: Decompressor::with_dictionary(r, dictionary),
+			}),
+			None => Err(io::Error::new(io::ErrorKind::InvalidData, "Stream has been compressed with a dictionary that has not been registered")),
+		}
+	}
+
+	/// Creates DcbDecompressor from Read, for a single dictionary, and reads the dcb header.
+	/// Returns an error with kind InvalidData, if the stream does not start with a dcb header,
+	/// or if it has been compressed with a different dictionary.
+	pub fn with_dictionary(r: R, dictionary: &[u8]) -> io::Result<DcbDecompressor<R>> {
+		let (r, hash) = match read_header(r) {
+			Ok(result) => result,
+			Err(e) => return Err(e),
+		};
+
+		if hash != sha256(dictionary) {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "Stream has been compressed with a different dictionary than the given one"));
+		}
+
+		Ok(DcbDecompressor{
+			decompressor: Decompressor::with_dictionary(r, dictionary),
+		})
+	}
+}
+
+impl<R: Read> Read for DcbDecompressor<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.decompressor.read(buf)
+	}
+}
+
+/// Reads the dcb header, and returns the stream along with the SHA-256 digest from the header.
+fn read_header<R: Read>(mut r: R) -> io::Result<(R, [u8; 32])> {
+	let mut header = [0; 36];
+
+	match r.read_exact(&mut header) {
+		Ok(()) => {},
+		Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "Stream does not start with a dcb header"));
+		},
+		Err(e) => return Err(e),
+	}
+
+	if header[..4] != DCB_MAGIC {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "Stream does not start with a dcb header"));
+	}
+
+	let mut hash = [0; 32];
+	hash.copy_from_slice(&header[4..]);
+
+	Ok((r, hash))
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_write_dcb_header() {
+		use super::DcbCompressor;
+		use ::sha256::sha256;
+
+		let dcb_stream = DcbCompressor::new(Vec::new(), b"dictionary").unwrap().finish().unwrap();
+
+		assert_eq!(&[0xff, b'D', b'C', b'B'], &dcb_stream[..4]);
+		assert_eq!(&sha256(b"dictionary")[..], &dcb_stream[4..36]);
+	}
+
+	#[test]
+	fn should_reject_unknown_dictionaries() {
+		use super::{ DcbCompressor, DcbDecompressor, DcbDictionaries };
+		use std::io::Write;
+
+		let mut compressor = DcbCompressor::new(Vec::new(), b"Hello, World!").unwrap();
+		compressor.write_all(b"Hello, World! Hello, World!").unwrap();
+		let dcb_stream = compressor.finish().unwrap();
+
+		let mut dictionaries = DcbDictionaries::new();
+		dictionaries.register(b"Hello, World?");
+
+		assert!(DcbDecompressor::new(&dcb_stream[..], &dictionaries).is_err());
+		assert!(DcbDecompressor::with_dictionary(&dcb_stream[..], b"Hello, World?").is_err());
+		assert!(DcbDecompressor::with_dictionary(&dcb_stream[..], b"Hello, World!").is_ok());
+		assert!(DcbDecompressor::with_dictionary(&dcb_stream[1..], b"Hello, World!").is_err());
+		assert!(DcbDecompressor::with_dictionary(&dcb_stream[..20], b"Hello, World!").is_err());
+	}
+
+	#[test]
+	fn should_decompress_with_registered_dictionary() {
+		use super::{ DcbCompressor, DcbDecompressor, DcbDictionaries };
+		use std::io::{ Read, Write };
+
+		let old_bundle = (0..1000).map(|i| format!("export function f{}(x) {{ return x + {}; }}\n", i, i)).collect::<String>();
+		let new_bundle = old_bundle.replace("x + 500;", "x - 500;");
+
+		let mut compressor = DcbCompressor::new(Vec::new(), old_bundle.as_bytes()).unwrap();
+		compressor.write_all(new_bundle.as_bytes()).unwrap();
+		let dcb_stream = compressor.finish().unwrap();
+
+		assert!(dcb_stream.len() < 100);
+
+		let mut dictionaries = DcbDictionaries::new();
+		dictionaries.register(b"unrelated");
+		dictionaries.register(old_bundle.as_bytes());
+
+		let mut decompressed = Vec::new();
+		let _ = DcbDecompressor::new(&dcb_stream[..], &dictionaries).unwrap().read_to_end(&mut decompressed);
+
+		assert_eq!(new_bundle.into_bytes(), decompressed);
+	}
+}
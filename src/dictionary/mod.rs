@@ -1,15 +1,22 @@
+/// trainer builds custom dictionaries from sample corpora.
+mod trainer;
+pub use self::trainer::train;
+
+/// Offset of the first word of each length in BROTLI_DICTIONARY, by word length.
 pub const BROTLI_DICTIONARY_OFFSETS_BY_LENGTH: [usize; 25] = [
 	     0,      0,      0,      0,      0,   4096,   9216,  21504,  35840,  44032,
 	 53248,  63488 , 74752,  87040,  93696, 100864, 104704, 106752, 108928, 113536,
 	115968, 118528, 119872, 121280, 122016,
 ];
 
+/// Base 2 logarithm of the number of words of each length in BROTLI_DICTIONARY, by word length.
 pub const BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH: [usize; 25] = [
 	 0,  0,  0,  0, 10, 10, 11, 11, 10, 10,
 	10, 10, 10,  9,  9,  8,  7,  7,  8,  7,
 	 7,  6,  6,  5,  5,
 ];
 
+/// The static dictionary of RFC 7932, Appendix A.
 pub const BROTLI_DICTIONARY: [u8; 122784] = [
 	0x74, 0x69, 0x6d, 0x65, 0x64, 0x6f, 0x77, 0x6e, 0x6c, 0x69, 0x66, 0x65, 0x6c,
 	0x65, 0x66, 0x74, 0x62, 0x61, 0x63, 0x6b, 0x63, 0x6f, 0x64, 0x65, 0x64, 0x61,
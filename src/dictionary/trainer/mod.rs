@@ -0,0 +1,257 @@
+use super::{ BROTLI_DICTIONARY_OFFSETS_BY_LENGTH, BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH, BROTLI_DICTIONARY };
+use ::transformation::Transforms;
+
+use std::collections::{ HashMap, HashSet };
+
+/// Length of the substrings, whose frequencies across samples rate a segment.
+const D: usize = 8;
+/// Length of the segments that make up a dictionary.
+const K: usize = 48;
+
+/// Minimum length of a reference to the static dictionary, which a compressor would emit.
+const MIN_STATIC_WORD_LENGTH: usize = 4;
+
+/// Words of the static dictionary, with the length of the longest one.
+struct StaticWords {
+	words: HashSet<Vec<u8>>,
+	max_length: usize,
+}
+
+/// Returns the words of the static dictionary, transformed by each of the 121 transforms of
+/// RFC 7932, which a compressor can refer to, if they are at least MIN_STATIC_WORD_LENGTH long.
+fn static_words() -> StaticWords {
+	let transforms = Transforms::rfc();
+	let mut words = HashSet::new();
+	let mut max_length = 0;
+
+	for length in 4..BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH.len() {
+		let offset = BROTLI_DICTIONARY_OFFSETS_BY_LENGTH[length];
+
+		for index in 0..(1 << BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH[length]) {
+			let word = &BROTLI_DICTIONARY[(offset + index * length)..(offset + (index + 1) * length)];
+
+			for id in 0..transforms.len() {
+				let transformed = transforms.transform(id, word);
+
+				if transformed.len() >= MIN_STATIC_WORD_LENGTH {
+					max_length = ::std::cmp::max(max_length, transformed.len());
+					words.insert(transformed);
+				}
+			}
+		}
+	}
+
+	StaticWords{
+		words,
+		max_length,
+	}
+}
+
+/// Returns for every position in sample, whether it is covered by a word of the static dictionary.
+fn static_coverage(sample: &[u8], words: &StaticWords) -> Vec<bool> {
+	let mut covered = vec![false; sample.len()];
+
+	for i in 0..sample.len() {
+		for length in MIN_STATIC_WORD_LENGTH..::std::cmp::min(words.max_length + 1, sample.len() - i + 1) {
+			if words.words.contains(&sample[i..(i + length)]) {
+				for c in &mut covered[i..(i + length)] {
+					*c = true;
+				}
+			}
+		}
+	}
+
+	covered
+}
+
+/// Builds a raw dictionary of at most target_size bytes from a corpus of samples,
+/// for Compressor::with_dictionary() and Decompressor::with_dictionary().
+///
+/// The dictionary is made up of segments of the samples, which hold the most substrings
+/// that occur in many different samples. Substrings that are covered by the static dictionary
+/// already, and substrings of segments that have been picked before, do not count, so that
+/// nothing is duplicated. The most valuable segments go last, where they are closest to the
+/// data, and hence cheapest to refer to.
+///
+/// # Examples
+/// ```
+/// use brotli::dictionary::train;
+///
+/// let samples = (0..100).map(|i| format!("{{\"id\": {}, \"status\": \"delivered\", \"carrier_code\": \"XQ{}\"}}", i, i % 7))
+///     .collect::<Vec<_>>();
+/// let dictionary = train(&samples, 1024);
+///
+/// assert!(dictionary.len() <= 1024);
+/// assert!(dictionary.windows(14).any(|w| w == b"carrier_code\":"));
+/// ```
+pub fn train<S: AsRef<[u8]>>(samples: &[S], target_size: usize) -> Vec<u8> {
+	let words = static_words();
+	let samples = samples.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+	let coverage = samples.iter().map(|s| static_coverage(s, &words)).collect::<Vec<_>>();
+
+	// number of samples that hold each substring, outside of static dictionary words
+	let mut frequencies: HashMap<&[u8], (usize, usize)> = HashMap::new();
+
+	for (n, (sample, covered)) in samples.iter().zip(coverage.iter()).enumerate() {
+		for i in 0..(sample.len() + 1).saturating_sub(D) {
+			if covered[i..(i + D)].iter().all(|&c| c) {
+				continue;
+			}
+
+			let entry = frequencies.entry(&sample[i..(i + D)]).or_insert((0, usize::MAX));
+			if entry.1 != n {
+				*entry = (entry.0 + 1, n);
+			}
+		}
+	}
+
+	// substrings that occur in a single sample only are not worth the space
+	let mut frequencies = frequencies.into_iter()
+		.filter(|&(_, (f, _))| f > 1)
+		.map(|(dmer, (f, _))| (dmer, f))
+		.collect::<HashMap<_, _>>();
+
+	// The samples are split into epochs, of which each contributes its best segment.
+	let total = samples.iter().fold(0, |acc, s| acc + s.len());
+	let n_epochs = ::std::cmp::max(1, target_size / K);
+	let epoch_size = ::std::cmp::max(K, total.div_ceil(n_epochs));
+
+	let mut segments = Vec::new();
+	let mut epoch = Vec::new();
+	let mut epoch_len = 0;
+
+	for sample in &samples {
+		epoch.push(*sample);
+		epoch_len += sample.len();
+
+		if epoch_len >= epoch_size {
+			if let Some(segment) = best_segment(&epoch, &mut frequencies) {
+				segments.push(segment);
+			}
+
+			epoch.clear();
+			epoch_len = 0;
+		}
+	}
+
+	if let Some(segment) = best_segment(&epoch, &mut frequencies) {
+		segments.push(segment);
+	}
+
+	// ascending by score, so that the best segments go last, but stable for equal scores
+	segments.sort_by_key(|segment| segment.0);
+
+	let mut dictionary = segments.into_iter().fold(Vec::new(), |mut acc, (_, segment)| {
+		acc.extend_from_slice(segment);
+		acc
+	});
+
+	if dictionary.len() > target_size {
+		let excess = dictionary.len() - target_size;
+		dictionary.drain(0..excess);
+	}
+
+	dictionary
+}
+
+/// Returns (score, segment) for the segment of K bytes in an epoch, or shorter at the end of a sample,
+/// whose distinct substrings have the highest sum of frequencies. The frequencies of its substrings
+/// are cleared, so that no later segment duplicates them. Returns None, if no segment has a score.
+fn best_segment<'a>(epoch: &[&'a [u8]], frequencies: &mut HashMap<&[u8], usize>) -> Option<(usize, &'a [u8])> {
+	let mut best: Option<(usize, &'a [u8])> = None;
+
+	for &sample in epoch {
+		if sample.len() < D {
+			continue;
+		}
+
+		// substrings in the sliding window, with the number of their occurrences in it
+		let mut active: HashMap<&[u8], usize> = HashMap::new();
+		let mut score = 0;
+		let n_dmers = sample.len() - D + 1;
+		let window = K - D + 1;
+
+		for i in 0..n_dmers {
+			let dmer = &sample[i..(i + D)];
+			let count = active.entry(dmer).or_insert(0);
+			*count += 1;
+			if *count == 1 {
+				score += *frequencies.get(dmer).unwrap_or(&0);
+			}
+
+			if i >= window {
+				let leaving = &sample[(i - window)..(i - window + D)];
+				let count = active.get_mut(leaving).unwrap();
+				*count -= 1;
+				if *count == 0 {
+					score -= *frequencies.get(leaving).unwrap_or(&0);
+				}
+			}
+
+			let start = (i + 1).saturating_sub(window);
+
+			best = match best {
+				Some((best_score, _)) if best_score >= score => best,
+				_ if score == 0 => best,
+				_ => Some((score, &sample[start..::std::cmp::min(start + K, sample.len())])),
+			};
+		}
+	}
+
+	let (score, segment) = match best {
+		Some(best) => best,
+		None => return None,
+	};
+
+	// trim the substrings that do not count from both ends
+	let scoring = (0..(segment.len() + 1).saturating_sub(D))
+		.filter(|&i| frequencies.contains_key(&segment[i..(i + D)]))
+		.collect::<Vec<_>>();
+	let segment = &segment[scoring[0]..(scoring[scoring.len() - 1] + D)];
+
+	for i in 0..(segment.len() + 1 - D) {
+		frequencies.remove(&segment[i..(i + D)]);
+	}
+
+	Some((score, segment))
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_respect_target_size() {
+		use super::train;
+
+		let samples = (0..200).map(|i| format!("<item id=\"{}\"><name>product {}</name><price currency=\"EUR\">{}.99</price></item>", i, i % 13, i % 50))
+			.collect::<Vec<_>>();
+
+		for &target_size in &[0, 10, 100, 1000] {
+			assert!(train(&samples, target_size).len() <= target_size);
+		}
+
+		assert!(train(&Vec::<Vec<u8>>::new(), 1000).is_empty());
+	}
+
+	#[test]
+	fn should_pick_common_substrings_only_once() {
+		use super::train;
+
+		let samples = (0..50).map(|i| format!("{{\"zx_quux_frobnicator\": {}, \"qq_wibble\": {}}}", i * 7919 % 1000, i))
+			.collect::<Vec<_>>();
+		let dictionary = train(&samples, 4096);
+
+		assert_eq!(1, dictionary.windows(18).filter(|w| w == b"zx_quux_frobnicato").count());
+	}
+
+	#[test]
+	fn should_skip_static_dictionary_words() {
+		use super::{ static_coverage, static_words };
+
+		let words = static_words();
+		let coverage = static_coverage(b"qxzvInformationqxzv", &words);
+
+		assert!(coverage[..4].iter().all(|&c| !c));
+		assert!(coverage[4..15].iter().all(|&c| c));
+		assert!(coverage[15..].iter().all(|&c| !c));
+	}
+}
@@ -0,0 +1,373 @@
+use ::huffman::tree::Tree;
+use ::{ Decompressor, State };
+
+use std::cmp;
+use std::io;
+use std::io::Read;
+
+/// Width of the column with the bits of a field, in the style of docs/analysis_*.txt.
+const BITS_COLUMN: usize = 20;
+/// Fields with more bits than this show their length instead of their bits.
+const MAX_BITS_SHOWN: usize = 32;
+/// Number of bytes of literals, and number of codes of prefix codes, that annotations show.
+const MAX_PREVIEW: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Line {
+	Section(&'static str),
+	/// Bits from the first to the last, exclusive, of a field, with its annotation.
+	Field(usize, usize, String),
+}
+
+/// Annotations, which a Decompressor collects while it decodes a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Trace {
+	/// position of the first bit that has not been annotated yet
+	pos: usize,
+	lines: Vec<Line>,
+	/// number of literals of the current command, that have been annotated already
+	literals: usize,
+	ended: bool,
+}
+
+impl Trace {
+	pub(crate) fn new() -> Trace {
+		Trace{
+			pos: 0,
+			lines: Vec::new(),
+			literals: 0,
+			ended: false,
+		}
+	}
+}
+
+impl<R: Read> Decompressor<R> {
+	/// Annotates the bits since the last annotation, if any, unless the stream is not traced.
+	pub(crate) fn trace_field(&mut self, text: String) {
+		let end = self.in_stream.global_bit_pos();
+
+		if let Some(ref mut trace) = self.trace {
+			trace.lines.push(Line::Field(trace.pos, end, text));
+			trace.pos = end;
+		}
+	}
+
+	/// Annotates the bits since the last annotation, if there are any.
+	pub(crate) fn trace_padding(&mut self, text: &str) {
+		let end = self.in_stream.global_bit_pos();
+
+		if self.trace.as_ref().is_some_and(|trace| trace.pos < end) {
+			self.trace_field(String::from(text));
+		}
+	}
+
+	fn trace_section(&mut self, section: &'static str) {
+		if let Some(ref mut trace) = self.trace {
+			trace.lines.push(Line::Section(section));
+		}
+	}
+
+	/// Annotates the literals of the current command from the given index on, and
+	/// remembers them as annotated.
+	pub(crate) fn trace_literals(&mut self, literals: &[u8], to: usize) {
+		let from = match self.trace {
+			Some(ref trace) => trace.literals,
+			None => return,
+		};
+
+		if to > from {
+			self.trace_field(format!("{} literals: {}", to - from, preview(&literals[from..to])));
+		}
+
+		if let Some(ref mut trace) = self.trace {
+			trace.literals = to;
+		}
+	}
+
+	/// Annotates a state, which the decompressor has just entered, with the bits that have been
+	/// read to get there.
+	pub(crate) fn trace_state(&mut self, state: &State) {
+		let text = match *state {
+			State::HeaderBegin => return self.trace_section("Brotli Stream Header"),
+			State::WBits(wbits) => format!("WBITS => {}, window size = 1 << WBITS - 16 = {}", wbits, (1usize << wbits) - 16),
+			State::HeaderMetaBlockBegin => return self.trace_section("Brotli Meta-Block Header"),
+			State::IsLast(is_last) => format!("ISLAST => {}", is_last),
+			State::IsLastEmpty(is_last_empty) => format!("ISLASTEMPTY => {}", is_last_empty),
+			State::MNibbles(m_nibbles) => format!("MNIBBLES => {}", m_nibbles),
+			State::MSkipBytes(m_skip_bytes) => format!("MSKIPBYTES => {}", m_skip_bytes),
+			State::MSkipLen(m_skip_len) => format!("MSKIPLEN => {}", m_skip_len),
+			State::MLen(m_len) => format!("MLEN => {}", m_len),
+			State::IsUncompressed(is_uncompressed) => format!("ISUNCOMPRESSED => {}", is_uncompressed),
+			State::MLenLiterals(ref literals) => format!("{} uncompressed bytes: {}", literals.len(), preview(literals)),
+			State::NBltypesL(n_bltypes) => format!("NBLTYPESL => {}", n_bltypes),
+			State::NBltypesI(n_bltypes) => format!("NBLTYPESI => {}", n_bltypes),
+			State::NBltypesD(n_bltypes) => format!("NBLTYPESD => {}", n_bltypes),
+			State::PrefixCodeBlockTypesLiterals(ref prefix_code) => format!("prefix code block types literals: {}", describe_prefix_code(prefix_code)),
+			State::PrefixCodeBlockTypesInsertAndCopyLengths(ref prefix_code) => format!("prefix code block types insert and copy lengths: {}", describe_prefix_code(prefix_code)),
+			State::PrefixCodeBlockTypesDistances(ref prefix_code) => format!("prefix code block types distances: {}", describe_prefix_code(prefix_code)),
+			State::PrefixCodeBlockCountsLiterals(ref prefix_code) => format!("prefix code block counts literals: {}", describe_prefix_code(prefix_code)),
+			State::PrefixCodeBlockCountsInsertAndCopyLengths(ref prefix_code) => format!("prefix code block counts insert and copy lengths: {}", describe_prefix_code(prefix_code)),
+			State::PrefixCodeBlockCountsDistances(ref prefix_code) => format!("prefix code block counts distances: {}", describe_prefix_code(prefix_code)),
+			State::FirstBlockCountLiterals(blen) => format!("BLEN_L => {}", blen),
+			State::FirstBlockCountInsertAndCopyLengths(blen) => format!("BLEN_I => {}", blen),
+			State::FirstBlockCountDistances(blen) => format!("BLEN_D => {}", blen),
+			State::NPostfix(n_postfix) => format!("NPOSTFIX => {}", n_postfix),
+			State::NDirect(n_direct) => format!("NDIRECT => {}", n_direct),
+			State::ContextModesLiterals(ref context_modes) => format!("context modes literals => {:?}", context_modes),
+			State::NTreesL(n_trees) => format!("NTREESL => {}", n_trees),
+			State::NTreesD(n_trees) => format!("NTREESD => {}", n_trees),
+			State::ContextMapLiterals(ref c_map) => format!("context map literals => {:?}", c_map),
+			State::ContextMapDistances(ref c_map) => format!("context map distances => {:?}", c_map),
+			State::PrefixCodesDistances(_) => return self.trace_section("Brotli Meta-Block Data"),
+			State::InsertAndCopyLength(insert_and_copy_length) if insert_and_copy_length < 128 => {
+				format!("insert and copy length code => {}, implicit distance code 0", insert_and_copy_length)
+			},
+			State::InsertAndCopyLength(insert_and_copy_length) => format!("insert and copy length code => {}", insert_and_copy_length),
+			State::InsertLengthAndCopyLength((insert_length, copy_length)) => {
+				format!("insert length = {}, copy length = {}", insert_length, copy_length)
+			},
+			State::InsertLiterals(ref literals) => {
+				let len = literals.len();
+
+				self.trace_literals(literals, len);
+
+				if let Some(ref mut trace) = self.trace {
+					trace.literals = 0;
+				}
+
+				return;
+			},
+			State::DistanceCode(distance_code) => format!("distance code => {}", distance_code),
+			State::Distance(distance) => format!("distance = {}", distance),
+			State::CopyLiterals(ref literals) => match self.dictionary_reference() {
+				Some((word_id, transform_id)) => format!("dictionary reference, word id {}, transform id {}, length {}: {}",
+					word_id, transform_id, self.meta_block.copy_length.unwrap(), preview(literals)),
+				None => format!("copy {} bytes from distance {}: {}", literals.len(), self.meta_block.distance.unwrap(), preview(literals)),
+			},
+			State::StreamEnd => {
+				let ended = match self.trace {
+					Some(ref mut trace) => {
+						let ended = trace.ended;
+						trace.ended = true;
+						ended
+					},
+					None => return,
+				};
+
+				if !ended {
+					self.trace_section("End of Stream");
+				}
+
+				return;
+			},
+			_ => return,
+		};
+
+		self.trace_field(text);
+	}
+}
+
+/// Returns the bytes as a string, with non-printable bytes escaped, and shortened to
+/// MAX_PREVIEW bytes.
+pub(crate) fn preview(bytes: &[u8]) -> String {
+	let mut text = String::from("\"");
+
+	for &byte in &bytes[..cmp::min(bytes.len(), MAX_PREVIEW)] {
+		text.extend(::std::ascii::escape_default(byte).map(|c| c as char));
+	}
+
+	text.push('"');
+
+	if bytes.len() > MAX_PREVIEW {
+		text.push('…');
+	}
+
+	text
+}
+
+/// Returns the symbols of a prefix code with their codes, in the order of the bit stream,
+/// shortened to MAX_PREVIEW symbols.
+pub(crate) fn describe_prefix_code(prefix_code: &Tree) -> String {
+	let codes = prefix_code.codes();
+	let mut text = format!("{} symbols", codes.len());
+
+	for &(symbol, ref code) in codes.iter().take(MAX_PREVIEW) {
+		text.push_str(&format!(", {} = {}", symbol, code.iter().map(|&bit| if bit { '1' } else { '0' }).collect::<String>()));
+	}
+
+	if codes.len() > MAX_PREVIEW {
+		text.push_str(", …");
+	}
+
+	text
+}
+
+/// Returns the bits from first to last, exclusive, with the last bit first, grouped by nibbles.
+fn bits(data: &[u8], first: usize, last: usize) -> String {
+	let mut text = String::new();
+
+	for pos in (first..last).rev() {
+		text.push(if data[pos >> 3] & (1 << (pos & 7)) > 0 { '1' } else { '0' });
+
+		if pos > first && (pos - first).is_multiple_of(4) {
+			text.push(' ');
+		}
+	}
+
+	text
+}
+
+fn render(name: &str, data: &[u8], lines: &[Line]) -> String {
+	let mut text = format!("Analysis of {}:\n", name);
+
+	text.push_str(&data.iter().take(16).map(|byte| format!("0x{:02x}", byte)).collect::<Vec<_>>().join(" "));
+	text.push_str(if data.len() > 16 { " …\n" } else { "\n" });
+
+	for line in lines {
+		match *line {
+			Line::Section(section) => {
+				text.push_str(&format!("\n{}\n{}\n", section, "=".repeat(section.chars().count())));
+			},
+			Line::Field(first, last, ref annotation) => {
+				let field = if last - first > MAX_BITS_SHOWN {
+					format!("[{} bits]", last - first)
+				} else {
+					bits(data, first, last)
+				};
+
+				if field.chars().count() >= BITS_COLUMN {
+					text.push_str(&format!("@{:<8}{}\n", first, field));
+					text.push_str(&format!("{:9}{:w$}# {}\n", "", "", annotation, w = BITS_COLUMN));
+				} else {
+					text.push_str(&format!("@{:<8}{:w$}# {}\n", first, field, annotation, w = BITS_COLUMN));
+				}
+			},
+		}
+	}
+
+	text
+}
+
+/// Decodes a stream, and returns an annotated listing of its bits, in the style of
+/// docs/analysis_*.txt: the stream and meta-block headers field by field, prefix codes,
+/// context maps, and each command, each literal run and each dictionary reference with its
+/// transform, all prefixed with the bit offset at which they start. If the stream is
+/// invalid, the listing ends at the error, with the bit offset at which it has been detected.
+/// Returns an error, only if reading from the stream fails.
+///
+/// # Examples
+/// ```
+/// use brotli::dump;
+///
+/// let listing = dump(&[0x0b, 0x00, 0x80, 0x58, 0x03][..]).unwrap();
+///
+/// assert!(listing.contains("# WBITS => 22"));
+/// assert!(listing.contains("# 1 uncompressed bytes: \"X\""));
+/// ```
+pub fn dump<R: Read>(mut r: R) -> io::Result<String> {
+	let mut data = Vec::new();
+
+	match r.read_to_end(&mut data) {
+		Ok(_) => {},
+		Err(e) => return Err(e),
+	}
+
+	let mut decompressor = Decompressor::new(&data[..]);
+	decompressor.trace = Some(Trace::new());
+
+	let result = io::copy(&mut decompressor, &mut io::sink());
+	let trace = decompressor.trace.take().unwrap();
+	let mut text = render("stream", &data, &trace.lines);
+
+	if let Err(e) = result {
+		text.push_str(&format!("\nError: {}\n", e));
+	}
+
+	Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Line;
+
+	fn lines(data: &[u8]) -> Vec<Line> {
+		use super::Trace;
+		use ::Decompressor;
+		use std::io;
+
+		let mut decompressor = Decompressor::new(data);
+		decompressor.trace = Some(Trace::new());
+
+		let _ = io::copy(&mut decompressor, &mut io::sink());
+
+		decompressor.trace.unwrap().lines
+	}
+
+	#[test]
+	fn should_annotate_uncompressed_meta_block() {
+		use super::dump;
+
+		let listing = dump(&[0x0b, 0x00, 0x80, 0x58, 0x03][..]).unwrap();
+
+		for expected in &["Brotli Stream Header", "# WBITS => 22", "# ISLAST => false", "# MLEN => 1",
+				"# ISUNCOMPRESSED => true", "# 1 uncompressed bytes: \"X\"", "# ISLASTEMPTY => true", "End of Stream"] {
+			assert!(listing.contains(expected), "{:?} missing in\n{}", expected, listing);
+		}
+
+		assert!(listing.contains("@0       1011                # WBITS => 22"));
+	}
+
+	#[test]
+	fn should_account_for_every_bit() {
+		use std::fs::File;
+		use std::io::Read;
+
+		for name in &["data/x.compressed", "data/alice29.txt.compressed", "data/quickfox_repeated.compressed"] {
+			let mut data = Vec::new();
+			File::open(name).unwrap().read_to_end(&mut data).unwrap();
+
+			let mut pos = 0;
+
+			for line in lines(&data) {
+				if let Line::Field(first, last, _) = line {
+					assert_eq!(pos, first);
+					assert!(first <= last);
+
+					pos = last;
+				}
+			}
+
+			assert_eq!(data.len() * 8, pos);
+		}
+	}
+
+	#[test]
+	fn should_annotate_commands_and_dictionary_references() {
+		use std::fs::File;
+		use std::io::Read;
+
+		let mut data = Vec::new();
+		File::open("data/quickfox_repeated.compressed").unwrap().read_to_end(&mut data).unwrap();
+
+		let lines = lines(&data);
+		let annotated = |prefix: &str| lines.iter().any(|line| match *line {
+			Line::Field(_, _, ref text) => text.starts_with(prefix),
+			_ => false,
+		});
+
+		assert!(annotated("insert and copy length code => "));
+		assert!(annotated("copy "));
+		assert!(annotated("dictionary reference, word id "));
+	}
+
+	#[test]
+	fn should_report_offset_of_error() {
+		use super::dump;
+
+		// ISLAST, ISLASTEMPTY followed by a non-zero trailer bit
+		let listing = dump(&[0x0b, 0x00, 0x80, 0x58, 0x0b][..]).unwrap();
+
+		assert!(listing.contains("# ISLASTEMPTY => true"));
+		assert!(listing.ends_with("Error: Enocuntered non-zero bit trailing the stream (state StreamEnd, meta-block 1, bit offset 40, byte offset 1)\n"), "{}", listing);
+	}
+}
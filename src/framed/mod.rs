@@ -0,0 +1,595 @@
+use ::compressor::Compressor;
+use ::crc32::{ crc32, Crc32 };
+use ::Decompressor;
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::{ Display, Formatter };
+use std::io;
+use std::io::{ Read, Write };
+
+/// Magic bytes at the start of a frame.
+const FRAME_MAGIC: [u8; 4] = [0xce, b'B', b'R', b'F'];
+/// Version of the frame format, that follows the magic bytes.
+const FRAME_VERSION: u8 = 1;
+/// Flag for a content size in the frame header.
+const FLAG_CONTENT_SIZE: u8 = 0x01;
+/// Flag for a checksum after every chunk.
+const FLAG_CHUNK_CHECKSUMS: u8 = 0x02;
+/// Number of compressed bytes, after which FramedCompressor completes a chunk.
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Corruption of a frame, as opposed to a Brotli stream that is malformed in itself.
+/// FramedDecompressor returns io::Errors of kind InvalidData, which carry a FrameError,
+/// for corrupt frames, so that they can be told apart with downcast_ref().
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameError {
+	/// A chunk checksum did not match its chunk.
+	ChunkChecksum,
+	/// The content checksum did not match the decompressed content.
+	ContentChecksum,
+	/// The decompressed content did not have the size from the frame header.
+	ContentSize,
+	/// The frame does not start with the magic bytes.
+	InvalidMagic,
+	/// The frame header has flags set, that this version does not know.
+	NonZeroReservedFlags,
+	/// The frame ended before its trailer.
+	UnexpectedEOF,
+	/// The frame has a newer format version than this one.
+	UnsupportedVersion,
+}
+
+impl Display for FrameError {
+	fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+		fmt.write_str(match *self {
+			FrameError::ChunkChecksum => "Chunk checksum did not match, frame is corrupt",
+			FrameError::ContentChecksum => "Content checksum did not match, frame is corrupt",
+			FrameError::ContentSize => "Content size did not match the size in the frame header",
+			FrameError::InvalidMagic => "Stream does not start with a frame header",
+			FrameError::NonZeroReservedFlags => "Encountered non-zero reserved flags in frame header",
+			FrameError::UnexpectedEOF => "Frame ended unexpectedly",
+			FrameError::UnsupportedVersion => "Frame has an unsupported format version",
+		})
+	}
+}
+
+impl Error for FrameError {}
+
+impl From<FrameError> for io::Error {
+	fn from(e: FrameError) -> io::Error {
+		io::Error::new(io::ErrorKind::InvalidData, e)
+	}
+}
+
+/// Splits the compressed stream into chunks, each preceded by its length, and optionally
+/// followed by its checksum. A chunk of length 0 ends the sequence.
+#[derive(Debug)]
+struct ChunkWriter<W: Write> {
+	inner: W,
+	buf: Vec<u8>,
+	chunk_checksums: bool,
+}
+
+impl<W: Write> ChunkWriter<W> {
+	fn write_chunk(&mut self) -> io::Result<()> {
+		if self.buf.is_empty() {
+			return Ok(());
+		}
+
+		let len = self.buf.len() as u32;
+
+		match self.inner.write_all(&u32_to_le(len)).and_then(|_| self.inner.write_all(&self.buf)) {
+			Ok(()) => {},
+			Err(e) => return Err(e),
+		}
+
+		if self.chunk_checksums {
+			match self.inner.write_all(&u32_to_le(crc32(&self.buf))) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		self.buf.clear();
+
+		Ok(())
+	}
+
+	fn finish(mut self) -> io::Result<W> {
+		match self.write_chunk().and_then(|_| self.inner.write_all(&u32_to_le(0))) {
+			Ok(()) => Ok(self.inner),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+impl<W: Write> Write for ChunkWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let n = ::std::cmp::min(buf.len(), CHUNK_SIZE - self.buf.len());
+		self.buf.extend_from_slice(&buf[..n]);
+
+		if self.buf.len() == CHUNK_SIZE {
+			match self.write_chunk() {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(n)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.write_chunk().and_then(|_| self.inner.flush())
+	}
+}
+
+/// Wraps an output stream, and writes a self-describing frame around the Brotli stream:
+/// magic bytes, the format version, flags, the content size, if it is given,
+/// the compressed stream in chunks, each with a checksum, if enabled, and finally the
+/// CRC-32 checksum of the content. The frame needs to be completed with finish().
+///
+/// # Examples
+/// ```
+/// use std::io::{ Read, Write };
+/// use brotli::{ FramedCompressor, FramedDecompressor };
+///
+/// let input = b"To be, or not to be: that is the question";
+///
+/// let mut compressor = FramedCompressor::with_options(Vec::new(), Some(input.len() as u64), true).unwrap();
+/// compressor.write_all(input).unwrap();
+/// let frame = compressor.finish().unwrap();
+///
+/// let mut decompressed = Vec::new();
+/// FramedDecompressor::new(&frame[..]).unwrap().read_to_end(&mut decompressed).unwrap();
+///
+/// assert_eq!(input.to_vec(), decompressed);
+/// ```
+#[derive(Debug)]
+pub struct FramedCompressor<W: Write> {
+	compressor: Compressor<ChunkWriter<W>>,
+	content_size: Option<u64>,
+	count_input: u64,
+	checksum: Crc32,
+}
+
+impl<W: Write> FramedCompressor<W> {
+	/// Creates FramedCompressor from Write, for a frame without content size and without
+	/// chunk checksums, and writes the frame header.
+	pub fn new(w: W) -> io::Result<FramedCompressor<W>> {
+		FramedCompressor::with_options(w, None, false)
+	}
+
+	/// Creates FramedCompressor from Write, and writes the frame header. If content_size is
+	/// given, it goes into the frame header, and writing more or less input than that fails
+	/// with an error of kind InvalidInput. If chunk_checksums is set, every chunk of the
+	/// compressed stream is followed by its checksum.
+	pub fn with_options(mut w: W, content_size: Option<u64>, chunk_checksums: bool) -> io::Result<FramedCompressor<W>> {
+		let mut header = FRAME_MAGIC.to_vec();
+		let mut flags = 0;
+
+		if content_size.is_some() {
+			flags |= FLAG_CONTENT_SIZE;
+		}
+
+		if chunk_checksums {
+			flags |= FLAG_CHUNK_CHECKSUMS;
+		}
+
+		header.push(FRAME_VERSION);
+		header.push(flags);
+
+		if let Some(size) = content_size {
+			header.extend_from_slice(&u64_to_le(size));
+		}
+
+		match w.write_all(&header) {
+			Ok(()) => {},
+			Err(e) => return Err(e),
+		}
+
+		Ok(FramedCompressor{
+			compressor: Compressor::new(ChunkWriter{
+				inner: w,
+				buf: Vec::new(),
+				chunk_checksums,
+			}),
+			content_size,
+			count_input: 0,
+			checksum: Crc32::new(),
+		})
+	}
+
+	/// Compresses all pending input, completes the frame, and returns the underlying writer.
+	pub fn finish(self) -> io::Result<W> {
+		match self.content_size {
+			Some(size) if size != self.count_input => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Input is shorter than the content size of the frame")),
+			_ => {},
+		}
+
+		let checksum = self.checksum.sum();
+
+		match self.compressor.finish().and_then(|chunk_writer| chunk_writer.finish()) {
+			Ok(mut w) => match w.write_all(&u32_to_le(checksum)) {
+				Ok(()) => Ok(w),
+				Err(e) => Err(e),
+			},
+			Err(e) => Err(e),
+		}
+	}
+}
+
+impl<W: Write> Write for FramedCompressor<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self.content_size {
+			Some(size) if (buf.len() as u64) > size - self.count_input => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Input exceeds the content size of the frame")),
+			_ => {},
+		}
+
+		let n = match self.compressor.write(buf) {
+			Ok(n) => n,
+			Err(e) => return Err(e),
+		};
+
+		self.checksum.update(&buf[..n]);
+		self.count_input += n as u64;
+
+		Ok(n)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.compressor.flush()
+	}
+}
+
+/// Reads the chunks of the compressed stream, verifies them, and reads the content checksum
+/// after the last chunk. Errors are kept, because Decompressor does not pass them on.
+#[derive(Debug)]
+struct ChunkReader<R: Read> {
+	inner: R,
+	buf: Vec<u8>,
+	pos: usize,
+	chunk_checksums: bool,
+	content_checksum: Option<u32>,
+	error: Option<FrameError>,
+}
+
+impl<R: Read> ChunkReader<R> {
+	fn read_chunk(&mut self) -> Result<(), FrameError> {
+		let len = match read_u32_le(&mut self.inner) {
+			Ok(len) => len,
+			Err(e) => return Err(e),
+		};
+
+		self.buf.clear();
+		self.pos = 0;
+
+		if len == 0 {
+			return match read_u32_le(&mut self.inner) {
+				Ok(checksum) => {
+					self.content_checksum = Some(checksum);
+
+					Ok(())
+				},
+				Err(e) => Err(e),
+			};
+		}
+
+		match (&mut self.inner).take(len as u64).read_to_end(&mut self.buf) {
+			Ok(n) if n == len as usize => {},
+			_ => return Err(FrameError::UnexpectedEOF),
+		}
+
+		if self.chunk_checksums {
+			match read_u32_le(&mut self.inner) {
+				Ok(checksum) if checksum == crc32(&self.buf) => {},
+				Ok(_) => return Err(FrameError::ChunkChecksum),
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(())
+	}
+}
+
+impl<R: Read> Read for ChunkReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		while self.pos == self.buf.len() {
+			if self.content_checksum.is_some() {
+				return Ok(0);
+			}
+
+			match self.read_chunk() {
+				Ok(()) => {},
+				Err(e) => {
+					self.error = Some(e);
+
+					return Err(io::Error::from(e));
+				},
+			}
+		}
+
+		let n = ::std::cmp::min(buf.len(), self.buf.len() - self.pos);
+		buf[..n].copy_from_slice(&self.buf[self.pos..(self.pos + n)]);
+		self.pos += n;
+
+		Ok(n)
+	}
+}
+
+/// Wraps an input stream of a frame from FramedCompressor, and decompresses it, while it
+/// verifies chunk checksums, the content size and the content checksum on the fly.
+/// Corrupt frames fail with an io::Error of kind InvalidData, that carries a FrameError,
+/// while malformed Brotli streams fail with a plain io::Error of kind InvalidData.
+///
+/// # Examples
+/// ```
+/// use std::io::{ Read, Write };
+/// use brotli::{ FrameError, FramedCompressor, FramedDecompressor };
+///
+/// let mut compressor = FramedCompressor::with_options(Vec::new(), None, true).unwrap();
+/// compressor.write_all(b"Hello, World!").unwrap();
+/// let mut frame = compressor.finish().unwrap();
+///
+/// // flip a bit in the compressed stream
+/// frame[10] ^= 0x04;
+///
+/// let e = FramedDecompressor::new(&frame[..]).unwrap().read_to_end(&mut Vec::new()).unwrap_err();
+///
+/// assert_eq!(Some(&FrameError::ChunkChecksum), e.get_ref().and_then(|e| e.downcast_ref::<FrameError>()));
+/// ```
+#[derive(Debug)]
+pub struct FramedDecompressor<R: Read> {
+	decompressor: Decompressor<ChunkReader<R>>,
+	content_size: Option<u64>,
+	count_output: u64,
+	checksum: Crc32,
+	verified: bool,
+}
+
+impl<R: Read> FramedDecompressor<R> {
+	/// Creates FramedDecompressor from Read, and reads the frame header. Returns an error with
+	/// kind InvalidData, if the stream does not start with a frame header of a known version.
+	pub fn new(mut r: R) -> io::Result<FramedDecompressor<R>> {
+		let mut header = [0; 6];
+
+		match r.read_exact(&mut header) {
+			Ok(()) => {},
+			Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(io::Error::from(FrameError::InvalidMagic)),
+			Err(e) => return Err(e),
+		}
+
+		if header[..4] != FRAME_MAGIC {
+			return Err(io::Error::from(FrameError::InvalidMagic));
+		}
+
+		if header[4] != FRAME_VERSION {
+			return Err(io::Error::from(FrameError::UnsupportedVersion));
+		}
+
+		let flags = header[5];
+
+		if flags & !(FLAG_CONTENT_SIZE | FLAG_CHUNK_CHECKSUMS) != 0 {
+			return Err(io::Error::from(FrameError::NonZeroReservedFlags));
+		}
+
+		let content_size = if flags & FLAG_CONTENT_SIZE == 0 {
+			None
+		} else {
+			let mut size = [0; 8];
+
+			match r.read_exact(&mut size) {
+				Ok(()) => Some(size.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64)),
+				Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(io::Error::from(FrameError::UnexpectedEOF)),
+				Err(e) => return Err(e),
+			}
+		};
+
+		Ok(FramedDecompressor{
+			decompressor: Decompressor::new(ChunkReader{
+				inner: r,
+				buf: Vec::new(),
+				pos: 0,
+				chunk_checksums: flags & FLAG_CHUNK_CHECKSUMS != 0,
+				content_checksum: None,
+				error: None,
+			}),
+			content_size,
+			count_output: 0,
+			checksum: Crc32::new(),
+			verified: false,
+		})
+	}
+
+	/// Returns the content size from the frame header, if the frame has one.
+	pub fn content_size(&self) -> Option<u64> {
+		self.content_size
+	}
+
+	fn verify(&self) -> Result<(), FrameError> {
+		match self.content_size {
+			Some(size) if size != self.count_output => return Err(FrameError::ContentSize),
+			_ => {},
+		}
+
+		match self.decompressor.get_ref().content_checksum {
+			Some(checksum) if checksum == self.checksum.sum() => Ok(()),
+			Some(_) => Err(FrameError::ContentChecksum),
+			None => Err(FrameError::UnexpectedEOF),
+		}
+	}
+}
+
+impl<R: Read> Read for FramedDecompressor<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.verified || buf.is_empty() {
+			return Ok(0);
+		}
+
+		let n = match self.decompressor.read(buf) {
+			Ok(n) => n,
+			Err(e) => return match self.decompressor.get_ref().error {
+				Some(frame_error) => Err(io::Error::from(frame_error)),
+				None => Err(e),
+			},
+		};
+
+		self.checksum.update(&buf[..n]);
+		self.count_output += n as u64;
+
+		match self.content_size {
+			Some(size) if self.count_output > size => return Err(io::Error::from(FrameError::ContentSize)),
+			_ => {},
+		}
+
+		if n == 0 {
+			match self.verify() {
+				Ok(()) => self.verified = true,
+				Err(e) => return Err(io::Error::from(e)),
+			}
+		}
+
+		Ok(n)
+	}
+}
+
+fn u32_to_le(n: u32) -> [u8; 4] {
+	[n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+fn u64_to_le(n: u64) -> [u8; 8] {
+	let mut bytes = [0; 8];
+
+	for (i, byte) in bytes.iter_mut().enumerate() {
+		*byte = (n >> (8 * i)) as u8;
+	}
+
+	bytes
+}
+
+fn read_u32_le<R: Read>(r: &mut R) -> Result<u32, FrameError> {
+	let mut bytes = [0; 4];
+
+	match r.read_exact(&mut bytes) {
+		Ok(()) => Ok(bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u32)),
+		Err(_) => Err(FrameError::UnexpectedEOF),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	fn frame(input: &[u8], content_size: Option<u64>, chunk_checksums: bool) -> Vec<u8> {
+		use super::FramedCompressor;
+		use std::io::Write;
+
+		let mut compressor = FramedCompressor::with_options(Vec::new(), content_size, chunk_checksums).unwrap();
+		compressor.write_all(input).unwrap();
+		compressor.finish().unwrap()
+	}
+
+	fn frame_error(frame: &[u8]) -> Option<super::FrameError> {
+		use super::{ FrameError, FramedDecompressor };
+		use std::io::Read;
+
+		let e = match FramedDecompressor::new(frame) {
+			Ok(mut decompressor) => decompressor.read_to_end(&mut Vec::new()).unwrap_err(),
+			Err(e) => e,
+		};
+
+		e.get_ref().and_then(|e| e.downcast_ref::<FrameError>()).cloned()
+	}
+
+	#[test]
+	fn should_write_frame_header() {
+		let frame = frame(b"Hello", Some(5), true);
+
+		assert_eq!(&[0xce, b'B', b'R', b'F', 1, 0x03, 5, 0, 0, 0, 0, 0, 0, 0], &frame[..14]);
+		assert_eq!(&[0x82, 0x89, 0xd1, 0xf7], &frame[(frame.len() - 4)..]);
+	}
+
+	#[test]
+	fn should_round_trip_frames() {
+		use super::FramedDecompressor;
+		use std::io::Read;
+
+		let input = (0..20000).map(|i| format!("line {} of {}\n", i * 7 % 1000, i)).collect::<String>().into_bytes();
+
+		for &(content_size, chunk_checksums) in &[(None, false), (None, true), (Some(input.len() as u64), false), (Some(input.len() as u64), true)] {
+			let frame = frame(&input, content_size, chunk_checksums);
+			let mut decompressor = FramedDecompressor::new(&frame[..]).unwrap();
+			let mut decompressed = Vec::new();
+
+			assert_eq!(content_size, decompressor.content_size());
+
+			decompressor.read_to_end(&mut decompressed).unwrap();
+
+			assert_eq!(input, decompressed);
+		}
+
+		let frame = frame(&[], Some(0), true);
+		let mut decompressed = Vec::new();
+		FramedDecompressor::new(&frame[..]).unwrap().read_to_end(&mut decompressed).unwrap();
+
+		assert!(decompressed.is_empty());
+	}
+
+	#[test]
+	fn should_reject_content_size_mismatch() {
+		use super::FramedCompressor;
+		use std::io::Write;
+
+		let mut compressor = FramedCompressor::with_options(Vec::new(), Some(4), false).unwrap();
+		assert!(compressor.write_all(b"Hello").is_err());
+
+		let mut compressor = FramedCompressor::with_options(Vec::new(), Some(6), false).unwrap();
+		compressor.write_all(b"Hello").unwrap();
+		assert!(compressor.finish().is_err());
+	}
+
+	#[test]
+	fn should_report_corruption_as_frame_error() {
+		use super::FrameError;
+
+		let input = b"Hello, World! Hello, World! Hello, World!";
+
+		let mut corrupt = frame(input, None, true);
+		corrupt[12] ^= 0x01;
+		assert_eq!(Some(FrameError::ChunkChecksum), frame_error(&corrupt));
+
+		let mut corrupt = frame(input, None, true);
+		let len = corrupt.len();
+		corrupt[len - 1] ^= 0x01;
+		assert_eq!(Some(FrameError::ContentChecksum), frame_error(&corrupt));
+
+		let mut corrupt = frame(input, Some(input.len() as u64), false);
+		corrupt[6] += 1;
+		assert_eq!(Some(FrameError::ContentSize), frame_error(&corrupt));
+
+		let corrupt = frame(input, None, true);
+		assert_eq!(Some(FrameError::UnexpectedEOF), frame_error(&corrupt[..(corrupt.len() - 2)]));
+
+		let mut corrupt = frame(input, None, true);
+		corrupt[0] = 0;
+		assert_eq!(Some(FrameError::InvalidMagic), frame_error(&corrupt));
+
+		let mut corrupt = frame(input, None, true);
+		corrupt[4] = 2;
+		assert_eq!(Some(FrameError::UnsupportedVersion), frame_error(&corrupt));
+
+		let mut corrupt = frame(input, None, true);
+		corrupt[5] |= 0x80;
+		assert_eq!(Some(FrameError::NonZeroReservedFlags), frame_error(&corrupt));
+	}
+
+	#[test]
+	fn should_report_malformed_brotli_as_plain_error() {
+		use std::io::Write;
+
+		// a frame without chunk checksums, around a stream that is not Brotli
+		let mut frame = vec![0xce, b'B', b'R', b'F', 1, 0, 4, 0, 0, 0];
+		frame.write_all(&[0xff, 0xff, 0xff, 0xff]).unwrap();
+		frame.write_all(&[0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+
+		assert_eq!(None, frame_error(&frame));
+	}
+}
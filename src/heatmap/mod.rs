@@ -0,0 +1,252 @@
+use ::{ Decompressor, State };
+
+use std::io;
+use std::io::Read;
+
+/// Compressed bits, attributed to each decompressed byte of a stream: the bits of a literal
+/// go to the literal, the bits of the command and the distance of a copy are spread across
+/// the copied bytes, and the bits of headers, prefix codes and context maps go to the
+/// meta-block as its overhead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heatmap {
+	/// decompressed bytes
+	pub bytes: Vec<u8>,
+	/// compressed bits, that each decompressed byte costs
+	pub bits: Vec<f64>,
+	/// offset of the first decompressed byte, and overhead bits, of each meta-block
+	pub meta_blocks: Vec<(usize, usize)>,
+}
+
+impl Heatmap {
+	/// Returns the heatmap as a JSON object, with the bits of each byte, and the meta-blocks.
+	pub fn to_json(&self) -> String {
+		format!("{{\"bits\":[{}],\"meta_blocks\":[{}]}}",
+			self.bits.iter().map(|b| format!("{:.2}", b)).collect::<Vec<_>>().join(","),
+			self.meta_blocks.iter().map(|&(offset, overhead)| format!("{{\"offset\":{},\"overhead_bits\":{}}}", offset, overhead)).collect::<Vec<_>>().join(","))
+	}
+
+	/// Returns an HTML page, which shows the decompressed bytes, colored from green for cheap
+	/// bytes to red for expensive ones, with their bits as tooltips. Each meta-block starts with
+	/// a marker, with its overhead as tooltip.
+	pub fn to_html(&self) -> String {
+		let mut html = String::from(concat!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>brotli heatmap</title>\n",
+			"<style>pre { white-space: pre-wrap; font-family: monospace; } .m { color: #fff; background: #000; }</style>\n",
+			"</head>\n<body>\n<pre>"));
+		let mut meta_blocks = self.meta_blocks.iter().enumerate().peekable();
+
+		for (i, (&byte, &bits)) in self.bytes.iter().zip(self.bits.iter()).enumerate() {
+			while meta_blocks.peek().is_some_and(|&(_, &(offset, _))| offset == i) {
+				let (n, &(_, overhead)) = meta_blocks.next().unwrap();
+
+				html.push_str(&format!("<span class=\"m\" title=\"meta-block {}: {} bits of overhead\">&#182;</span>", n, overhead));
+			}
+
+			// 0 bits are green, 12 bits and more are red
+			let hue = 120.0 - 120.0 * bits.min(12.0) / 12.0;
+			let text = match byte {
+				b'&' => String::from("&amp;"),
+				b'<' => String::from("&lt;"),
+				b'>' => String::from("&gt;"),
+				b'"' => String::from("&quot;"),
+				b'\n' | b'\t' => (byte as char).to_string(),
+				0x20..=0x7e => (byte as char).to_string(),
+				_ => format!("\\x{:02x}", byte),
+			};
+
+			html.push_str(&format!("<span style=\"background: hsl({:.0}, 100%, 75%)\" title=\"{}: {:.2} bits\">{}</span>", hue, i, bits, text));
+		}
+
+		html.push_str("</pre>\n</body>\n</html>\n");
+
+		html
+	}
+}
+
+/// Attribution of bits, which a Decompressor performs while it decodes a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Attribution {
+	/// position of the first bit that has not been attributed yet
+	pos: usize,
+	/// bits of the current command and its distance, which go to its copied bytes
+	command_bits: usize,
+	/// index of the first byte of the current command
+	command_start: usize,
+	/// overhead before the first meta-block
+	overhead_bits: usize,
+	heatmap: Heatmap,
+}
+
+impl<R: Read> Decompressor<R> {
+	fn attribute_bits(&mut self) -> usize {
+		let end = self.in_stream.global_bit_pos();
+		let attribution = self.attribution.as_mut().unwrap();
+		let bits = end - attribution.pos;
+
+		attribution.pos = end;
+
+		bits
+	}
+
+	/// Attributes the bits since the last attribution to a literal, which has just been read.
+	pub(crate) fn attribute_literal(&mut self) {
+		let bits = self.attribute_bits();
+
+		self.attribution.as_mut().unwrap().heatmap.bits.push(bits as f64);
+	}
+
+	/// Attributes the bits, which the decompressor has read to enter a state.
+	pub(crate) fn attribute_state(&mut self, state: &State) {
+		let bits = self.attribute_bits();
+		let offset = self.count_output;
+		let attribution = self.attribution.as_mut().unwrap();
+
+		match *state {
+			State::HeaderMetaBlockBegin => {
+				let overhead_bits = attribution.overhead_bits + bits;
+
+				attribution.overhead_bits = 0;
+				attribution.heatmap.meta_blocks.push((offset, overhead_bits));
+			},
+			State::MLenLiterals(ref literals) => {
+				for _ in 0..literals.len() {
+					attribution.heatmap.bits.push(bits as f64 / literals.len() as f64);
+				}
+			},
+			State::InsertAndCopyLength(_) => {
+				attribution.command_bits = bits;
+				attribution.command_start = attribution.heatmap.bits.len();
+			},
+			State::InsertLengthAndCopyLength(_) | State::InsertLiterals(_) | State::DistanceCode(_) | State::Distance(_) => {
+				attribution.command_bits += bits;
+			},
+			State::CopyLiterals(ref literals) => {
+				for _ in 0..literals.len() {
+					attribution.heatmap.bits.push(attribution.command_bits as f64 / literals.len() as f64);
+				}
+
+				attribution.command_bits = 0;
+			},
+			State::DataMetaBlockEnd if attribution.command_bits > 0 => {
+				// The last command of a meta-block may not copy, then its inserted bytes pay for it.
+				let n = attribution.heatmap.bits.len() - attribution.command_start;
+
+				if n > 0 {
+					for b in &mut attribution.heatmap.bits[attribution.command_start..] {
+						*b += attribution.command_bits as f64 / n as f64;
+					}
+				} else {
+					attribution.heatmap.meta_blocks.last_mut().unwrap().1 += attribution.command_bits;
+				}
+
+				attribution.command_bits = 0;
+			},
+			_ => match attribution.heatmap.meta_blocks.last_mut() {
+				Some(meta_block) => meta_block.1 += bits,
+				None => attribution.overhead_bits += bits,
+			},
+		}
+	}
+}
+
+/// Decodes a stream, and attributes its bits to the decompressed bytes. Returns an error,
+/// if the stream is invalid.
+///
+/// # Examples
+/// ```
+/// use brotli::heatmap;
+///
+/// let heatmap = heatmap(&[0x0b, 0x00, 0x80, 0x58, 0x03][..]).unwrap();
+///
+/// assert_eq!(b"X".to_vec(), heatmap.bytes);
+/// assert_eq!(vec![8.0], heatmap.bits);
+/// ```
+pub fn heatmap<R: Read>(r: R) -> io::Result<Heatmap> {
+	let mut decompressor = Decompressor::new(r);
+	decompressor.attribution = Some(Attribution{
+		pos: 0,
+		command_bits: 0,
+		command_start: 0,
+		overhead_bits: 0,
+		heatmap: Heatmap{
+			bytes: Vec::new(),
+			bits: Vec::new(),
+			meta_blocks: Vec::new(),
+		},
+	});
+
+	let mut bytes = Vec::new();
+
+	match decompressor.read_to_end(&mut bytes) {
+		Ok(_) => {},
+		Err(e) => return Err(e),
+	}
+
+	// the bits trailing the last meta-block
+	let bits = decompressor.attribute_bits();
+	let mut attribution = decompressor.attribution.take().unwrap();
+
+	match attribution.heatmap.meta_blocks.last_mut() {
+		Some(meta_block) => meta_block.1 += bits,
+		None => unreachable!(), // confirmed unreachable, a valid stream has at least one meta-block
+	}
+
+	attribution.heatmap.bytes = bytes;
+
+	Ok(attribution.heatmap)
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_attribute_every_bit() {
+		use super::heatmap;
+		use std::fs::File;
+		use std::io::Read;
+
+		for name in &["data/empty.compressed", "data/alice29.txt.compressed", "data/quickfox_repeated.compressed", "data/metablock_reset.compressed"] {
+			let mut data = Vec::new();
+			File::open(name).unwrap().read_to_end(&mut data).unwrap();
+
+			let heatmap = heatmap(&data[..]).unwrap();
+			let bits = heatmap.bits.iter().fold(0.0, |acc, b| acc + b);
+			let overhead = heatmap.meta_blocks.iter().fold(0, |acc, &(_, overhead)| acc + overhead);
+
+			assert_eq!(heatmap.bytes.len(), heatmap.bits.len());
+			assert!((bits + overhead as f64 - (data.len() * 8) as f64).abs() < 0.01, "{}", name);
+		}
+	}
+
+	#[test]
+	fn should_make_repetitions_cheap() {
+		use super::heatmap;
+		use ::compressor::Compressor;
+		use std::io::Write;
+
+		let mut input = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".to_vec();
+		let unique = input.len();
+		for _ in 0..50 {
+			input.extend_from_slice(b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. ");
+		}
+
+		let mut compressor = Compressor::new(Vec::new());
+		compressor.write_all(&input).unwrap();
+		let compressed = compressor.finish().unwrap();
+
+		let heatmap = heatmap(&compressed[..]).unwrap();
+		let first = heatmap.bits[..unique].iter().fold(0.0, |acc, b| acc + b) / unique as f64;
+		let rest = heatmap.bits[unique..].iter().fold(0.0, |acc, b| acc + b) / (input.len() - unique) as f64;
+
+		assert_eq!(input, heatmap.bytes);
+		assert!(rest * 10.0 < first);
+	}
+
+	#[test]
+	fn should_render_json_and_html() {
+		use super::heatmap;
+
+		let heatmap = heatmap(&[0x0b, 0x00, 0x80, 0x58, 0x03][..]).unwrap();
+
+		assert_eq!("{\"bits\":[8.00],\"meta_blocks\":[{\"offset\":0,\"overhead_bits\":24},{\"offset\":1,\"overhead_bits\":8}]}", heatmap.to_json());
+		assert!(heatmap.to_html().contains("title=\"0: 8.00 bits\">X</span>"));
+	}
+}
@@ -48,5 +48,144 @@ pub fn codes_from_lengths(lengths: &[usize]) -> tree::Tree {
 	codes_from_lengths_and_symbols(lengths, &symbols)
 }
 
+pub fn bit_strings_from_lengths(lengths: &[usize]) -> Vec<Vec<bool>> {
+	let max_length = lengths.iter().fold(0, |acc, &len| if len > acc { len } else { acc });
+	let mut bl_count = vec![0; max_length + 1];
+	for &len in lengths {
+		bl_count[len] += 1;
+	}
+
+	let mut code = 0;
+	let mut next_code = vec![0; max_length + 1];
+	for bits in 1..max_length + 1 {
+		code = (code + bl_count[bits - 1]) << 1;
+		next_code[bits] = code;
+	}
+
+	let mut bit_strings = Vec::with_capacity(lengths.len());
+	for &len in lengths {
+		if len > 0 {
+			bit_strings.push(bit_string_from_code_and_length(next_code[len], len));
+			next_code[len] += 1;
+		} else {
+			bit_strings.push(Vec::new());
+		}
+	}
+
+	bit_strings
+}
+
+#[test]
+fn should_assign_canonical_bit_strings() {
+	assert_eq!(vec![vec![true, false], vec![false], vec![], vec![true, true]], bit_strings_from_lengths(&[2, 1, 0, 2]));
+}
+
+/// Computes code lengths for the symbols of a histogram, where no code is
+/// longer than max_length bits. Symbols with a frequency of zero get length 0.
+///
+/// Uses the package-merge algorithm, so the code is optimal among all codes
+/// that respect the length limit. A single used symbol gets length 1.
+pub fn lengths_from_frequencies(frequencies: &[usize], max_length: usize) -> Vec<usize> {
+	let mut lengths = vec![0; frequencies.len()];
+	let mut leaves = frequencies.iter().enumerate()
+		.filter(|&(_, &f)| f > 0)
+		.map(|(symbol, &f)| (f, symbol))
+		.collect::<Vec<_>>();
+	let n = leaves.len();
+
+	match n {
+		0 => return lengths,
+		1 => {
+			lengths[leaves[0].1] = 1;
+			return lengths;
+		},
+		2 => {
+			lengths[leaves[0].1] = 1;
+			lengths[leaves[1].1] = 1;
+			return lengths;
+		},
+		_ => {},
+	}
+
+	assert!(max_length < 32 && n <= 1 << max_length);
+
+	leaves.sort();
 
+	// lists[0] holds the leaves for the deepest level, every following list merges the
+	// leaves with the packages formed from pairs of its predecessor. An item is a leaf,
+	// if its flag is true.
+	let mut lists = Vec::with_capacity(max_length);
+	lists.push(leaves.iter().map(|&(w, _)| (w, true)).collect::<Vec<_>>());
 
+	for level in 1..max_length {
+		let packages = lists[level - 1].chunks(2)
+			.filter(|pair| pair.len() == 2)
+			.map(|pair| (pair[0].0 + pair[1].0, false))
+			.collect::<Vec<_>>();
+		let mut list = Vec::with_capacity(n + packages.len());
+		let (mut i, mut j) = (0, 0);
+
+		while i < n || j < packages.len() {
+			if j >= packages.len() || (i < n && leaves[i].0 <= packages[j].0) {
+				list.push((leaves[i].0, true));
+				i += 1;
+			} else {
+				list.push(packages[j]);
+				j += 1;
+			}
+		}
+
+		lists.push(list);
+	}
+
+	// Select the 2n - 2 cheapest items of the last list. Leaves that are selected on a
+	// level are always the lightest ones, and the selected packages on a level expand
+	// into the first items of the level below.
+	let mut n_selected = 2 * n - 2;
+
+	for list in lists.iter().rev() {
+		let n_leaves = list[0..n_selected].iter().filter(|&&(_, is_leaf)| is_leaf).count();
+
+		for &(_, symbol) in &leaves[0..n_leaves] {
+			lengths[symbol] += 1;
+		}
+
+		n_selected = 2 * (n_selected - n_leaves);
+	}
+
+	lengths
+}
+
+#[test]
+fn should_build_complete_code() {
+	let lengths = lengths_from_frequencies(&[10, 0, 3, 3, 1, 1, 20], 15);
+
+	assert_eq!(vec![2, 0, 4, 3, 5, 5, 1], lengths);
+}
+
+#[test]
+fn should_limit_code_lengths() {
+	let frequencies = (0..30).map(|i| 1 << i).collect::<Vec<_>>();
+	let lengths = lengths_from_frequencies(&frequencies, 15);
+
+	assert!(lengths.iter().all(|&len| len > 0 && len <= 15));
+	assert_eq!(32768, lengths.iter().fold(0, |acc, &len| acc + (32768 >> len)));
+}
+
+#[test]
+fn should_build_optimal_limited_code() {
+	// unlimited, the optimal code would need lengths up to 5
+	let frequencies = [1, 1, 2, 4, 8, 16];
+	let lengths = lengths_from_frequencies(&frequencies, 3);
+
+	assert!(lengths.iter().all(|&len| len <= 3));
+	assert_eq!(8, lengths.iter().fold(0, |acc, &len| acc + (8 >> len)));
+	assert_eq!(2 * 3 + 2 * 3 + 4 * 3 + 8 * 2 + 16 * 2, frequencies.iter().zip(lengths.iter()).fold(0, |acc, (&f, &len)| acc + f * len));
+}
+
+#[test]
+fn should_handle_degenerate_histograms() {
+	assert_eq!(vec![0, 0, 0], lengths_from_frequencies(&[0, 0, 0], 15));
+	assert_eq!(vec![0, 1, 0], lengths_from_frequencies(&[0, 7, 0], 15));
+	assert_eq!(vec![1, 0, 1], lengths_from_frequencies(&[1000, 0, 1], 15));
+}
@@ -41,9 +41,9 @@ impl Tree {
 
 	pub fn from_raw_data(buf: Vec<Option<Symbol>>, len: usize, last_symbol: Option<Symbol>) -> Tree {
 		Tree {
-			buf: buf,
-			len: len,
-			last_symbol: last_symbol,
+			buf,
+			len,
+			last_symbol,
 		}
 	}
 
@@ -81,6 +81,39 @@ impl Tree {
 		}
 	}
 
+	pub fn bit_string_for_symbol(&self, symbol: Symbol) -> Option<Vec<bool>> {
+		if self.len == 1 && self.last_symbol == Some(symbol) {
+			return Some(Vec::new());
+		}
+
+		for (index, entry) in self.buf.iter().enumerate() {
+			if *entry == Some(symbol) {
+				let len = (0usize.leading_zeros() - (index + 1).leading_zeros() - 1) as usize;
+
+				return Some(super::bit_string_from_code_and_length(index + 1 - (1 << len), len));
+			}
+		}
+
+		None
+	}
+
+	/// Returns all symbols with their codes, ordered by code.
+	pub fn codes(&self) -> Vec<(Symbol, Vec<bool>)> {
+		if self.len == 1 {
+			return vec![(self.last_symbol.unwrap(), Vec::new())];
+		}
+
+		let mut codes = self.buf.iter().enumerate().filter_map(|(index, entry)| entry.map(|symbol| {
+			let len = (0usize.leading_zeros() - (index + 1).leading_zeros() - 1) as usize;
+
+			(symbol, super::bit_string_from_code_and_length(index + 1 - (1 << len), len))
+		})).collect::<Vec<_>>();
+
+		codes.sort_by(|a, b| a.1.cmp(&b.1));
+
+		codes
+	}
+
 	pub fn lookup_symbol<R: Read>(&self, mut r: &mut BitReader<R>) -> Result<Option<Symbol>, ::bitreader::BitReaderError, >  {
 		// println!("self.len = {:?}", self.len);
 
@@ -193,6 +226,20 @@ mod tests {
 		assert_eq!(tree.lookup_symbol(&mut lookup_stream), Ok(Some(6667)));
 	}
 
+	#[test]
+	fn should_find_bit_string_for_symbol() {
+		use super::Tree;
+
+		let mut tree = Tree::with_max_depth(2);
+		tree.insert(&[false, false], 6666);
+		tree.insert(&[true], 666);
+		tree.insert(&[false, true], 6667);
+
+		assert_eq!(Some(vec![false, true]), tree.bit_string_for_symbol(6667));
+		assert_eq!(Some(vec![true]), tree.bit_string_for_symbol(666));
+		assert_eq!(None, tree.bit_string_for_symbol(6668));
+	}
+
 	#[test]
 	fn should_lookup_first_level_leaf_right() {
 		use ::bitreader::BitReader;
@@ -4,25 +4,79 @@
 //! This allows a consumer to wrap a Brotli-compressed Stream into a Decompressor,
 //! using the familiar methods provided by the Read trait for processing
 //! the uncompressed stream.
+//!
+//! Conversely, a Compressor wraps a Write, and compresses everything that is
+//! written to it into a Brotli stream.
 
 /// bitreader wraps a Read to provide bit-oriented read access to a stream.
 mod bitreader;
+/// bitwriter provides bit-oriented write access to a buffer, the counterpart to bitreader.
+mod bitwriter;
+/// commands iterates over the commands that a stream decodes to.
+mod commands;
+pub use ::commands::{ BlockCategory, Command, Commands };
+/// compressor provides a Write adapter that compresses a stream.
+mod compressor;
+pub use ::compressor::{ Compressor, max_compressed_size };
+/// concat joins catable streams into one stream.
+mod concat;
+pub use ::concat::concat;
+/// crc32 computes checksums, which detect corruption in frames.
+mod crc32;
+/// dcb frames streams that are compressed with a dictionary, as in HTTP Compression Dictionary Transport.
+mod dcb;
+pub use ::dcb::{ DcbCompressor, DcbDecompressor, DcbDictionaries };
+/// dump annotates the bits of a stream, for analysis.
+mod dump;
+pub use ::dump::dump;
+/// framed wraps streams into a self-describing container, with sizes and checksums.
+mod framed;
+pub use ::framed::{ FrameError, FramedCompressor, FramedDecompressor };
+/// heatmap attributes the bits of a stream to its decompressed bytes.
+mod heatmap;
+pub use ::heatmap::{ Heatmap, heatmap };
 mod huffman;
+/// parallel compresses large inputs in chunks, and decompresses independent chunks, on multiple threads.
+mod parallel;
+pub use ::parallel::{ ParallelCompressor, ParallelDecompressor };
+/// peek reads the stream header, and the header of the first meta-block.
+mod peek;
+pub use ::peek::{ MetaBlockType, StreamHeader, peek_header };
+/// recovery decodes as much as possible of corrupt streams.
+mod recovery;
+pub use ::recovery::{ Corruption, Segment, recover };
 /// ringbuffer provides a data structure RingBuffer that uses a single, fixed-size buffer as if it were connected end-to-end.
 /// This structure lends itself easily to buffering data streams.
 mod ringbuffer;
-
-
-mod dictionary;
-use ::dictionary::{ BROTLI_DICTIONARY_OFFSETS_BY_LENGTH, BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH, BROTLI_DICTIONARY };
+/// seekable writes files of independently compressed chunks with a seek table, for random access.
+mod seekable;
+pub use ::seekable::{ SeekableCompressor, SeekableDecompressor };
+/// sha256 computes digests, which identify dictionaries.
+mod sha256;
+/// shareddictionary parses serialized dictionaries, which replace or extend the static dictionary.
+mod shareddictionary;
+pub use ::shareddictionary::SharedDictionary;
+/// stats collects statistics of meta-blocks, which show how a stream spends its bits.
+mod stats;
+pub use ::stats::{ MetaBlockStats, stats };
+/// validate checks streams for errors, without decompressing them to anything.
+mod validate;
+pub use ::validate::{ StreamInfo, validate };
+
+/// dictionary holds the static dictionary of RFC 7932, and trains custom dictionaries.
+pub mod dictionary;
 mod lookuptable;
 use ::lookuptable::{ LUT_0, LUT_1, LUT_2, INSERT_LENGTHS_AND_COPY_LENGTHS };
 mod transformation;
-use ::transformation::transformation;
 
 use ::bitreader::{ BitReader, BitReaderError };
+use ::commands::Recorder;
+use ::dump::Trace;
+use ::heatmap::Attribution;
+use ::stats::Collector;
 use ::huffman::tree::Tree;
 use ::ringbuffer::RingBuffer;
+use ::sha256::sha256;
 
 use std::collections::VecDeque;
 use std::cmp;
@@ -67,6 +121,10 @@ type DistanceCode = u32;
 type Distance = u32;
 type HSkip = u8;
 
+/// Starts the content of a metadata meta-block that identifies the raw dictionary,
+/// which a stream has been compressed with. The SHA-256 digest of the dictionary follows.
+const DICTIONARY_ID_MAGIC: &[u8; 4] = b"BRPD";
+
 #[derive(Debug, Clone, PartialEq)]
 enum PrefixCodeKind {
 	Simple,
@@ -290,10 +348,64 @@ enum State {
 	StreamEnd,
 }
 
+impl State {
+	/// Returns the name of the state, without the value it holds.
+	fn name(&self) -> &'static str {
+		match *self {
+			State::StreamBegin => "StreamBegin",
+			State::HeaderBegin => "HeaderBegin",
+			State::WBits(_) => "WBits",
+			State::HeaderEnd => "HeaderEnd",
+			State::HeaderMetaBlockBegin => "HeaderMetaBlockBegin",
+			State::IsLast(_) => "IsLast",
+			State::IsLastEmpty(_) => "IsLastEmpty",
+			State::MNibbles(_) => "MNibbles",
+			State::MSkipBytes(_) => "MSkipBytes",
+			State::MSkipLen(_) => "MSkipLen",
+			State::MLen(_) => "MLen",
+			State::IsUncompressed(_) => "IsUncompressed",
+			State::MLenLiterals(_) => "MLenLiterals",
+			State::NBltypesL(_) => "NBltypesL",
+			State::PrefixCodeBlockTypesLiterals(_) => "PrefixCodeBlockTypesLiterals",
+			State::PrefixCodeBlockCountsLiterals(_) => "PrefixCodeBlockCountsLiterals",
+			State::FirstBlockCountLiterals(_) => "FirstBlockCountLiterals",
+			State::NBltypesI(_) => "NBltypesI",
+			State::PrefixCodeBlockTypesInsertAndCopyLengths(_) => "PrefixCodeBlockTypesInsertAndCopyLengths",
+			State::PrefixCodeBlockCountsInsertAndCopyLengths(_) => "PrefixCodeBlockCountsInsertAndCopyLengths",
+			State::FirstBlockCountInsertAndCopyLengths(_) => "FirstBlockCountInsertAndCopyLengths",
+			State::NBltypesD(_) => "NBltypesD",
+			State::PrefixCodeBlockTypesDistances(_) => "PrefixCodeBlockTypesDistances",
+			State::PrefixCodeBlockCountsDistances(_) => "PrefixCodeBlockCountsDistances",
+			State::FirstBlockCountDistances(_) => "FirstBlockCountDistances",
+			State::NPostfix(_) => "NPostfix",
+			State::NDirect(_) => "NDirect",
+			State::ContextModesLiterals(_) => "ContextModesLiterals",
+			State::NTreesL(_) => "NTreesL",
+			State::NTreesD(_) => "NTreesD",
+			State::ContextMapDistances(_) => "ContextMapDistances",
+			State::ContextMapLiterals(_) => "ContextMapLiterals",
+			State::PrefixCodesLiterals(_) => "PrefixCodesLiterals",
+			State::PrefixCodesInsertAndCopyLengths(_) => "PrefixCodesInsertAndCopyLengths",
+			State::PrefixCodesDistances(_) => "PrefixCodesDistances",
+			State::DataMetaBlockBegin => "DataMetaBlockBegin",
+			State::InsertAndCopyLength(_) => "InsertAndCopyLength",
+			State::InsertLengthAndCopyLength(_) => "InsertLengthAndCopyLength",
+			State::InsertLiterals(_) => "InsertLiterals",
+			State::DistanceCode(_) => "DistanceCode",
+			State::Distance(_) => "Distance",
+			State::CopyLiterals(_) => "CopyLiterals",
+			State::DataMetaBlockEnd => "DataMetaBlockEnd",
+			State::MetaBlockEnd => "MetaBlockEnd",
+			State::StreamEnd => "StreamEnd",
+		}
+	}
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum DecompressorError {
 	CodeLengthsChecksum,
 	ExpectedEndOfStream,
+	ExceededDictionary,
 	ExceededExpectedBytes,
 	InvalidBlockCountCode,
 	InvalidBlockSwitchCommandCode,
@@ -303,6 +415,7 @@ enum DecompressorError {
 	InvalidTransformId,
 	InvalidNonPositiveDistance,
 	LessThanTwoNonZeroCodeLengths,
+	MissingDictionary,
 	NoCodeLength,
 	NonZeroFillBit,
 	NonZeroReservedBit,
@@ -316,6 +429,7 @@ enum DecompressorError {
 	RingBufferError,
 	RunLengthExceededSizeOfContextMap,
 	UnexpectedEOF,
+	WrongDictionary,
 }
 
 impl Display for DecompressorError {
@@ -330,6 +444,7 @@ impl Error for DecompressorError {
 		match *self {
 			DecompressorError::CodeLengthsChecksum => "Code length check sum did not add up in complex prefix code",
 			DecompressorError::ExpectedEndOfStream => "Expected end-of-stream, but stream did not end",
+			DecompressorError::ExceededDictionary => "Encountered copy beyond the end of the raw dictionary",
 			DecompressorError::ExceededExpectedBytes => "More uncompressed bytes than expected in meta-block",
 			DecompressorError::InvalidBlockCountCode => "Encountered invalid value for block count code",
 			DecompressorError::InvalidBlockSwitchCommandCode => "Encountered invalid value for block switch command code",
@@ -339,6 +454,7 @@ impl Error for DecompressorError {
 			DecompressorError::InvalidTransformId => "Encountered invalid transform id in reference to static dictionary",
 			DecompressorError::InvalidNonPositiveDistance => "Encountered invalid non-positive distance",
 			DecompressorError::LessThanTwoNonZeroCodeLengths => "Encountered invalid complex prefix code with less than two non-zero codelengths",
+			DecompressorError::MissingDictionary => "Stream has been compressed with a dictionary, but no dictionary was given",
 			DecompressorError::NoCodeLength => "Encountered invalid complex prefix code with all zero codelengths",
 			DecompressorError::NonZeroFillBit => "Enocuntered non-zero fill bit",
 			DecompressorError::NonZeroReservedBit => "Enocuntered non-zero reserved bit",
@@ -352,7 +468,86 @@ impl Error for DecompressorError {
 			DecompressorError::RingBufferError => "Error accessing distance ring buffer",
 			DecompressorError::RunLengthExceededSizeOfContextMap => "Run length excceeded declared length of context map",
 			DecompressorError::UnexpectedEOF => "Encountered unexpected EOF",
+			DecompressorError::WrongDictionary => "Stream has been compressed with a different dictionary than the given one",
+		}
+	}
+}
+
+/// Error in a malformed Brotli stream, with the position at which decoding has failed.
+/// Decompressor returns io::Errors of kind InvalidData, which carry a StreamError, so that
+/// the position can be retrieved with downcast_ref(), and compared with the output of dump().
+///
+/// # Examples
+/// ```
+/// use std::io::Read;
+/// use brotli::{ Decompressor, StreamError };
+///
+/// // a non-zero bit trails the stream
+/// let e = Decompressor::new(&[0x0b, 0x00, 0x80, 0x58, 0x0b][..]).read_to_end(&mut Vec::new()).unwrap_err();
+/// let stream_error = e.get_ref().and_then(|e| e.downcast_ref::<StreamError>()).unwrap();
+///
+/// assert_eq!("StreamEnd", stream_error.state);
+/// assert_eq!(Some(1), stream_error.meta_block);
+/// assert_eq!(40, stream_error.bit_offset);
+/// assert_eq!(1, stream_error.byte_offset);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamError {
+	/// description of the error
+	pub message: String,
+	/// name of the state, which the decompressor has been in, when decoding failed
+	pub state: &'static str,
+	/// index of the meta-block, in which decoding failed, or None, if it failed in the stream header
+	pub meta_block: Option<usize>,
+	/// index of the command within the meta-block, in which decoding failed, or None, if it
+	/// failed before the first command of the meta-block
+	pub command: Option<usize>,
+	/// offset of the bit in the compressed stream, at which the error has been detected
+	pub bit_offset: u64,
+	/// number of bytes, which have been decompressed before the error
+	pub byte_offset: u64,
+}
+
+impl Display for StreamError {
+	fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+		let mut text = format!("{} (state {}", self.message, self.state);
+
+		if let Some(meta_block) = self.meta_block {
+			text.push_str(&format!(", meta-block {}", meta_block));
+		}
+
+		if let Some(command) = self.command {
+			text.push_str(&format!(", command {}", command));
 		}
+
+		text.push_str(&format!(", bit offset {}, byte offset {})", self.bit_offset, self.byte_offset));
+
+		fmt.write_str(&text)
+	}
+}
+
+impl Error for StreamError {
+	fn description(&self) -> &str {
+		&self.message
+	}
+}
+
+/// Returns a copy of an io::Error, that is to be returned again, with its StreamError, if it
+/// carries one, or with its kind and message otherwise.
+pub(crate) fn clone_error(e: &io::Error) -> io::Error {
+	match e.get_ref().and_then(|inner| inner.downcast_ref::<StreamError>()) {
+		Some(stream_error) => io::Error::new(e.kind(), stream_error.clone()),
+		None => io::Error::new(e.kind(), e.to_string()),
+	}
+}
+
+/// Callback for the progress of a Decompressor, with the numbers of compressed bytes that have
+/// been consumed, and of decompressed bytes that have been produced.
+struct Progress(Box<dyn FnMut(u64, u64) + Send>);
+
+impl fmt::Debug for Progress {
+	fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+		fmt.write_str("Progress")
 	}
 }
 
@@ -391,6 +586,36 @@ pub struct Decompressor<R: Read> {
 	/// at the beginning of the stream, and then
 	/// lives until the end
 	distance_buf: RingBuffer<Distance>,
+	/// raw dictionary, which backward references reach beyond the output window
+	dictionary: Option<Vec<u8>>,
+	/// number of bytes of the raw dictionary
+	dictionary_len: usize,
+	/// static dictionary, for references beyond the output window
+	shared_dictionary: SharedDictionary,
+	/// annotations of the bits that have been read, if the stream is being dumped
+	trace: Option<Trace>,
+	/// commands that have been decoded, if the stream is being iterated over by commands
+	recorder: Option<Recorder>,
+	/// statistics of the meta-blocks, if they are being collected
+	collector: Option<Collector>,
+	/// bits of each decompressed byte, if they are being attributed
+	attribution: Option<Attribution>,
+	/// whether decoded bytes are only counted, because the stream is being validated
+	discard_output: bool,
+	/// number of meta-blocks, that have been started
+	count_meta_blocks: usize,
+	/// number of commands, that have been started in the current meta-block
+	count_commands: usize,
+	/// stream header, once it has been read
+	stream_header: Option<StreamHeader>,
+	/// whether decompress() returns, once the header of the first meta-block has been read
+	peek: bool,
+	/// callback, which is invoked at the end of each meta-block
+	progress: Option<Progress>,
+	/// error, that has ended decoding
+	error: Option<DecompressorError>,
+	/// whether decoding stops at the end of the stream, which may be followed by other streams
+	member: bool,
 }
 
 impl<R: Read> Decompressor<R> {
@@ -406,9 +631,117 @@ impl<R: Read> Decompressor<R> {
 			count_output: 0,
 			literal_buf: RingBuffer::from_vec(vec![0, 0]),
 			distance_buf: RingBuffer::from_vec(vec![4, 11, 15, 16]),
+			dictionary: None,
+			dictionary_len: 0,
+			shared_dictionary: SharedDictionary::default(),
+			trace: None,
+			recorder: None,
+			collector: None,
+			attribution: None,
+			discard_output: false,
+			count_meta_blocks: 0,
+			count_commands: 0,
+			stream_header: None,
+			peek: false,
+			progress: None,
+			error: None,
+			member: false,
+		}
+	}
+
+	/// Creates Decompressor from Read, for a stream that has been compressed with a raw
+	/// dictionary. As with a compound dictionary of the reference implementation, e.g. for
+	/// `brotli -D`, distances beyond the output window, or beyond the output while it is
+	/// shorter, refer to the dictionary, from its last byte backwards. The static dictionary
+	/// follows beyond the raw dictionary.
+	///
+	/// If the stream identifies the dictionary it has been compressed with, like streams from
+	/// Compressor::with_dictionary() do, decompression fails for a different dictionary.
+	pub fn with_dictionary(r: R, dictionary: &[u8]) -> Decompressor<R> {
+		let mut decompressor = Decompressor::new(r);
+		decompressor.dictionary = Some(dictionary.to_vec());
+		decompressor.dictionary_len = dictionary.len();
+
+		decompressor
+	}
+
+	/// Creates Decompressor from Read, for a stream that refers to a shared dictionary
+	/// in place of the static dictionary of RFC 7932. If the shared dictionary holds a raw
+	/// dictionary, it is referred to as with Decompressor::with_dictionary().
+	pub fn with_shared_dictionary(r: R, dictionary: &SharedDictionary) -> Decompressor<R> {
+		let mut decompressor = if dictionary.prefix().is_empty() {
+			Decompressor::new(r)
+		} else {
+			Decompressor::with_dictionary(r, dictionary.prefix())
+		};
+		decompressor.shared_dictionary = dictionary.clone();
+
+		decompressor
+	}
+
+	/// Returns the number of compressed bytes, that have been consumed so far. A partially
+	/// consumed byte counts as consumed.
+	pub fn total_in(&self) -> u64 {
+		self.in_stream.global_bit_pos().div_ceil(8) as u64
+	}
+
+	/// Returns the number of bytes, that have been decompressed so far, including those that
+	/// read() has not returned yet.
+	pub fn total_out(&self) -> u64 {
+		self.count_output as u64
+	}
+
+	/// Sets a callback, which is invoked at the end of each meta-block, with total_in() and
+	/// total_out(), e.g. to show the progress of a large stream.
+	///
+	/// # Examples
+	/// ```
+	/// use std::io::Read;
+	/// use std::sync::mpsc::channel;
+	/// use brotli::Decompressor;
+	///
+	/// let (sender, receiver) = channel();
+	/// let mut decompressor = Decompressor::new(&[0x0b, 0x00, 0x80, 0x58, 0x03][..]);
+	/// decompressor.set_progress_callback(move |total_in, total_out| sender.send((total_in, total_out)).unwrap());
+	/// decompressor.read_to_end(&mut Vec::new()).unwrap();
+	///
+	/// assert_eq!(vec![(4, 1), (5, 1)], receiver.try_iter().collect::<Vec<_>>());
+	/// ```
+	pub fn set_progress_callback<F: FnMut(u64, u64) + Send + 'static>(&mut self, callback: F) {
+		self.progress = Some(Progress(Box::new(callback)));
+	}
+
+	fn report_progress(&mut self) {
+		let (total_in, total_out) = (self.total_in(), self.total_out());
+
+		if let Some(ref mut progress) = self.progress {
+			(progress.0)(total_in, total_out);
 		}
 	}
 
+	/// Returns a reference to the underlying reader.
+	pub(crate) fn get_ref(&self) -> &R {
+		self.in_stream.get_ref()
+	}
+
+	/// Returns the underlying reader, whose position is undefined, as the stream is read ahead.
+	pub(crate) fn into_inner(self) -> R {
+		self.in_stream.into_inner()
+	}
+
+	/// Returns an io::Error of kind InvalidData for an error of the current state, with the
+	/// position in the stream.
+	pub(crate) fn stream_error(&self, e: DecompressorError) -> io::Error {
+		io::Error::new(io::ErrorKind::InvalidData, StreamError{
+			message: e.to_string(),
+			state: self.state.name(),
+			meta_block: self.count_meta_blocks.checked_sub(1),
+			command: self.count_commands.checked_sub(1),
+			bit_offset: self.in_stream.global_bit_pos() as u64,
+			byte_offset: self.count_output as u64,
+		})
+	}
+
 	fn parse_wbits(&mut self) -> Result<State, DecompressorError> {
 		match self.header.wbits_codes.lookup_symbol(&mut self.in_stream) {
 			Ok(Some(symbol)) => Ok(State::WBits(symbol as WBits)),
@@ -417,6 +750,20 @@ impl<R: Read> Decompressor<R> {
 		}
 	}
 
+	/// Checks that metadata, which identifies a dictionary, refers to the given dictionary.
+	/// Any other metadata is ignored.
+	fn check_dictionary_id(&self, metadata: &[u8]) -> Result<(), DecompressorError> {
+		if metadata.len() != DICTIONARY_ID_MAGIC.len() + 32 || !metadata.starts_with(DICTIONARY_ID_MAGIC) {
+			return Ok(());
+		}
+
+		match self.dictionary {
+			None => Err(DecompressorError::MissingDictionary),
+			Some(ref dictionary) if sha256(dictionary)[..] != metadata[DICTIONARY_ID_MAGIC.len()..] => Err(DecompressorError::WrongDictionary),
+			Some(_) => Ok(()),
+		}
+	}
+
 	fn parse_is_last(&mut self) -> Result<State, DecompressorError> {
 		match self.in_stream.read_bit() {
 			Ok(bit) => Ok(State::IsLast(bit)),
@@ -1021,11 +1368,16 @@ impl<R: Read> Decompressor<R> {
 		// println!("NTREESL = {:?}", n_trees_l);
 		// println!("alphabet_size = {:?}", alphabet_size);
 
-		for _ in 0..n_trees_l {
+		for i in 0..n_trees_l {
 			prefix_codes.push(match self.parse_prefix_code(alphabet_size) {
 				Ok(prefix_code) => prefix_code,
 				Err(e) => return Err(e),
 			});
+
+			if self.trace.is_some() {
+				let text = format!("prefix code literals {}: {}", i, ::dump::describe_prefix_code(&prefix_codes[i]));
+				self.trace_field(text);
+			}
 		}
 
 		Ok(State::PrefixCodesLiterals(prefix_codes))
@@ -1039,11 +1391,16 @@ impl<R: Read> Decompressor<R> {
 		// println!("NBLTYPESI = {:?}", n_bltypes_i);
 		// println!("alphabet_size = {:?}", alphabet_size);
 
-		for _ in 0..n_bltypes_i {
+		for i in 0..n_bltypes_i {
 			prefix_codes.push(match self.parse_prefix_code(alphabet_size) {
 				Ok(prefix_code) => prefix_code,
 				Err(e) => return Err(e),
 			});
+
+			if self.trace.is_some() {
+				let text = format!("prefix code insert and copy lengths {}: {}", i, ::dump::describe_prefix_code(&prefix_codes[i]));
+				self.trace_field(text);
+			}
 		}
 
 		Ok(State::PrefixCodesInsertAndCopyLengths(prefix_codes))
@@ -1057,11 +1414,16 @@ impl<R: Read> Decompressor<R> {
 		// println!("NDIRECT = {:?}", self.meta_block.header.n_direct.unwrap());
 		// println!("NPOSTFIX = {:?}", self.meta_block.header.n_postfix.unwrap());
 
-		for _ in 0..n_trees_d {
+		for i in 0..n_trees_d {
 			prefix_codes.push(match self.parse_prefix_code(alphabet_size) {
 				Ok(prefix_code) => prefix_code,
 				Err(e) => return Err(e),
 			});
+
+			if self.trace.is_some() {
+				let text = format!("prefix code distances {}: {}", i, ::dump::describe_prefix_code(&prefix_codes[i]));
+				self.trace_field(text);
+			}
 		}
 
 		Ok(State::PrefixCodesDistances(prefix_codes))
@@ -1257,7 +1619,21 @@ impl<R: Read> Decompressor<R> {
 
 		let prefix_tree_counts = self.meta_block.prefix_tree_block_counts_literals.as_ref().unwrap().clone();
 
-		self.parse_block_switch_command(prefix_tree_types, btype, btype_prev, n_bltypes, prefix_tree_counts)
+		match self.parse_block_switch_command(prefix_tree_types, btype, btype_prev, n_bltypes, prefix_tree_counts) {
+			Ok((block_type, block_count)) => {
+				if self.trace.is_some() {
+					self.trace_field(format!("block switch literals => block type {}, block count {}", block_type, block_count));
+				}
+
+				self.record(Command::BlockSwitch{
+					category: BlockCategory::Literals,
+					block_type: block_type as usize,
+				});
+
+				Ok((block_type, block_count))
+			},
+			Err(e) => Err(e),
+		}
 	}
 
 	fn parse_block_switch_command_insert_and_copy_lengths(&mut self) -> Result<BlockSwitch, DecompressorError> {
@@ -1269,7 +1645,21 @@ impl<R: Read> Decompressor<R> {
 
 		let prefix_tree_counts = self.meta_block.prefix_tree_block_counts_insert_and_copy_lengths.as_ref().unwrap().clone();
 
-		self.parse_block_switch_command(prefix_tree_types, btype, btype_prev, n_bltypes, prefix_tree_counts)
+		match self.parse_block_switch_command(prefix_tree_types, btype, btype_prev, n_bltypes, prefix_tree_counts) {
+			Ok((block_type, block_count)) => {
+				if self.trace.is_some() {
+					self.trace_field(format!("block switch insert and copy lengths => block type {}, block count {}", block_type, block_count));
+				}
+
+				self.record(Command::BlockSwitch{
+					category: BlockCategory::InsertAndCopyLengths,
+					block_type: block_type as usize,
+				});
+
+				Ok((block_type, block_count))
+			},
+			Err(e) => Err(e),
+		}
 	}
 
 	fn parse_block_switch_command_distances(&mut self) -> Result<BlockSwitch, DecompressorError> {
@@ -1280,7 +1670,56 @@ impl<R: Read> Decompressor<R> {
 
 		let prefix_tree_counts = self.meta_block.prefix_tree_block_counts_distances.as_ref().unwrap().clone();
 
-		self.parse_block_switch_command(prefix_tree_types, btype, btype_prev, n_bltypes, prefix_tree_counts)
+		match self.parse_block_switch_command(prefix_tree_types, btype, btype_prev, n_bltypes, prefix_tree_counts) {
+			Ok((block_type, block_count)) => {
+				if self.trace.is_some() {
+					self.trace_field(format!("block switch distances => block type {}, block count {}", block_type, block_count));
+				}
+
+				self.record(Command::BlockSwitch{
+					category: BlockCategory::Distances,
+					block_type: block_type as usize,
+				});
+
+				Ok((block_type, block_count))
+			},
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Returns the context id of the next literal, in the context mode of the current literal block type.
+	fn literal_context_id(&self) -> usize {
+		let btype = self.meta_block.btype_l as usize;
+		let context_mode = self.meta_block.context_modes_literals.as_ref().unwrap()[btype];
+
+		// debug(&format!("[p1, p2] = {:?}", self.literal_buf));
+		// debug(&format!("Context Mode = {:?}", context_mode));
+
+		match context_mode {
+			0 => {
+				let p1 = *self.literal_buf.nth(0).unwrap() as usize;
+
+				p1 & 0x3f
+			},
+			1 => {
+				let p1 = *self.literal_buf.nth(0).unwrap() as usize;
+
+				p1 >> 2
+			},
+			2 => {
+				let p1 = *self.literal_buf.nth(0).unwrap() as usize;
+				let p2 = *self.literal_buf.nth(1).unwrap() as usize;
+
+				LUT_0[p1] | LUT_1[p2]
+			},
+			3 => {
+				let p1 = *self.literal_buf.nth(0).unwrap() as usize;
+				let p2 = *self.literal_buf.nth(1).unwrap() as usize;
+
+				(LUT_2[p1] << 3) | LUT_2[p2]
+			},
+			_ => unreachable!(), // confirmed unreachable, context_mode is always read from two bits
+		}
 	}
 
 	fn parse_insert_literals(&mut self) -> Result<State, DecompressorError> {
@@ -1288,9 +1727,15 @@ impl<R: Read> Decompressor<R> {
 		let insert_length = self.meta_block.insert_length.unwrap() as usize;
 		let mut literals = vec![0; insert_length];
 
-		for mut lit in &mut literals {
+		for i in 0..insert_length {
 			// debug(&format!("parse_insert_literals(): blen_l = {:?}", self.meta_block.blen_l));
 
+			// annotate the literals before the block switch apart from those after it
+			if self.meta_block.blen_l == Some(0) {
+				self.trace_literals(&literals, i);
+				self.record_literals(&literals, i);
+			}
+
 			match self.meta_block.blen_l {
 				None => {},
 				Some(0) => match self.parse_block_switch_command_literals() {
@@ -1309,36 +1754,7 @@ impl<R: Read> Decompressor<R> {
 
 			// println!("btype = {:?}", btype);
 
-			let context_mode = self.meta_block.context_modes_literals.as_ref().unwrap()[btype];
-
-			// debug(&format!("[p1, p2] = {:?}", self.literal_buf));
-			// debug(&format!("Context Mode = {:?}", context_mode));
-
-			let cid = match context_mode {
-				0 => {
-					let p1 = *self.literal_buf.nth(0).unwrap() as usize;
-
-					p1 & 0x3f
-				},
-				1 => {
-					let p1 = *self.literal_buf.nth(0).unwrap() as usize;
-
-					p1 >> 2
-				},
-				2 => {
-					let p1 = *self.literal_buf.nth(0).unwrap() as usize;
-					let p2 = *self.literal_buf.nth(1).unwrap() as usize;
-
-					LUT_0[p1] | LUT_1[p2]
-				},
-				3 => {
-					let p1 = *self.literal_buf.nth(0).unwrap() as usize;
-					let p2 = *self.literal_buf.nth(1).unwrap() as usize;
-
-					(LUT_2[p1] << 3) | LUT_2[p2]
-				},
-				_ => unreachable!(), // confirmed unreachable, context_mode is always read from two bits
-			};
+			let cid = self.literal_context_id();
 
 			// println!("(btype, cid) = {:?}", (btype, cid));
 
@@ -1350,7 +1766,7 @@ impl<R: Read> Decompressor<R> {
 
 
 
-			*lit = match self.meta_block.prefix_trees_literals.as_ref().unwrap()[index].lookup_symbol(&mut self.in_stream) {
+			literals[i] = match self.meta_block.prefix_trees_literals.as_ref().unwrap()[index].lookup_symbol(&mut self.in_stream) {
 				Ok(Some(symbol)) => symbol as Literal,
 				Ok(None) => return Err(DecompressorError::ParseErrorInsertLiterals),
 				Err(_) => return Err(DecompressorError::UnexpectedEOF),
@@ -1358,7 +1774,11 @@ impl<R: Read> Decompressor<R> {
 
 			// debug(&format!("Literal = {:?}", String::from_utf8(vec![lit])));
 
-			self.literal_buf.push(*lit);
+			self.literal_buf.push(literals[i]);
+
+			if self.attribution.is_some() {
+				self.attribute_literal();
+			}
 		}
 
 		Ok(State::InsertLiterals(literals))
@@ -1473,20 +1893,41 @@ impl<R: Read> Decompressor<R> {
 
 		// println!("(dc, db, d) = {:?}", (self.meta_block.distance_code, self.distance_buf.clone(), distance));
 
-		if self.meta_block.distance_code.unwrap() > 0 && distance as usize <= cmp::min(self.header.window_size.unwrap(), self.count_output) {
+		if self.meta_block.distance_code.unwrap() > 0 && distance as usize <= self.max_distance() + self.dictionary_len {
 			self.distance_buf.push(distance);
 		}
 
 		Ok(State::Distance(distance))
 	}
 
+	/// Returns the largest distance of a backward reference into the output.
+	fn max_distance(&self) -> usize {
+		cmp::min(self.count_output, self.header.window_size.unwrap())
+	}
+
+	/// Returns (word_id, transform_id), if the current copy is a reference to the static
+	/// dictionary, rather than a backward reference, or a reference to the raw dictionary.
+	/// Valid until the copied literals are output.
+	fn dictionary_reference(&self) -> Option<(usize, usize)> {
+		let copy_length = self.meta_block.copy_length.unwrap() as usize;
+		let distance = self.meta_block.distance.unwrap() as usize;
+		let max_allowed_distance = self.max_distance() + self.dictionary_len;
+
+		if distance <= max_allowed_distance {
+			None
+		} else {
+			match self.shared_dictionary.reference(self.literal_context_id(), copy_length, distance - max_allowed_distance - 1) {
+				Ok(reference) => Some(reference),
+				Err(_) => unreachable!(), // confirmed unreachable, copy_literals() has resolved the reference
+			}
+		}
+	}
+
 	fn copy_literals(&mut self) -> Result<State, DecompressorError> {
-		let window_size = self.header.window_size.unwrap();
 		let copy_length = self.meta_block.copy_length.unwrap() as usize;
-		let count_output = self.count_output;
 		let distance = self.meta_block.distance.unwrap() as usize;
 		let output_window = self.output_window.as_ref().unwrap();
-		let max_allowed_distance = cmp::min(count_output, window_size);
+		let max_allowed_distance = self.max_distance();
 
 		if distance <= max_allowed_distance {
 			let mut window = vec![0; copy_length];
@@ -1503,50 +1944,76 @@ impl<R: Read> Decompressor<R> {
 			}
 
 			Ok(State::CopyLiterals(window))
-		} else {
-			if copy_length < 4 || copy_length > 24 {
-				return Err(DecompressorError::InvalidLengthInStaticDictionary);
+		} else if distance - max_allowed_distance <= self.dictionary_len {
+			// The copy must not run beyond the end of the raw dictionary.
+			let address = self.dictionary_len - (distance - max_allowed_distance);
+
+			if copy_length > self.dictionary_len - address {
+				return Err(DecompressorError::ExceededDictionary);
 			}
 
-			let word_id = distance - max_allowed_distance - 1;
-			let n_words_length = if copy_length < 4 {
-				0
-			} else {
-				{
-					let rhs = BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH[copy_length];
-					1 << rhs
-				}
-			};
-			let index = word_id % n_words_length;
-			let offset_from = BROTLI_DICTIONARY_OFFSETS_BY_LENGTH[copy_length] + index * copy_length;
-			let offset_to = BROTLI_DICTIONARY_OFFSETS_BY_LENGTH[copy_length] + (index + 1) * copy_length;
-			let base_word = &BROTLI_DICTIONARY[offset_from..offset_to];
-			let transform_id = {
-				let rhs = BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH[copy_length];
-			 	word_id >> rhs
-			};
+			let dictionary = self.dictionary.as_ref().unwrap();
 
-			if transform_id > 120 {
-				return Err(DecompressorError::InvalidTransformId);
+			Ok(State::CopyLiterals(dictionary[address..(address + copy_length)].to_vec()))
+		} else {
+			let address = distance - max_allowed_distance - self.dictionary_len - 1;
+
+			match self.shared_dictionary.word(self.literal_context_id(), copy_length, address) {
+				Ok(transformed_word) => Ok(State::CopyLiterals(transformed_word)),
+				Err(e) => Err(e),
 			}
+		}
 
-			// debug(&format!("base word = {:?}", String::from_utf8(Vec::from(base_word))));
-			// debug(&format!("transform id = {:?}", transform_id));
+	}
 
 
-			let transformed_word = transformation(transform_id, base_word);
+	/// Stores an error, so that every later call of decompress() returns it, and returns the
+	/// number of bytes that have been written to the buffer before it, if any, or the error.
+	fn fail(&mut self, e: DecompressorError, buf_pos: usize) -> Result<usize, DecompressorError> {
+		self.error = Some(e.clone());
 
-			Ok(State::CopyLiterals(transformed_word))
+		if buf_pos > 0 {
+			Ok(buf_pos)
+		} else {
+			Err(e)
 		}
-
 	}
 
-
 	fn decompress(&mut self, buf: &mut [u8]) -> Result<usize, DecompressorError> {
+		if let Some(ref e) = self.error {
+			return Err(e.clone());
+		}
+
 		let mut buf_pos = 0;
 
 		loop {
-			match self.state.clone() {
+			let state = self.state.clone();
+
+			if self.trace.is_some() {
+				self.trace_state(&state);
+			}
+
+			if self.recorder.is_some() {
+				self.record_state(&state);
+			}
+
+			if self.collector.is_some() {
+				self.collect_state(&state);
+			}
+
+			if self.attribution.is_some() {
+				self.attribute_state(&state);
+			}
+
+			if self.count_meta_blocks <= 1 {
+				self.peek_state(&state);
+
+				if self.peek && self.stream_header.is_some_and(|h| h.first_meta_block.is_some()) {
+					return Ok(buf_pos);
+				}
+			}
+
+			match state {
 				State::StreamBegin => {
 
 					self.state = State::HeaderBegin;
@@ -1554,7 +2021,7 @@ impl<R: Read> Decompressor<R> {
 				State::HeaderBegin => {
 					self.state = match self.parse_wbits() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::WBits(wbits) => {
@@ -1571,9 +2038,11 @@ impl<R: Read> Decompressor<R> {
 				},
 				State::HeaderMetaBlockBegin => {
 					self.meta_block = MetaBlock::new();
+					self.count_meta_blocks += 1;
+					self.count_commands = 0;
 					self.state = match self.parse_is_last() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::IsLast(true) => {
@@ -1583,7 +2052,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_is_last_empty() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::IsLast(false) => {
@@ -1593,7 +2062,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_m_nibbles() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::IsLastEmpty(true) => {
@@ -1601,6 +2070,7 @@ impl<R: Read> Decompressor<R> {
 
 					// debug(&format!("ISLASTEMPTY = true"));
 
+					self.report_progress();
 
 					self.state = State::StreamEnd;
 				},
@@ -1611,14 +2081,18 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_m_nibbles() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::MNibbles(0) => {
 					match self.in_stream.read_bit() {
-						Ok(true) => return Err(DecompressorError::NonZeroReservedBit),
+						Ok(true) => return self.fail(DecompressorError::NonZeroReservedBit, buf_pos),
 						Ok(false) => {},
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
+					}
+
+					if self.trace.is_some() {
+						self.trace_field(String::from("reserved bit"));
 					}
 
 					// debug(&format!("MNibbles = 0"));
@@ -1627,7 +2101,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_m_skip_bytes() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					}
 				},
 				State::MNibbles(m_nibbles) => {
@@ -1637,7 +2111,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_m_len() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					}
 				},
 				State::MSkipBytes(0) => {
@@ -1647,10 +2121,16 @@ impl<R: Read> Decompressor<R> {
 
 					match self.in_stream.read_u8_from_byte_tail() {
 						Ok(0) => {},
-						Ok(_) => return Err(DecompressorError::NonZeroFillBit),
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+						Ok(_) => return self.fail(DecompressorError::NonZeroFillBit, buf_pos),
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
 					};
 
+					if self.trace.is_some() {
+						self.trace_padding("fill bits");
+					}
+
+					self.record(Command::Metadata(Vec::new()));
+
 					self.state = State::MetaBlockEnd;
 				},
 				State::MSkipBytes(m_skip_bytes) => {
@@ -1660,7 +2140,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_m_skip_len() {
 						Ok(state) => state,
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
 					}
 				},
 				State::MSkipLen(m_skip_len) => {
@@ -1670,13 +2150,30 @@ impl<R: Read> Decompressor<R> {
 
 					match self.in_stream.read_u8_from_byte_tail() {
 						Ok(0) => {},
-						Ok(_) => return Err(DecompressorError::NonZeroFillBit),
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+						Ok(_) => return self.fail(DecompressorError::NonZeroFillBit, buf_pos),
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
 					};
 
-					match self.in_stream.read_fixed_length_string(m_skip_len as usize) {
-						Ok(_) => {},
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+					if self.trace.is_some() {
+						self.trace_padding("fill bits");
+					}
+
+					let metadata = match self.in_stream.read_fixed_length_string(m_skip_len as usize) {
+						Ok(metadata) => metadata,
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
+					};
+
+					if self.trace.is_some() {
+						self.trace_field(format!("{} bytes of metadata: {}", metadata.len(), ::dump::preview(&metadata)));
+					}
+
+					if self.recorder.is_some() {
+						self.record(Command::Metadata(metadata.clone()));
+					}
+
+					match self.check_dictionary_id(&metadata) {
+						Ok(()) => {},
+						Err(e) => return self.fail(e, buf_pos),
 					};
 
 					self.state = State::MetaBlockEnd;
@@ -1689,12 +2186,12 @@ impl<R: Read> Decompressor<R> {
 					self.state = if self.meta_block.header.is_last.unwrap() {
 						match self.parse_n_bltypes_l() {
 							Ok(state) => state,
-							Err(_) => return Err(DecompressorError::UnexpectedEOF),
+							Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
 						}
 					} else {
 						match self.parse_is_uncompressed() {
 							Ok(state) => state,
-							Err(_) => return Err(DecompressorError::UnexpectedEOF),
+							Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
 						}
 					};
 				},
@@ -1705,22 +2202,28 @@ impl<R: Read> Decompressor<R> {
 
 					match self.in_stream.read_u8_from_byte_tail() {
 						Ok(0) => {},
-						Ok(_) => return Err(DecompressorError::NonZeroFillBit),
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+						Ok(_) => return self.fail(DecompressorError::NonZeroFillBit, buf_pos),
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
 					};
 
+					if self.trace.is_some() {
+						self.trace_padding("fill bits");
+					}
+
 					self.state = match self.parse_mlen_literals() {
 						Ok(state) => state,
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
 					};
 				},
 				State::MLenLiterals(m_len_literals) => {
 					for literal in &m_len_literals {
-						if buf_pos < buf.len() {
-							buf[buf_pos] = *literal;
-							buf_pos += 1;
-						} else {
-							self.buf.push_front(*literal);
+						if !self.discard_output {
+							if buf_pos < buf.len() {
+								buf[buf_pos] = *literal;
+								buf_pos += 1;
+							} else {
+								self.buf.push_front(*literal);
+							}
 						}
 						self.output_window.as_mut().unwrap().push(*literal);
 						self.literal_buf.push(*literal);
@@ -1739,7 +2242,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_n_bltypes_l() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::NBltypesL(n_bltypes_l) => {
@@ -1750,12 +2253,12 @@ impl<R: Read> Decompressor<R> {
 					self.state = if n_bltypes_l >= 2 {
 						match self.parse_prefix_code_block_types_literals() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					} else {
 						match self.parse_n_bltypes_i() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					}
 				},
@@ -1766,7 +2269,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_prefix_code_block_counts_literals() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::PrefixCodeBlockCountsLiterals(prefix_tree) => {
@@ -1776,7 +2279,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_first_block_count_literals() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::FirstBlockCountLiterals(blen) => {
@@ -1786,7 +2289,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_n_bltypes_i() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::NBltypesI(n_bltypes_i) => {
@@ -1797,12 +2300,12 @@ impl<R: Read> Decompressor<R> {
 					self.state = if n_bltypes_i >= 2 {
 						match self.parse_prefix_code_block_types_insert_and_copy_lengths() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					} else {
 						match self.parse_n_bltypes_d() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					}
 				},
@@ -1813,7 +2316,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_prefix_code_block_counts_insert_and_copy_lengths() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::PrefixCodeBlockCountsInsertAndCopyLengths(prefix_tree) => {
@@ -1823,7 +2326,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_first_block_count_insert_and_copy_lengths() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::FirstBlockCountInsertAndCopyLengths(blen) => {
@@ -1833,7 +2336,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_n_bltypes_d() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::NBltypesD(n_bltypes_d) => {
@@ -1844,12 +2347,12 @@ impl<R: Read> Decompressor<R> {
 					self.state = if n_bltypes_d >= 2 {
 						match self.parse_prefix_code_block_types_distances() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					} else {
 						match self.parse_n_postfix() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					};
 				},
@@ -1860,7 +2363,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_prefix_code_block_counts_distances() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::PrefixCodeBlockCountsDistances(prefix_tree) => {
@@ -1870,7 +2373,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_first_block_count_distances() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::FirstBlockCountDistances(blen) => {
@@ -1880,7 +2383,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_n_postfix() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::NPostfix(n_postfix) => {
@@ -1890,7 +2393,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_n_direct() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::NDirect(n_direct) => {
@@ -1900,7 +2403,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_context_modes_literals() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::ContextModesLiterals(context_modes) => {
@@ -1910,7 +2413,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_n_trees_l() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::NTreesL(n_trees_l) => {
@@ -1922,12 +2425,12 @@ impl<R: Read> Decompressor<R> {
 					self.state = if n_trees_l >= 2 {
 						match self.parse_context_map_literals() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					} else {
 						match self.parse_n_trees_d() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					};
 				},
@@ -1938,7 +2441,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_n_trees_d() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::NTreesD(n_trees_d) => {
@@ -1950,12 +2453,12 @@ impl<R: Read> Decompressor<R> {
 					self.state = if n_trees_d >= 2 {
 						match self.parse_context_map_distances() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					} else {
 						match self.parse_prefix_codes_literals() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					};
 				},
@@ -1968,7 +2471,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_prefix_codes_literals() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::PrefixCodesLiterals(prefix_trees) => {
@@ -1980,7 +2483,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_prefix_codes_insert_and_copy_lengths() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::PrefixCodesInsertAndCopyLengths(prefix_trees) => {
@@ -1990,7 +2493,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.parse_prefix_codes_distances() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::PrefixCodesDistances(prefix_trees) => {
@@ -2001,9 +2504,10 @@ impl<R: Read> Decompressor<R> {
 					self.state = State::DataMetaBlockBegin;
 				},
 				State::DataMetaBlockBegin => {
+					self.count_commands += 1;
 					self.state =  match self.parse_insert_and_copy_length() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::InsertAndCopyLength(insert_and_copy_length) => {
@@ -2018,7 +2522,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.decode_insert_and_copy_length() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::InsertLengthAndCopyLength(insert_length_and_copy_length) => {
@@ -2035,14 +2539,14 @@ impl<R: Read> Decompressor<R> {
 
 					if m_len < self.meta_block.count_output + self.meta_block.insert_length.unwrap() as usize {
 
-						return Err(DecompressorError::ExceededExpectedBytes);
+						return self.fail(DecompressorError::ExceededExpectedBytes, buf_pos);
 					}
 
 					// println!("Insert Length and Copy Length = {:?}", insert_length_and_copy_length);
 
 					self.state = match self.parse_insert_literals() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::InsertLiterals(insert_literals) => {
@@ -2051,15 +2555,17 @@ impl<R: Read> Decompressor<R> {
 					// println!("MLEN = {:?}", m_len);
 					if m_len < self.meta_block.count_output + insert_literals.len() {
 
-						return Err(DecompressorError::ExceededExpectedBytes);
+						return self.fail(DecompressorError::ExceededExpectedBytes, buf_pos);
 					}
 
 					for literal in &insert_literals {
-						if buf_pos < buf.len() {
-							buf[buf_pos] = *literal;
-							buf_pos += 1;
-						} else {
-							self.buf.push_front(*literal);
+						if !self.discard_output {
+							if buf_pos < buf.len() {
+								buf[buf_pos] = *literal;
+								buf_pos += 1;
+							} else {
+								self.buf.push_front(*literal);
+							}
 						}
 						self.output_window.as_mut().unwrap().push(*literal);
 						self.count_output += 1;
@@ -2071,7 +2577,7 @@ impl<R: Read> Decompressor<R> {
 					} else {
 						match self.parse_distance_code() {
 							Ok(state) => state,
-							Err(e) => return Err(e),
+							Err(e) => return self.fail(e, buf_pos),
 						}
 					};
 
@@ -2086,7 +2592,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.decode_distance() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::Distance(distance) => {
@@ -2096,7 +2602,7 @@ impl<R: Read> Decompressor<R> {
 
 					self.state = match self.copy_literals() {
 						Ok(state) => state,
-						Err(e) => return Err(e),
+						Err(e) => return self.fail(e, buf_pos),
 					};
 				},
 				State::CopyLiterals(copy_literals) => {
@@ -2104,15 +2610,17 @@ impl<R: Read> Decompressor<R> {
 
 					if m_len < self.meta_block.count_output + copy_literals.len() {
 
-						return Err(DecompressorError::ExceededExpectedBytes);
+						return self.fail(DecompressorError::ExceededExpectedBytes, buf_pos);
 					}
 
 					for literal in &copy_literals {
-						if buf_pos < buf.len() {
-							buf[buf_pos] = *literal;
-							buf_pos += 1;
-						} else {
-							self.buf.push_front(*literal);
+						if !self.discard_output {
+							if buf_pos < buf.len() {
+								buf[buf_pos] = *literal;
+								buf_pos += 1;
+							} else {
+								self.buf.push_front(*literal);
+							}
 						}
 						self.literal_buf.push(*literal);
 
@@ -2144,6 +2652,8 @@ impl<R: Read> Decompressor<R> {
 					self.state = State::MetaBlockEnd;
 				},
 				State::MetaBlockEnd => {
+					self.report_progress();
+
 					self.state = if self.meta_block.header.is_last.unwrap() {
 
 						State::StreamEnd
@@ -2151,18 +2661,33 @@ impl<R: Read> Decompressor<R> {
 
 						State::HeaderMetaBlockBegin
 					};
+
+					// Return output at the meta-block boundary, instead of waiting for the
+					// next meta-block, which the stream might not be able to provide yet,
+					// e.g. after a flush of the compressor on the other end of a socket.
+					if buf_pos > 0 && self.state == State::HeaderMetaBlockBegin {
+						return Ok(buf_pos);
+					}
 				},
 				State::StreamEnd => {
 					match self.in_stream.read_u8_from_byte_tail() {
 						Ok(0) => {},
-						Ok(_) => return Err(DecompressorError::NonZeroTrailerBit),
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+						Ok(_) => return self.fail(DecompressorError::NonZeroTrailerBit, buf_pos),
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
+					}
+
+					if self.trace.is_some() {
+						self.trace_padding("trailing bits");
+					}
+
+					if self.member {
+						return Ok(buf_pos);
 					}
 
 					match self.in_stream.read_u8() {
 						Err(BitReaderError::EOF) => return Ok(buf_pos),
-						Ok(_) => return Err(DecompressorError::ExpectedEndOfStream),
-						Err(_) => return Err(DecompressorError::UnexpectedEOF),
+						Ok(_) => return self.fail(DecompressorError::ExpectedEndOfStream, buf_pos),
+						Err(_) => return self.fail(DecompressorError::UnexpectedEOF, buf_pos),
 					}
 				}
 			};
@@ -2174,7 +2699,7 @@ impl<R: Read> Read for Decompressor<R> {
 	fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
 		if self.buf.is_empty() {
 			match self.decompress(&mut buf) {
-				Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e.description())),
+				Err(e) => Err(self.stream_error(e)),
 				Ok(l) => {
 					Ok(l)
 				},
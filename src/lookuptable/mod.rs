@@ -56,6 +56,20 @@ pub const LUT_2: [usize; 256] = [
 ];
 
 
+pub const INSERT_LENGTH_CODES: [(u32, usize); 24] = [
+	(    0,  0), (    1,  0), (    2,  0), (    3,  0), (    4,  0), (    5,  0),
+	(    6,  1), (    8,  1), (   10,  2), (   14,  2), (   18,  3), (   26,  3),
+	(   34,  4), (   50,  4), (   66,  5), (   98,  5), (  130,  6), (  194,  7),
+	(  322,  8), (  578,  9), ( 1090, 10), ( 2114, 12), ( 6210, 14), (22594, 24),
+];
+
+pub const COPY_LENGTH_CODES: [(u32, usize); 24] = [
+	(    2,  0), (    3,  0), (    4,  0), (    5,  0), (    6,  0), (    7,  0),
+	(    8,  0), (    9,  0), (   10,  1), (   12,  1), (   14,  2), (   18,  2),
+	(   22,  3), (   30,  3), (   38,  4), (   54,  4), (   70,  5), (  102,  5),
+	(  134,  6), (  198,  7), (  326,  8), (  582,  9), ( 1094, 10), ( 2118, 24),
+];
+
 // @NOTE Code to generate INSERT_LENGTHS_AND_COPY_LENGTHS below
 //
 // let mut table = vec![((0, 0), (0, 0)); 704];
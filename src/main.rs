@@ -1,6 +1,6 @@
 extern crate brotli;
 
-use std::io::Read;
+use std::io::{ Read, Write };
 use brotli::Decompressor;
 use std::fs;
 use std::path::Path;
@@ -29,7 +29,122 @@ use std::path::Path;
 
 
 
+/// prints an error, that has occurred for the file at path, and exits
+fn fail(path: &str, e: std::io::Error) -> ! {
+	eprintln!("{:?}: {}", path, e);
+	std::process::exit(1);
+}
+
+/// train <target size> <dictionary file> <sample files...>
+/// writes a dictionary, trained on the sample files, for use as a raw dictionary
+fn train(args: &[String]) {
+	if args.len() < 3 {
+		println!("usage: main train <target size> <dictionary file> <sample files...>");
+		std::process::exit(1);
+	}
+
+	let target_size = args[0].parse::<usize>().unwrap();
+	let samples = args[2..].iter().map(|path| {
+		let mut sample = Vec::new();
+		fs::File::open(path).unwrap().read_to_end(&mut sample).unwrap();
+		sample
+	}).collect::<Vec<_>>();
+
+	let dictionary = brotli::dictionary::train(&samples, target_size);
+	fs::File::create(&args[1]).unwrap().write_all(&dictionary).unwrap();
+
+	println!("{:?}: {} bytes, trained on {} samples", &args[1], dictionary.len(), samples.len());
+}
+
+/// dump <file>
+/// prints the bits of a compressed file, annotated field by field, in the style of docs/analysis_*.txt
+fn dump(args: &[String]) {
+	if args.len() != 1 {
+		println!("usage: main dump <file>");
+		std::process::exit(1);
+	}
+
+	let dump = match fs::File::open(&args[0]).and_then(brotli::dump) {
+		Ok(dump) => dump,
+		Err(e) => fail(&args[0], e),
+	};
+
+	print!("{}", dump);
+}
+
+/// stats [--json] <file>
+/// prints statistics of each meta-block of a compressed file, as text, or as a JSON array
+fn stats(args: &[String]) {
+	let (json, path) = match args.len() {
+		1 => (false, &args[0]),
+		2 if args[0] == "--json" => (true, &args[1]),
+		_ => {
+			println!("usage: main stats [--json] <file>");
+			std::process::exit(1);
+		},
+	};
+
+	let stats = match fs::File::open(path).and_then(brotli::stats) {
+		Ok(stats) => stats,
+		Err(e) => fail(path, e),
+	};
+
+	if json {
+		println!("[{}]", stats.iter().map(|s| s.to_json()).collect::<Vec<_>>().join(","));
+	} else {
+		for (i, s) in stats.iter().enumerate() {
+			println!("Meta-Block {}\n{}", i, s);
+		}
+	}
+}
+
+/// heatmap [--json] <file>
+/// prints the compressed bits of each decompressed byte of a compressed file, as HTML page, or as JSON
+fn heatmap(args: &[String]) {
+	let (json, path) = match args.len() {
+		1 => (false, &args[0]),
+		2 if args[0] == "--json" => (true, &args[1]),
+		_ => {
+			println!("usage: main heatmap [--json] <file>");
+			std::process::exit(1);
+		},
+	};
+
+	let heatmap = match fs::File::open(path).and_then(brotli::heatmap) {
+		Ok(heatmap) => heatmap,
+		Err(e) => fail(path, e),
+	};
+
+	if json {
+		println!("{}", heatmap.to_json());
+	} else {
+		print!("{}", heatmap.to_html());
+	}
+}
+
 fn main() {
+	let args = std::env::args().collect::<Vec<_>>();
+
+	if args.len() > 1 && args[1] == "train" {
+		train(&args[2..]);
+		return;
+	}
+
+	if args.len() > 1 && args[1] == "dump" {
+		dump(&args[2..]);
+		return;
+	}
+
+	if args.len() > 1 && args[1] == "stats" {
+		stats(&args[2..]);
+		return;
+	}
+
+	if args.len() > 1 && args[1] == "heatmap" {
+		heatmap(&args[2..]);
+		return;
+	}
+
 	// let mut input = Vec::new();
 	// let res = Decompressor::new(std::fs::File::open("data/alice29.txt.compressed").unwrap()).read_to_end(&mut input);
 
@@ -0,0 +1,809 @@
+use ::compressor::Compressor;
+use ::concat::parse_catable_header;
+use ::seekable::{ Chunk, decompress_chunk, read_seek_table };
+use ::{ clone_error, Decompressor, Header };
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{ Read, Seek, SeekFrom, Write };
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{ Arc, Mutex };
+use std::sync::mpsc::{ channel, Receiver, Sender };
+use std::thread;
+use std::thread::JoinHandle;
+
+/// Size of the input chunks, unless given otherwise.
+const DEFAULT_CHUNK_SIZE: usize = 1 << 20;
+/// Number of bytes at the end of a chunk, that prime the compression of the next chunk.
+const PRIME_SIZE: usize = 1 << 16;
+
+/// A job for a worker of a Pool, with its index, by which its result is returned.
+type Job<T> = (usize, Box<dyn FnOnce() -> T + Send>);
+
+/// A fixed set of worker threads, which run jobs from a channel, and return their results
+/// with the index of the job, in the order in which they complete.
+#[derive(Debug)]
+struct Pool<T> {
+	jobs: Option<Sender<Job<T>>>,
+	results: Receiver<(usize, thread::Result<T>)>,
+	workers: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> Pool<T> {
+	fn new(threads: usize) -> Pool<T> {
+		let (jobs, job_receiver) = channel::<Job<T>>();
+		let (result_sender, results) = channel();
+		let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+		let workers = (0..threads).map(|_| {
+			let job_receiver = job_receiver.clone();
+			let result_sender = result_sender.clone();
+
+			thread::spawn(move || loop {
+				// The lock is held while waiting for the next job only.
+				let job = job_receiver.lock().unwrap().recv();
+
+				let (index, job) = match job {
+					Ok(job) => job,
+					Err(_) => return,
+				};
+
+				// A panicking job must not take the worker down, or its result would never arrive.
+				if result_sender.send((index, panic::catch_unwind(AssertUnwindSafe(job)))).is_err() {
+					return;
+				}
+			})
+		}).collect();
+
+		Pool{
+			jobs: Some(jobs),
+			results,
+			workers,
+		}
+	}
+
+	/// Hands a job to the next free worker.
+	fn submit(&self, index: usize, job: Box<dyn FnOnce() -> T + Send>) {
+		self.jobs.as_ref().unwrap().send((index, job)).unwrap();
+	}
+
+	/// Waits for the next job to complete, and returns its index and result, or the panic
+	/// of the job.
+	fn receive(&self) -> (usize, thread::Result<T>) {
+		self.results.recv().unwrap()
+	}
+}
+
+impl<T> Drop for Pool<T> {
+	/// Closes the channel of jobs, upon which the workers end, and waits for them.
+	fn drop(&mut self) {
+		self.jobs.take();
+
+		for worker in self.workers.drain(..) {
+			let _ = worker.join();
+		}
+	}
+}
+
+/// Wraps an output stream, and compresses everything that is written to it into a single
+/// Brotli stream, like Compressor, but on multiple threads. The input is cut into chunks,
+/// which are compressed in parallel into catable streams, and then joined in order.
+///
+/// Chunks start and end on a byte boundary in the joined stream. If priming is enabled, every
+/// chunk is compressed with the last 64 KiB of input before it, i.e. the tail of the previous
+/// chunk, as a dictionary. It can then refer back into the previous chunk, which is already
+/// part of the output when the stream gets decompressed. That gives better compression
+/// across chunk boundaries.
+///
+/// The chunks are compressed by a fixed set of worker threads, which live as long as the
+/// ParallelCompressor. The output depends on the chunk size, on priming, and on where flush()
+/// is called, but not on the number of threads or their timing, so it is the same on every run.
+/// The stream needs to be completed with finish().
+///
+/// # Examples
+/// ```
+/// use std::io::{ Read, Write };
+/// use brotli::{ Decompressor, ParallelCompressor };
+///
+/// let input = (0..100000).map(|i| format!("{} ", i % 1234)).collect::<String>();
+///
+/// let mut compressor = ParallelCompressor::with_options(Vec::new(), 4, 65536, true);
+/// compressor.write_all(input.as_bytes()).unwrap();
+/// let brotli_stream = compressor.finish().unwrap();
+///
+/// let mut decompressed = Vec::new();
+/// Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed).unwrap();
+///
+/// assert_eq!(input.into_bytes(), decompressed);
+/// ```
+#[derive(Debug)]
+pub struct ParallelCompressor<W: Write> {
+	inner: W,
+	threads: usize,
+	pool: Pool<io::Result<Vec<u8>>>,
+	chunk_size: usize,
+	prime: bool,
+	buf: Vec<u8>,
+	/// last bytes of input before the buffer, up to PRIME_SIZE
+	tail: Vec<u8>,
+	/// true, once the header of the stream has been written
+	started: bool,
+}
+
+impl<W: Write> ParallelCompressor<W> {
+	/// Creates ParallelCompressor from Write, for the given number of threads, with chunks
+	/// of 1 MiB, and with priming.
+	pub fn new(w: W, threads: usize) -> ParallelCompressor<W> {
+		ParallelCompressor::with_options(w, threads, DEFAULT_CHUNK_SIZE, true)
+	}
+
+	/// Creates ParallelCompressor from Write, for the given number of threads, with chunks of
+	/// chunk_size bytes. If prime is true, every chunk is primed with the tail of the previous
+	/// chunk. Up to threads chunks are buffered at a time.
+	///
+	/// # Panics
+	/// Panics, if threads or chunk_size is 0.
+	pub fn with_options(w: W, threads: usize, chunk_size: usize, prime: bool) -> ParallelCompressor<W> {
+		assert!(threads > 0 && chunk_size > 0);
+
+		ParallelCompressor{
+			inner: w,
+			threads,
+			pool: Pool::new(threads),
+			chunk_size,
+			prime,
+			buf: Vec::new(),
+			tail: Vec::new(),
+			started: false,
+		}
+	}
+
+	/// Compresses the first len bytes of the buffer, in chunks, on the workers, and writes
+	/// them out in order.
+	fn compress_chunks(&mut self, len: usize) -> io::Result<()> {
+		let mut n_chunks = 0;
+		let mut start = 0;
+
+		while start < len {
+			let end = cmp::min(start + self.chunk_size, len);
+			let chunk = self.buf[start..end].to_vec();
+			let dictionary = if !self.prime {
+				Vec::new()
+			} else if start >= PRIME_SIZE {
+				self.buf[(start - PRIME_SIZE)..start].to_vec()
+			} else {
+				let mut dictionary = self.tail[self.tail.len().saturating_sub(PRIME_SIZE - start)..].to_vec();
+				dictionary.extend_from_slice(&self.buf[..start]);
+				dictionary
+			};
+
+			self.pool.submit(n_chunks, Box::new(move || {
+				let mut compressor = Compressor::with_options(Vec::new(), true, &dictionary, false);
+
+				compressor.write_all(&chunk).and_then(|_| compressor.finish())
+			}));
+
+			n_chunks += 1;
+			start = end;
+		}
+
+		// All results are collected, before any error returns, so that none is left for the next batch.
+		let mut streams = (0..n_chunks).map(|_| None).collect::<Vec<_>>();
+
+		for _ in 0..n_chunks {
+			let (index, result) = self.pool.receive();
+
+			streams[index] = Some(match result {
+				Ok(result) => result,
+				Err(_) => Err(io::Error::other("Compression thread panicked")),
+			});
+		}
+
+		let header = Header::new();
+
+		for stream in streams {
+			let stream = match stream.unwrap() {
+				Ok(stream) => stream,
+				Err(e) => return Err(e),
+			};
+
+			// The first stream keeps its header, all others are joined to it.
+			let body = if self.started {
+				&stream[parse_catable_header(&header, &stream).unwrap().1..]
+			} else {
+				&stream[..]
+			};
+
+			match self.inner.write_all(body) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+
+			self.started = true;
+		}
+
+		if self.prime {
+			self.tail.extend_from_slice(&self.buf[(len - cmp::min(len, PRIME_SIZE))..len]);
+			let excess = self.tail.len().saturating_sub(PRIME_SIZE);
+			self.tail.drain(0..excess);
+		}
+
+		self.buf.drain(0..len);
+
+		Ok(())
+	}
+
+	/// Compresses all pending input, completes the stream, and returns the underlying writer.
+	pub fn finish(mut self) -> io::Result<W> {
+		let len = self.buf.len();
+
+		match self.compress_chunks(len) {
+			Ok(()) => {},
+			Err(e) => return Err(e),
+		}
+
+		let bytes = if self.started {
+			// ISLAST, ISLASTEMPTY
+			vec![0x03]
+		} else {
+			// WBITS 16, ISLAST, ISLASTEMPTY
+			vec![0x06]
+		};
+
+		match self.inner.write_all(&bytes).and_then(|_| self.inner.flush()) {
+			Ok(()) => Ok(self.inner),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+impl<W: Write> Write for ParallelCompressor<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.buf.extend_from_slice(buf);
+
+		let batch_size = self.threads * self.chunk_size;
+
+		while self.buf.len() >= batch_size {
+			match self.compress_chunks(batch_size) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(buf.len())
+	}
+
+	/// Compresses all pending input, of which the last chunk may be shorter than the chunk
+	/// size, and flushes the underlying writer. Everything written so far can then be
+	/// decompressed from the output.
+	fn flush(&mut self) -> io::Result<()> {
+		let len = self.buf.len();
+
+		match self.compress_chunks(len) {
+			Ok(()) => self.inner.flush(),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+/// Content of a chunk, and the error that follows it, if any.
+type ChunkResult = (Vec<u8>, Option<io::Error>);
+
+/// Where ParallelDecompressor takes its output from.
+#[derive(Debug)]
+enum Source<R: Read + Seek> {
+	/// independent chunks from a seek table, and the stream they are read from
+	Chunks(R, Vec<Chunk>),
+	/// concatenated streams without seek table, which are decoded one after the other
+	Members{
+		/// decoder of the current stream, which stops at its end, or None after the last stream
+		decompressor: Option<Box<Decompressor<R>>>,
+		/// offset of the current stream
+		offset: u64,
+		/// length of the input
+		len: u64,
+	},
+}
+
+/// Wraps a seekable input stream, and decompresses it on multiple threads, if it consists of
+/// independently decompressible chunks, as files from SeekableCompressor do. The chunks are
+/// decompressed in parallel by a fixed set of worker threads, and returned in order. At most
+/// as many chunks as there are threads are decompressed ahead of the output, so that memory
+/// stays bounded by the chunk size.
+///
+/// The chunks are taken from the seek table. A stream without seek table is taken as
+/// concatenated Brotli streams, which are decoded one after the other on the reading thread,
+/// straight into the buffers passed to read(): where a stream ends, and the next one starts,
+/// is only known once it has been decoded. So only streams with a seek table gain speed.
+///
+/// The output and the errors are the same as those of sequential decompression, i.e. of
+/// SeekableDecompressor, or of Decompressor for each stream without seek table: all output up
+/// to the error of the first chunk that fails, and then that error, every time. Errors in later
+/// chunks do not show.
+///
+/// # Examples
+/// ```
+/// use std::io::{ Cursor, Read, Write };
+/// use brotli::{ ParallelDecompressor, SeekableCompressor };
+///
+/// let input = (0..100000).map(|i| format!("{} ", i % 1234)).collect::<String>();
+///
+/// let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 65536);
+/// compressor.write_all(input.as_bytes()).unwrap();
+/// let file = compressor.finish().unwrap();
+///
+/// let mut decompressed = Vec::new();
+/// ParallelDecompressor::new(Cursor::new(file), 4).unwrap().read_to_end(&mut decompressed).unwrap();
+///
+/// assert_eq!(input.into_bytes(), decompressed);
+/// ```
+#[derive(Debug)]
+pub struct ParallelDecompressor<R: Read + Seek> {
+	source: Source<R>,
+	pool: Pool<ChunkResult>,
+	threads: usize,
+	/// index of the next chunk to hand to a worker
+	next: usize,
+	/// whether all chunks have been handed to workers
+	done: bool,
+	/// index of the chunk after the current one
+	current: usize,
+	/// results that have arrived ahead of their turn, by index
+	pending: BTreeMap<usize, ChunkResult>,
+	/// output of the current chunk, and the position in it
+	buf: Vec<u8>,
+	pos: usize,
+	/// error that follows the output of the current chunk, and ends decompression
+	error: Option<io::Error>,
+}
+
+impl<R: Read + Seek> ParallelDecompressor<R> {
+	/// Creates ParallelDecompressor from Read + Seek, for the given number of threads, and
+	/// reads the seek table, if there is one. Returns an error with kind InvalidData, if the
+	/// seek table does not fit the stream.
+	///
+	/// # Panics
+	/// Panics, if threads is 0.
+	pub fn new(mut r: R, threads: usize) -> io::Result<ParallelDecompressor<R>> {
+		assert!(threads > 0);
+
+		let source = match read_seek_table(&mut r) {
+			Ok(Some(chunks)) => Source::Chunks(r, chunks),
+			Ok(None) => match r.seek(SeekFrom::End(0)).and_then(|len| r.seek(SeekFrom::Start(0)).map(|_| len)) {
+				// An empty input is a stream, too, albeit an invalid one.
+				Ok(len) => Source::Members{
+					decompressor: Some(Box::new(member_decompressor(r))),
+					offset: 0,
+					len,
+				},
+				Err(e) => return Err(e),
+			},
+			Err(e) => return Err(e),
+		};
+
+		// Streams without seek table are decoded on the reading thread, without workers.
+		let workers = match source {
+			Source::Chunks(..) => threads,
+			Source::Members{ .. } => 0,
+		};
+
+		Ok(ParallelDecompressor{
+			source,
+			pool: Pool::new(workers),
+			threads,
+			next: 0,
+			done: false,
+			current: 0,
+			pending: BTreeMap::new(),
+			buf: Vec::new(),
+			pos: 0,
+			error: None,
+		})
+	}
+
+	/// Reads the next chunk, and returns the job that decompresses it, or None after the last
+	/// chunk. Reading happens in order, before the job starts, so a read error shows for the
+	/// chunk it belongs to.
+	fn next_job(&mut self) -> Option<Box<dyn FnOnce() -> ChunkResult + Send>> {
+		let (inner, chunks) = match self.source {
+			Source::Chunks(ref mut inner, ref chunks) => (inner, chunks),
+			Source::Members{ .. } => return None,
+		};
+
+		if self.next == chunks.len() {
+			return None;
+		}
+
+		let chunk = chunks[self.next];
+		let mut compressed = vec![0; chunk.compressed_size as usize];
+		let read = inner.seek(SeekFrom::Start(chunk.compressed_offset)).and_then(|_| inner.read_exact(&mut compressed));
+
+		Some(Box::new(move || match read {
+			Ok(()) => decompress_chunk(&chunk, &compressed),
+			Err(e) => (Vec::new(), Some(e)),
+		}))
+	}
+
+	/// Hands chunks to workers, until as many chunks as there are threads are ahead of the
+	/// output, and returns the result of the next chunk, or None after the last chunk.
+	fn next_chunk(&mut self) -> Option<ChunkResult> {
+		while !self.done && self.next - self.current < self.threads {
+			match self.next_job() {
+				Some(job) => {
+					self.pool.submit(self.next, job);
+					self.next += 1;
+				},
+				None => self.done = true,
+			}
+		}
+
+		if self.current == self.next {
+			return None;
+		}
+
+		while !self.pending.contains_key(&self.current) {
+			let (index, result) = self.pool.receive();
+
+			self.pending.insert(index, match result {
+				Ok(result) => result,
+				Err(_) => (Vec::new(), Some(io::Error::other("Decompression thread panicked"))),
+			});
+		}
+
+		let result = self.pending.remove(&self.current);
+		self.current += 1;
+
+		result
+	}
+
+	/// Decompresses concatenated streams without seek table into buf, one after the other.
+	/// Every stream is decoded once, up to its end, which is where the next stream starts.
+	fn read_members(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if let Some(ref e) = self.error {
+			return Err(clone_error(e));
+		}
+
+		let (decompressor, offset, len) = match self.source {
+			Source::Members{ ref mut decompressor, ref mut offset, len } => (decompressor, offset, len),
+			Source::Chunks(..) => unreachable!(),
+		};
+
+		while let Some(mut member) = decompressor.take() {
+			match member.read(buf) {
+				Ok(0) if !buf.is_empty() => {},
+				Ok(n) => {
+					*decompressor = Some(member);
+
+					return Ok(n);
+				},
+				Err(e) => {
+					self.error = Some(clone_error(&e));
+
+					return Err(e);
+				},
+			}
+
+			// Streams end on a byte boundary, but the decoder has read ahead of it.
+			*offset += member.total_in();
+
+			if *offset < len {
+				let mut inner = (*member).into_inner();
+
+				match inner.seek(SeekFrom::Start(*offset)) {
+					Ok(_) => *decompressor = Some(Box::new(member_decompressor(inner))),
+					Err(e) => {
+						self.error = Some(clone_error(&e));
+
+						return Err(e);
+					},
+				}
+			}
+		}
+
+		Ok(0)
+	}
+}
+
+impl<R: Read + Seek> Read for ParallelDecompressor<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if let Source::Members{ .. } = self.source {
+			return self.read_members(buf);
+		}
+
+		while self.pos == self.buf.len() {
+			if let Some(ref e) = self.error {
+				return Err(clone_error(e));
+			}
+
+			match self.next_chunk() {
+				Some((content, error)) => {
+					self.buf = content;
+					self.pos = 0;
+					self.error = error;
+				},
+				None => return Ok(0),
+			}
+		}
+
+		let n = cmp::min(buf.len(), self.buf.len() - self.pos);
+		buf[..n].copy_from_slice(&self.buf[self.pos..(self.pos + n)]);
+		self.pos += n;
+
+		Ok(n)
+	}
+}
+
+/// Returns a Decompressor for a stream, that may be followed by other streams.
+fn member_decompressor<R: Read>(r: R) -> Decompressor<R> {
+	let mut decompressor = Decompressor::new(r);
+	decompressor.member = true;
+
+	decompressor
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_run_jobs_on_fixed_workers() {
+		use super::Pool;
+		use std::collections::HashSet;
+		use std::thread;
+
+		let pool = Pool::new(2);
+
+		for i in 0..20 {
+			pool.submit(i, Box::new(move || {
+				if i == 7 {
+					panic!("job 7");
+				}
+
+				thread::current().id()
+			}));
+		}
+
+		let results = (0..20).map(|_| pool.receive()).collect::<Vec<_>>();
+
+		assert!(results.iter().all(|&(index, ref result)| result.is_err() == (index == 7)));
+		assert!(results.iter().filter_map(|(_, result)| result.as_ref().ok()).collect::<HashSet<_>>().len() <= 2);
+	}
+
+	#[test]
+	fn should_decompress_chunks_in_order() {
+		use super::ParallelDecompressor;
+		use ::SeekableCompressor;
+		use std::io::{ Cursor, Read, Write };
+
+		let input = (0..50000).map(|i| format!("{} ", i * 31 % 977)).collect::<String>().into_bytes();
+
+		let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 3000);
+		compressor.write_all(&input).unwrap();
+		let file = compressor.finish().unwrap();
+
+		for &threads in &[1, 2, 7, 100] {
+			let mut decompressed = Vec::new();
+			ParallelDecompressor::new(Cursor::new(&file[..]), threads).unwrap().read_to_end(&mut decompressed).unwrap();
+
+			assert_eq!(input, decompressed);
+		}
+	}
+
+	#[test]
+	fn should_fail_like_sequential_decompression() {
+		use super::ParallelDecompressor;
+		use ::{ Compressor, SeekableCompressor, SeekableDecompressor };
+		use std::io::{ Cursor, Read, Write };
+
+		fn read_all<R: Read>(mut r: R) -> (Vec<u8>, Option<String>) {
+			let mut output = Vec::new();
+			let mut buf = [0; 1000];
+
+			loop {
+				match r.read(&mut buf) {
+					Ok(0) => return (output, None),
+					Ok(n) => output.extend_from_slice(&buf[..n]),
+					Err(e) => return (output, Some(e.to_string())),
+				}
+			}
+		}
+
+		let input = (0..50000).map(|i| format!("{} ", i * 31 % 977)).collect::<String>().into_bytes();
+
+		let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 3000);
+		compressor.write_all(&input).unwrap();
+		let mut file = compressor.finish().unwrap();
+
+		// corrupt the middle of the file, and the first byte of a later chunk
+		let len = file.len();
+		file[len / 2] ^= 0xff;
+		file[len * 3 / 4] ^= 0xff;
+
+		let expected = read_all(SeekableDecompressor::new(Cursor::new(&file[..])).unwrap());
+		assert!(expected.1.is_some());
+
+		for &threads in &[1, 3, 16] {
+			assert_eq!(expected, read_all(ParallelDecompressor::new(Cursor::new(&file[..]), threads).unwrap()));
+		}
+
+		// without seek table, truncated
+		let mut compressor = Compressor::new(Vec::new());
+		compressor.write_all(&input).unwrap();
+		let mut brotli_stream = compressor.finish().unwrap();
+		let len = brotli_stream.len();
+		brotli_stream.truncate(len / 2);
+
+		let expected = read_all(::Decompressor::new(&brotli_stream[..]));
+		assert!(expected.1.is_some());
+		assert_eq!(expected, read_all(ParallelDecompressor::new(Cursor::new(&brotli_stream[..]), 4).unwrap()));
+	}
+
+	#[test]
+	fn should_decompress_concatenated_streams() {
+		use super::ParallelDecompressor;
+		use ::{ Compressor, Decompressor, StreamError };
+		use std::io::{ Cursor, Read, Write };
+
+		let inputs = (0..3).map(|n| (0..20000).map(|i| format!("{} ", i * (n + 31) % 977)).collect::<String>().into_bytes()).collect::<Vec<_>>();
+		let members = inputs.iter().map(|input| {
+			let mut compressor = Compressor::new(Vec::new());
+			compressor.write_all(input).unwrap();
+			compressor.finish().unwrap()
+		}).collect::<Vec<_>>();
+
+		for &threads in &[1, 2, 8] {
+			let mut decompressed = Vec::new();
+			ParallelDecompressor::new(Cursor::new(members.concat()), threads).unwrap().read_to_end(&mut decompressed).unwrap();
+
+			assert_eq!(inputs.concat(), decompressed);
+		}
+
+		// corrupt the middle stream, so that the last one does not show
+		let mut corrupt = members[1].clone();
+		let len = corrupt.len();
+
+		for b in &mut corrupt[(len / 2)..(len / 2 + 8)] {
+			*b ^= 0xff;
+		}
+
+		let brotli_stream = [&members[0][..], &corrupt[..], &members[2][..]].concat();
+
+		// the corrupt stream is decoded up to its error, which may lie beyond its own bytes
+		let mut expected = inputs[0].clone();
+		let expected_error = Decompressor::new(&brotli_stream[members[0].len()..]).read_to_end(&mut expected).unwrap_err();
+
+		for &threads in &[1, 3] {
+			let mut decompressor = ParallelDecompressor::new(Cursor::new(&brotli_stream[..]), threads).unwrap();
+			let mut decompressed = Vec::new();
+			let e = decompressor.read_to_end(&mut decompressed).unwrap_err();
+
+			assert_eq!(expected, decompressed);
+			assert_eq!(expected_error.to_string(), e.to_string());
+			assert!(e.get_ref().and_then(|e| e.downcast_ref::<StreamError>()).is_some());
+			assert_eq!(e.to_string(), decompressor.read(&mut [0; 10]).unwrap_err().to_string());
+		}
+	}
+
+	#[test]
+	fn should_bound_buffered_output() {
+		use super::ParallelDecompressor;
+		use ::{ Compressor, SeekableCompressor };
+		use std::cmp;
+		use std::io::{ Cursor, Read, Seek, Write };
+
+		// output, that is held by the decompressor, but has not been returned yet
+		fn read_all<R: Read + Seek>(mut decompressor: ParallelDecompressor<R>) -> (Vec<u8>, usize) {
+			let mut output = Vec::new();
+			let mut buf = vec![0; 10000];
+			let mut peak = 0;
+
+			loop {
+				match decompressor.read(&mut buf).unwrap() {
+					0 => return (output, peak),
+					n => output.extend_from_slice(&buf[..n]),
+				}
+
+				let pending = decompressor.pending.values().map(|result| result.0.len()).sum::<usize>();
+				peak = cmp::max(peak, decompressor.buf.len() - decompressor.pos + pending);
+			}
+		}
+
+		// highly compressible, so that a little input makes a lot of output
+		let input = (0..1 << 20).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+		let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 1 << 16);
+		compressor.write_all(&input).unwrap();
+		let file = compressor.finish().unwrap();
+
+		let (decompressed, peak) = read_all(ParallelDecompressor::new(Cursor::new(file), 4).unwrap());
+
+		assert_eq!(input, decompressed);
+		assert!(peak <= 5 << 16);
+
+		let member = {
+			let mut compressor = Compressor::new(Vec::new());
+			compressor.write_all(&input).unwrap();
+			compressor.finish().unwrap()
+		};
+
+		// streams without seek table are decoded straight into the buffer of read()
+		let (decompressed, peak) = read_all(ParallelDecompressor::new(Cursor::new(member.repeat(3)), 4).unwrap());
+
+		assert_eq!(input.repeat(3), decompressed);
+		assert_eq!(0, peak);
+	}
+
+	#[test]
+	fn should_compress_empty_input() {
+		use super::ParallelCompressor;
+		use ::Decompressor;
+		use std::io::Read;
+
+		let brotli_stream = ParallelCompressor::new(Vec::new(), 2).finish().unwrap();
+		let mut decompressed = Vec::new();
+
+		Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed).unwrap();
+
+		assert!(decompressed.is_empty());
+	}
+
+	#[test]
+	fn should_compress_deterministically() {
+		use super::ParallelCompressor;
+		use ::Decompressor;
+		use std::io::{ Read, Write };
+
+		let input = (0..60000).map(|i| format!("{}:{} ", i % 701, i % 13)).collect::<String>().into_bytes();
+
+		for &prime in &[false, true] {
+			let streams = (0..3).map(|_| {
+				let mut compressor = ParallelCompressor::with_options(Vec::new(), 3, 20000, prime);
+
+				for piece in input.chunks(7777) {
+					compressor.write_all(piece).unwrap();
+				}
+
+				compressor.finish().unwrap()
+			}).collect::<Vec<_>>();
+
+			assert_eq!(streams[0], streams[1]);
+			assert_eq!(streams[0], streams[2]);
+
+			// the number of threads does not matter for the output
+			let mut compressor = ParallelCompressor::with_options(Vec::new(), 1, 20000, prime);
+			compressor.write_all(&input).unwrap();
+			assert_eq!(streams[0], compressor.finish().unwrap());
+
+			let mut decompressed = Vec::new();
+			Decompressor::new(&streams[0][..]).read_to_end(&mut decompressed).unwrap();
+
+			assert_eq!(input, decompressed);
+		}
+	}
+
+	#[test]
+	fn should_compress_better_with_priming() {
+		use super::ParallelCompressor;
+		use ::Decompressor;
+		use std::io::{ Read, Write };
+
+		// chunks that repeat the previous chunk, which only priming can take advantage of
+		let block = (0..5000).map(|i| ((i * 7919) % 251) as u8).collect::<Vec<_>>();
+		let input = (0..8).flat_map(|_| block.clone()).collect::<Vec<_>>();
+
+		let sizes = [false, true].iter().map(|&prime| {
+			let mut compressor = ParallelCompressor::with_options(Vec::new(), 4, 5000, prime);
+			compressor.write_all(&input).unwrap();
+			compressor.flush().unwrap();
+			let brotli_stream = compressor.finish().unwrap();
+
+			let mut decompressed = Vec::new();
+			Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed).unwrap();
+			assert_eq!(input, decompressed);
+
+			brotli_stream.len()
+		}).collect::<Vec<_>>();
+
+		assert!(2 * sizes[1] < sizes[0]);
+	}
+}
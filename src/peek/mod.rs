@@ -0,0 +1,164 @@
+use ::{ Decompressor, State };
+
+use std::io;
+use std::io::Read;
+
+/// The type of a meta-block, with the number of bytes it holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetaBlockType {
+	/// The last meta-block, which is empty, and ends the stream.
+	Empty,
+	/// Metadata of the given number of bytes, which do not go into the output.
+	Metadata(usize),
+	/// Bytes as they are, MLEN of them.
+	Uncompressed(usize),
+	/// Compressed data, which decompresses to MLEN bytes.
+	Compressed(usize),
+}
+
+/// The stream header, with the header of the first meta-block, as far as it has been read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamHeader {
+	/// WBITS, the base-2 logarithm of the size of the sliding window, plus 16
+	pub wbits: u8,
+	/// size of the sliding window, which backward references can reach into
+	pub window_size: usize,
+	/// type of the first meta-block, once its header has been read
+	pub first_meta_block: Option<MetaBlockType>,
+}
+
+impl StreamHeader {
+	/// Returns whether the stream is empty, because its first meta-block is the last, empty one.
+	pub fn is_empty(&self) -> bool {
+		self.first_meta_block == Some(MetaBlockType::Empty)
+	}
+
+	/// Returns MLEN of the first meta-block, or 0, if it holds no data, or has not been read.
+	pub fn mlen(&self) -> usize {
+		match self.first_meta_block {
+			Some(MetaBlockType::Uncompressed(m_len)) | Some(MetaBlockType::Compressed(m_len)) => m_len,
+			_ => 0,
+		}
+	}
+}
+
+impl<R: Read> Decompressor<R> {
+	/// Returns the stream header, once it has been read, with the type of the first
+	/// meta-block, once its header has been read, too.
+	pub fn header(&self) -> Option<StreamHeader> {
+		self.stream_header
+	}
+
+	/// Fills in the stream header from the states of the stream header and the first meta-block.
+	pub(crate) fn peek_state(&mut self, state: &State) {
+		let meta_block_type = match *state {
+			State::HeaderEnd => {
+				self.stream_header = Some(StreamHeader{
+					wbits: self.header.wbits.unwrap(),
+					window_size: self.header.window_size.unwrap(),
+					first_meta_block: None,
+				});
+
+				return;
+			},
+			State::IsLastEmpty(true) => MetaBlockType::Empty,
+			State::MSkipBytes(0) => MetaBlockType::Metadata(0),
+			State::MSkipLen(m_skip_len) => MetaBlockType::Metadata(m_skip_len as usize),
+			State::MLen(m_len) if self.meta_block.header.is_last.unwrap() => MetaBlockType::Compressed(m_len as usize),
+			State::IsUncompressed(true) => MetaBlockType::Uncompressed(self.meta_block.header.m_len.unwrap() as usize),
+			State::IsUncompressed(false) => MetaBlockType::Compressed(self.meta_block.header.m_len.unwrap() as usize),
+			_ => return,
+		};
+
+		if let Some(ref mut stream_header) = self.stream_header {
+			stream_header.first_meta_block = Some(meta_block_type);
+		}
+	}
+}
+
+/// Reads the stream header, and the header of the first meta-block, without decoding any
+/// further. The bytes may be the start of a stream only, as long as they hold both headers.
+/// Returns an error with kind InvalidData, if the headers are invalid, or incomplete.
+///
+/// # Examples
+/// ```
+/// use brotli::{ MetaBlockType, peek_header };
+///
+/// let header = peek_header(&[0x0b, 0x00, 0x80]).unwrap();
+///
+/// assert_eq!(22, header.wbits);
+/// assert_eq!(Some(MetaBlockType::Uncompressed(1)), header.first_meta_block);
+/// assert!(!header.is_empty());
+///
+/// assert!(peek_header(&[0x06]).unwrap().is_empty());
+/// ```
+pub fn peek_header(data: &[u8]) -> io::Result<StreamHeader> {
+	let mut decompressor = Decompressor::new(data);
+	decompressor.peek = true;
+	decompressor.discard_output = true;
+
+	loop {
+		match decompressor.stream_header {
+			Some(stream_header) if stream_header.first_meta_block.is_some() => return Ok(stream_header),
+			_ => {},
+		}
+
+		match decompressor.decompress(&mut []) {
+			Ok(_) => {},
+			Err(e) => return Err(decompressor.stream_error(e)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_peek_at_compressed_stream() {
+		use super::{ MetaBlockType, peek_header };
+		use std::fs::File;
+		use std::io::Read;
+
+		let mut data = Vec::new();
+		File::open("data/alice29.txt.compressed").unwrap().read_to_end(&mut data).unwrap();
+
+		let header = peek_header(&data[..16]).unwrap();
+
+		assert_eq!(Some(header), peek_header(&data).ok());
+		assert!(header.window_size >= 1 << 10);
+		assert!(header.mlen() > 0);
+		assert_eq!(Some(MetaBlockType::Compressed(header.mlen())), header.first_meta_block);
+	}
+
+	#[test]
+	fn should_fill_in_header_while_decompressing() {
+		use super::MetaBlockType;
+		use ::Decompressor;
+		use ::compressor::Compressor;
+		use std::io::{ Read, Write };
+
+		let mut compressor = Compressor::with_dictionary(Vec::new(), b"dictionary");
+		compressor.write_all(b"dictionary").unwrap();
+		let compressed = compressor.finish().unwrap();
+
+		let mut decompressor = Decompressor::with_dictionary(&compressed[..], b"dictionary");
+
+		assert_eq!(None, decompressor.header());
+
+		decompressor.read_to_end(&mut Vec::new()).unwrap();
+
+		let header = decompressor.header().unwrap();
+
+		match header.first_meta_block {
+			Some(MetaBlockType::Metadata(len)) => assert!(len > 0),
+			first_meta_block => panic!("unexpected {:?}", first_meta_block),
+		}
+	}
+
+	#[test]
+	fn should_reject_incomplete_header() {
+		use super::peek_header;
+
+		assert!(peek_header(&[]).is_err());
+		assert!(peek_header(&[0x0b, 0x00]).is_err());
+	}
+}
@@ -0,0 +1,342 @@
+use ::bitreader::BitReader;
+use ::bitwriter::BitWriter;
+use ::compressor::write_empty_metadata_meta_block;
+use ::{ Decompressor, Header, State, WBits };
+
+use std::io;
+use std::io::Read;
+
+/// WBITS of streams, whose header is lost.
+const DEFAULT_WBITS: WBits = 22;
+
+/// The point at which decoding of a segment has failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Corruption {
+	/// offset of the bit in the stream, at which the error has been detected
+	pub bit_offset: u64,
+	/// description of the error
+	pub message: String,
+}
+
+/// Output of a part of a stream, as recover() returns it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+	/// offset of the byte in the stream, at which decoding of the segment has started
+	pub offset: u64,
+	/// decompressed bytes, up to the error
+	pub data: Vec<u8>,
+	/// whether the segment starts at the start of the stream. Segments after a resynchronization
+	/// may be wrong, because their backward references may reach into data, that has been lost.
+	pub reliable: bool,
+	/// the error, which has ended the segment, if any
+	pub error: Option<Corruption>,
+}
+
+/// Returns whether a stream starts with the header of a meta-block that is not the last one,
+/// and that is either uncompressed or holds metadata, with zero fill bits, and content that
+/// fits into the stream. Those are the meta-blocks, after which the stream is byte-aligned,
+/// and which do not depend on the data before them.
+fn is_resync_point(stream: &[u8]) -> bool {
+	let mut in_stream = BitReader::new(stream);
+
+	match in_stream.read_bit() {
+		Ok(false) => {},
+		_ => return false,
+	}
+
+	let len = match in_stream.read_u8_from_n_bits(2) {
+		Ok(3) => {
+			let m_skip_bytes = match (in_stream.read_bit(), in_stream.read_u8_from_n_bits(2)) {
+				(Ok(false), Ok(m_skip_bytes)) => m_skip_bytes as usize,
+				_ => return false,
+			};
+
+			match in_stream.read_u32_from_n_bits(8 * m_skip_bytes) {
+				Ok(0) if m_skip_bytes == 0 => 0,
+				Ok(m_skip_len) if m_skip_bytes == 1 || m_skip_len >> (8 * m_skip_bytes - 8) > 0 => m_skip_len as usize + 1,
+				_ => return false,
+			}
+		},
+		Ok(m_nibbles) => {
+			let m_nibbles = m_nibbles as usize + 4;
+
+			let m_len = match in_stream.read_u32_from_n_bits(4 * m_nibbles) {
+				Ok(m_len) if m_nibbles == 4 || m_len >> (4 * m_nibbles - 4) > 0 => m_len as usize + 1,
+				_ => return false,
+			};
+
+			match in_stream.read_bit() {
+				Ok(true) => m_len,
+				_ => return false,
+			}
+		},
+		Err(_) => return false,
+	};
+
+	match in_stream.read_u8_from_byte_tail() {
+		Ok(0) => in_stream.global_bit_pos() / 8 + len <= stream.len(),
+		_ => false,
+	}
+}
+
+/// Outcome of decoding a stream as far as possible.
+struct Decoded {
+	/// decompressed bytes, unless they have been discarded
+	data: Vec<u8>,
+	/// the error with its bit offset, if any
+	error: Option<(usize, String)>,
+	/// number of meta-blocks that have been completed
+	completed: usize,
+	/// WBITS, if the stream header has been read
+	wbits: Option<WBits>,
+	/// bit offset of the last meta-block that has been completed before the error
+	last_start: usize,
+}
+
+/// Decodes a stream as far as possible. With a limit, decoding stops once that many meta-blocks
+/// have been completed, and the decompressed bytes are discarded.
+fn decode(stream: &[u8], max_meta_blocks: Option<usize>) -> Decoded {
+	let mut decompressor = Decompressor::new(stream);
+	decompressor.collect_stats();
+	decompressor.discard_output = max_meta_blocks.is_some();
+
+	let mut data = Vec::new();
+
+	// Without output, decompress() keeps all bytes in its buffer, even if it fails.
+	let error = loop {
+		let result = decompressor.decompress(&mut []);
+
+		while let Some(literal) = decompressor.buf.pop_back() {
+			data.push(literal);
+		}
+
+		match result {
+			Ok(_) if decompressor.state == State::StreamEnd => break None,
+			Ok(_) if max_meta_blocks.is_some_and(|max| decompressor.stats().len() > max) => break None,
+			Ok(_) => {},
+			Err(e) => break Some((decompressor.in_stream.global_bit_pos(), e.to_string())),
+		}
+	};
+
+	let stats = decompressor.stats();
+	let completed = if error.is_none() && decompressor.state == State::StreamEnd { stats.len() } else { stats.len().saturating_sub(1) };
+
+	// Meta-blocks follow each other without gaps, after WBITS.
+	let mut last_start = 0;
+
+	if completed > 0 {
+		let wbits = decompressor.header.wbits.unwrap();
+		last_start = Header::new().wbits_codes.bit_string_for_symbol(wbits as u16).unwrap().len();
+
+		for meta_block in &stats[..(completed - 1)] {
+			last_start += meta_block.total_bits();
+		}
+	}
+
+	Decoded{
+		data,
+		error,
+		completed,
+		wbits: decompressor.header.wbits,
+		last_start,
+	}
+}
+
+/// Decodes a stream in best-effort mode, for streams that are truncated or partially corrupt.
+///
+/// The first segment holds all bytes that have been decoded before the first error, along
+/// with the bit offset of the error. After an error, the stream is searched for byte-aligned
+/// uncompressed or metadata meta-blocks, from within the last meta-block that has been
+/// completed, at which decoding resumes. A resynchronization is accepted, if it completes
+/// at least two meta-blocks, or the stream.
+/// The segments after a resynchronization are flagged as unreliable, because backward
+/// references in them may refer to data, that has been lost. Returns an error, only if
+/// reading from the stream fails.
+///
+/// # Examples
+/// ```
+/// use brotli::recover;
+///
+/// let segments = recover(&[0x0b, 0x00, 0x80, 0x58, 0x0b][..]).unwrap();
+///
+/// assert_eq!(1, segments.len());
+/// assert_eq!(b"X".to_vec(), segments[0].data);
+/// assert_eq!(Some(40), segments[0].error.as_ref().map(|e| e.bit_offset));
+/// ```
+pub fn recover<R: Read>(mut r: R) -> io::Result<Vec<Segment>> {
+	let mut stream = Vec::new();
+
+	match r.read_to_end(&mut stream) {
+		Ok(_) => {},
+		Err(e) => return Err(e),
+	}
+
+	let decoded = decode(&stream, None);
+	let mut segments = vec![Segment{
+		offset: 0,
+		data: decoded.data,
+		reliable: true,
+		error: decoded.error.map(|(bit_offset, message)| Corruption{
+			bit_offset: bit_offset as u64,
+			message,
+		}),
+	}];
+
+	// Resumed streams get a header with the original WBITS, and an empty metadata
+	// meta-block, so that they continue at a byte boundary.
+	let mut out_stream = BitWriter::new();
+	out_stream.write_bit_string(&Header::new().wbits_codes.bit_string_for_symbol(decoded.wbits.unwrap_or(DEFAULT_WBITS) as u16).unwrap());
+	write_empty_metadata_meta_block(&mut out_stream);
+	let header = out_stream.take_bytes();
+
+	// The error may show only after a corrupt meta-block, so the search starts within
+	// the last meta-block that has been completed.
+	let mut pos = match segments[0].error {
+		Some(_) => decoded.last_start / 8 + 1,
+		None => stream.len(),
+	};
+
+	while pos < stream.len() {
+		if !is_resync_point(&stream[pos..]) {
+			pos += 1;
+			continue;
+		}
+
+		let mut resumed = header.clone();
+		resumed.extend_from_slice(&stream[pos..]);
+
+		// A candidate is only decoded as far as needed for its acceptance, and the meta-block
+		// of the header does not count.
+		let trial = decode(&resumed, Some(3));
+
+		if trial.error.is_some() && trial.completed < 3 {
+			pos += 1;
+			continue;
+		}
+
+		let decoded = decode(&resumed, None);
+		let error = decoded.error.map(|(bit_offset, message)| Corruption{
+			bit_offset: (pos * 8 + bit_offset - header.len() * 8) as u64,
+			message,
+		});
+
+		let next = match error {
+			Some(_) if decoded.last_start >= header.len() * 8 => pos + (decoded.last_start - header.len() * 8) / 8 + 1,
+			Some(_) => pos + 1,
+			None => stream.len(),
+		};
+
+		segments.push(Segment{
+			offset: pos as u64,
+			data: decoded.data,
+			reliable: false,
+			error,
+		});
+
+		pos = next;
+	}
+
+	Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+	/// Returns a stream of meta-blocks, which are uncompressed, because their bytes are random.
+	fn uncompressed_stream(blocks: &[&[u8]]) -> Vec<u8> {
+		use ::compressor::Compressor;
+		use std::io::Write;
+
+		let mut compressor = Compressor::new(Vec::new());
+
+		for block in blocks {
+			compressor.write_all(block).unwrap();
+			compressor.flush().unwrap();
+		}
+
+		compressor.finish().unwrap()
+	}
+
+	fn random_bytes(seed: u32, len: usize) -> Vec<u8> {
+		let mut seed = seed;
+
+		(0..len).map(|_| {
+			seed ^= seed << 13;
+			seed ^= seed >> 17;
+			seed ^= seed << 5;
+			(seed >> 24) as u8
+		}).collect()
+	}
+
+	#[test]
+	fn should_return_single_reliable_segment_for_valid_stream() {
+		use super::recover;
+		use std::fs::File;
+
+		let segments = recover(File::open("data/alice29.txt.compressed").unwrap()).unwrap();
+
+		assert_eq!(1, segments.len());
+		assert_eq!(152089, segments[0].data.len());
+		assert!(segments[0].reliable);
+		assert_eq!(None, segments[0].error);
+	}
+
+	#[test]
+	fn should_keep_output_before_truncation() {
+		use super::recover;
+
+		let blocks = (0..4).map(|i| random_bytes(i + 1, 1000)).collect::<Vec<_>>();
+		let stream = uncompressed_stream(&blocks.iter().map(|b| &b[..]).collect::<Vec<_>>());
+
+		let segments = recover(&stream[..(stream.len() / 2)]).unwrap();
+
+		assert_eq!(1, segments.len());
+		assert!(segments[0].error.is_some());
+		assert!(segments[0].data.len() >= 1000);
+		assert_eq!(&blocks.concat()[..segments[0].data.len()], &segments[0].data[..]);
+	}
+
+	#[test]
+	fn should_stop_decoding_after_limit() {
+		use super::decode;
+
+		let blocks = (0..4).map(|i| random_bytes(i + 1, 1000)).collect::<Vec<_>>();
+		let stream = uncompressed_stream(&blocks.iter().map(|b| &b[..]).collect::<Vec<_>>());
+
+		let trial = decode(&stream, Some(2));
+
+		assert_eq!(None, trial.error);
+		assert_eq!(2, trial.completed);
+		assert!(trial.data.is_empty());
+
+		let decoded = decode(&stream, None);
+
+		assert_eq!(None, decoded.error);
+		assert_eq!(blocks.concat(), decoded.data);
+	}
+
+	#[test]
+	fn should_resynchronize_at_uncompressed_meta_block() {
+		use super::recover;
+
+		let blocks = (0..4).map(|i| random_bytes(i + 1, 1000)).collect::<Vec<_>>();
+		let mut stream = uncompressed_stream(&blocks.iter().map(|b| &b[..]).collect::<Vec<_>>());
+
+		// The header of the second meta-block starts at a byte boundary, after the first one.
+		let second = stream.len() - 3 * (1000 + 3) - 1;
+		// MLEN - 1 = 999 becomes 1015, so that the third meta-block starts within the second one
+		stream[second] ^= 0x80;
+
+		let segments = recover(&stream[..]).unwrap();
+
+		assert!(segments.len() >= 2);
+		assert!(segments[0].reliable);
+		assert!(segments[0].error.is_some());
+		assert_eq!(&blocks[0][..], &segments[0].data[..1000]);
+
+		let last = &segments[segments.len() - 1];
+
+		assert!(!last.reliable);
+		assert_eq!(None, last.error);
+		assert_eq!(&blocks[2..].concat()[..], &last.data[(last.data.len() - 2000)..]);
+	}
+}
@@ -0,0 +1,604 @@
+use ::bitwriter::BitWriter;
+use ::compressor::{ Compressor, write_metadata_meta_block };
+use ::{ clone_error, Decompressor, Header };
+
+use std::cmp;
+use std::io;
+use std::io::{ Read, Seek, SeekFrom, Write };
+
+/// Ends the seek table, preceded by the number of chunks.
+const SEEK_TABLE_MAGIC: &[u8; 4] = b"BRST";
+/// Size of the input chunks, unless given otherwise.
+const DEFAULT_CHUNK_SIZE: usize = 1 << 20;
+/// A meta-block with ISLAST and ISLASTEMPTY set, on its own byte.
+const LAST_META_BLOCK: u8 = 0x03;
+/// Number of bytes of an entry in the seek table: compressed size and uncompressed size.
+const ENTRY_SIZE: usize = 8;
+/// Number of bytes at the end of a file, that describe the seek table: the number of chunks,
+/// the magic bytes, and the last meta-block.
+const FOOTER_SIZE: usize = 9;
+/// Largest ratio of uncompressed size to compressed size of a chunk, up to which its content
+/// is allocated up front: the seek table is not trusted with larger allocations.
+const MAX_PREALLOCATION_RATIO: u64 = 64;
+
+/// Wraps an output stream, and writes a seekable file to it: the input is cut into chunks of
+/// a fixed size, each of which is compressed into a complete Brotli stream of its own, followed
+/// by a seek table, which holds the compressed size and the uncompressed size of every chunk.
+/// The file needs to be completed with finish().
+///
+/// The seek table is a Brotli stream, too, which only holds a metadata meta-block, so that
+/// decoders that accept concatenated Brotli streams decode the whole file to its content.
+/// Chunk streams start and end on a byte boundary, and without their last byte, which is an
+/// empty last meta-block, they are catable streams for brotli::concat().
+///
+/// # Examples
+/// ```
+/// use std::io::{ Cursor, Read, Seek, SeekFrom, Write };
+/// use brotli::{ SeekableCompressor, SeekableDecompressor };
+///
+/// let input = (0..10000).map(|i| format!("{:>8}\n", i)).collect::<String>();
+///
+/// let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 4096);
+/// compressor.write_all(input.as_bytes()).unwrap();
+/// let file = compressor.finish().unwrap();
+///
+/// let mut decompressor = SeekableDecompressor::new(Cursor::new(file)).unwrap();
+/// let mut line = [0; 9];
+///
+/// decompressor.seek(SeekFrom::Start(9 * 7654)).unwrap();
+/// decompressor.read_exact(&mut line).unwrap();
+///
+/// assert_eq!(b"    7654\n", &line);
+/// ```
+#[derive(Debug)]
+pub struct SeekableCompressor<W: Write> {
+	inner: W,
+	chunk_size: usize,
+	buf: Vec<u8>,
+	/// compressed size and uncompressed size of every chunk so far
+	entries: Vec<(u32, u32)>,
+}
+
+impl<W: Write> SeekableCompressor<W> {
+	/// Creates SeekableCompressor from Write, with chunks of 1 MiB.
+	pub fn new(w: W) -> SeekableCompressor<W> {
+		SeekableCompressor::with_chunk_size(w, DEFAULT_CHUNK_SIZE)
+	}
+
+	/// Creates SeekableCompressor from Write, with chunks of chunk_size bytes. Smaller chunks
+	/// make for faster random access, larger chunks for better compression.
+	///
+	/// # Panics
+	/// Panics, if chunk_size is 0 or larger than 1 GiB.
+	pub fn with_chunk_size(w: W, chunk_size: usize) -> SeekableCompressor<W> {
+		assert!(chunk_size > 0 && chunk_size <= 1 << 30);
+
+		SeekableCompressor{
+			inner: w,
+			chunk_size,
+			buf: Vec::new(),
+			entries: Vec::new(),
+		}
+	}
+
+	fn write_chunk(&mut self, len: usize) -> io::Result<()> {
+		let mut compressor = Compressor::new_catable(Vec::with_capacity(len));
+
+		let mut chunk = match compressor.write_all(&self.buf[..len]).and_then(|_| compressor.finish()) {
+			Ok(chunk) => chunk,
+			Err(e) => return Err(e),
+		};
+
+		chunk.push(LAST_META_BLOCK);
+
+		match self.inner.write_all(&chunk) {
+			Ok(()) => {},
+			Err(e) => return Err(e),
+		}
+
+		self.entries.push((chunk.len() as u32, len as u32));
+		self.buf.drain(0..len);
+
+		Ok(())
+	}
+
+	/// Compresses all pending input into a last chunk, writes the seek table, and returns
+	/// the underlying writer. Returns an error with kind InvalidInput, if there are too
+	/// many chunks for the seek table.
+	pub fn finish(mut self) -> io::Result<W> {
+		if !self.buf.is_empty() {
+			let len = self.buf.len();
+
+			match self.write_chunk(len) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		if ENTRY_SIZE * self.entries.len() + FOOTER_SIZE - 1 > 1 << 24 {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "Too many chunks for the seek table"));
+		}
+
+		let mut seek_table = Vec::with_capacity(ENTRY_SIZE * self.entries.len() + FOOTER_SIZE - 1);
+
+		for &(compressed_size, uncompressed_size) in &self.entries {
+			seek_table.extend_from_slice(&u32_to_le(compressed_size));
+			seek_table.extend_from_slice(&u32_to_le(uncompressed_size));
+		}
+
+		seek_table.extend_from_slice(&u32_to_le(self.entries.len() as u32));
+		seek_table.extend_from_slice(SEEK_TABLE_MAGIC);
+
+		// WBITS is irrelevant for a stream without data, so take the shortest code.
+		let mut out_stream = BitWriter::new();
+		out_stream.write_bit_string(&Header::new().wbits_codes.bit_string_for_symbol(16).unwrap());
+		write_metadata_meta_block(&mut out_stream, &seek_table);
+
+		let mut bytes = out_stream.take_bytes();
+		bytes.push(LAST_META_BLOCK);
+
+		match self.inner.write_all(&bytes).and_then(|_| self.inner.flush()) {
+			Ok(()) => Ok(self.inner),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+impl<W: Write> Write for SeekableCompressor<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.buf.extend_from_slice(buf);
+
+		while self.buf.len() >= self.chunk_size {
+			let len = self.chunk_size;
+
+			match self.write_chunk(len) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(buf.len())
+	}
+
+	/// Compresses all pending input into a chunk of its own, which may be shorter than
+	/// the chunk size, and flushes the underlying writer.
+	fn flush(&mut self) -> io::Result<()> {
+		if !self.buf.is_empty() {
+			let len = self.buf.len();
+
+			match self.write_chunk(len) {
+				Ok(()) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		self.inner.flush()
+	}
+}
+
+/// A chunk of a seekable file, as it is described by the seek table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chunk {
+	pub compressed_offset: u64,
+	pub compressed_size: u64,
+	pub uncompressed_offset: u64,
+	pub uncompressed_size: u64,
+}
+
+/// Reads the seek table from the end of a stream. Returns None, if the stream does not end
+/// with a seek table, and an error with kind InvalidData, if the seek table does not fit the
+/// stream. The position of the stream is undefined afterwards.
+pub fn read_seek_table<R: Read + Seek>(r: &mut R) -> io::Result<Option<Vec<Chunk>>> {
+	let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Seek table does not fit the stream");
+
+	let len = match r.seek(SeekFrom::End(0)) {
+		Ok(len) => len,
+		Err(e) => return Err(e),
+	};
+
+	if len < FOOTER_SIZE as u64 {
+		return Ok(None);
+	}
+
+	let mut footer = [0; FOOTER_SIZE];
+
+	match r.seek(SeekFrom::Start(len - FOOTER_SIZE as u64)).and_then(|_| r.read_exact(&mut footer)) {
+		Ok(()) => {},
+		Err(e) => return Err(e),
+	}
+
+	if &footer[4..8] != SEEK_TABLE_MAGIC || footer[8] != LAST_META_BLOCK {
+		return Ok(None);
+	}
+
+	let n_chunks = le_to_u32(&footer[0..4]) as u64;
+	let table_len = ENTRY_SIZE as u64 * n_chunks + FOOTER_SIZE as u64;
+
+	if table_len > len {
+		return Err(invalid());
+	}
+
+	let mut table = vec![0; ENTRY_SIZE * n_chunks as usize];
+
+	match r.seek(SeekFrom::Start(len - table_len)).and_then(|_| r.read_exact(&mut table)) {
+		Ok(()) => {},
+		Err(e) => return Err(e),
+	}
+
+	let mut chunks = Vec::with_capacity(n_chunks as usize);
+	let mut compressed_offset = 0;
+	let mut uncompressed_offset = 0;
+
+	for entry in table.chunks(ENTRY_SIZE) {
+		let chunk = Chunk{
+			compressed_offset,
+			compressed_size: le_to_u32(&entry[0..4]) as u64,
+			uncompressed_offset,
+			uncompressed_size: le_to_u32(&entry[4..8]) as u64,
+		};
+
+		compressed_offset += chunk.compressed_size;
+		uncompressed_offset += chunk.uncompressed_size;
+		chunks.push(chunk);
+	}
+
+	// the chunks precede the seek table
+	if compressed_offset > len - table_len {
+		return Err(invalid());
+	}
+
+	Ok(Some(chunks))
+}
+
+/// Decompresses a chunk, and checks its size against the seek table. Returns the content,
+/// and the error that has ended it, if any: a corrupt chunk yields its output up to the error,
+/// as sequential decompression does.
+pub fn decompress_chunk(chunk: &Chunk, compressed: &[u8]) -> (Vec<u8>, Option<io::Error>) {
+	let capacity = cmp::min(chunk.uncompressed_size, compressed.len() as u64 * MAX_PREALLOCATION_RATIO);
+	let mut content = Vec::with_capacity(capacity as usize);
+
+	// one byte more than the seek table has it, which tells a longer chunk apart
+	match Decompressor::new(compressed).take(chunk.uncompressed_size + 1).read_to_end(&mut content) {
+		Ok(_) => {},
+		Err(e) => return (content, Some(e)),
+	}
+
+	if content.len() as u64 != chunk.uncompressed_size {
+		content.truncate(chunk.uncompressed_size as usize);
+
+		return (content, Some(io::Error::new(io::ErrorKind::InvalidData, "Chunk does not match its size in the seek table")));
+	}
+
+	(content, None)
+}
+
+/// Wraps a seekable input stream of a file from SeekableCompressor, and provides random access
+/// to its content: only the chunk, that holds the current position, is decompressed.
+#[derive(Debug)]
+pub struct SeekableDecompressor<R: Read + Seek> {
+	inner: R,
+	chunks: Vec<Chunk>,
+	content_size: u64,
+	pos: u64,
+	/// index and content of the chunk that has been decompressed last, and the error that
+	/// follows its content, if any
+	current: Option<(usize, Vec<u8>, Option<io::Error>)>,
+}
+
+impl<R: Read + Seek> SeekableDecompressor<R> {
+	/// Creates SeekableDecompressor from Read + Seek, and reads the seek table from the end
+	/// of the stream. Returns an error with kind InvalidData, if the stream does not end with
+	/// a seek table, or if the seek table does not fit the stream.
+	pub fn new(mut r: R) -> io::Result<SeekableDecompressor<R>> {
+		let chunks = match read_seek_table(&mut r) {
+			Ok(Some(chunks)) => chunks,
+			Ok(None) => return Err(io::Error::new(io::ErrorKind::InvalidData, "Stream does not end with a seek table")),
+			Err(e) => return Err(e),
+		};
+
+		Ok(SeekableDecompressor{
+			inner: r,
+			content_size: chunks.last().map(|chunk| chunk.uncompressed_offset + chunk.uncompressed_size).unwrap_or(0),
+			chunks,
+			pos: 0,
+			current: None,
+		})
+	}
+
+	/// Returns the size of the decompressed content.
+	pub fn content_size(&self) -> u64 {
+		self.content_size
+	}
+
+	/// Returns the number of chunks.
+	pub fn chunk_count(&self) -> usize {
+		self.chunks.len()
+	}
+
+	/// Decompresses the chunk with the given index, unless it has been decompressed last.
+	fn load_chunk(&mut self, index: usize) -> io::Result<()> {
+		match self.current {
+			Some((current, _, _)) if current == index => return Ok(()),
+			_ => {},
+		}
+
+		let chunk = self.chunks[index];
+		let mut compressed = vec![0; chunk.compressed_size as usize];
+
+		match self.inner.seek(SeekFrom::Start(chunk.compressed_offset)).and_then(|_| self.inner.read_exact(&mut compressed)) {
+			Ok(()) => {},
+			Err(e) => return Err(e),
+		}
+
+		let (content, error) = decompress_chunk(&chunk, &compressed);
+		self.current = Some((index, content, error));
+
+		Ok(())
+	}
+}
+
+impl<R: Read + Seek> Read for SeekableDecompressor<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		// The error of a chunk follows its content, which may be complete.
+		if let Some((current, ref content, Some(ref e))) = self.current {
+			if self.pos == self.chunks[current].uncompressed_offset + content.len() as u64 {
+				return Err(clone_error(e));
+			}
+		}
+
+		if self.pos >= self.content_size || buf.is_empty() {
+			return Ok(0);
+		}
+
+		let pos = self.pos;
+		// the last chunk that starts at or before pos, skipping empty chunks
+		let index = match self.chunks.binary_search_by(|chunk| chunk.uncompressed_offset.cmp(&pos)) {
+			Ok(index) => index + self.chunks[index..].iter().take_while(|chunk| chunk.uncompressed_size == 0).count(),
+			Err(index) => index - 1,
+		};
+
+		match self.load_chunk(index) {
+			Ok(()) => {},
+			Err(e) => return Err(e),
+		}
+
+		let chunk = self.chunks[index];
+		let (_, ref content, ref error) = *self.current.as_ref().unwrap();
+		let start = (pos - chunk.uncompressed_offset) as usize;
+
+		// The content of a corrupt chunk ends early, with its error.
+		if start >= content.len() {
+			return Err(clone_error(error.as_ref().unwrap()));
+		}
+
+		let n = cmp::min(buf.len(), content.len() - start);
+
+		buf[..n].copy_from_slice(&content[start..(start + n)]);
+		self.pos += n as u64;
+
+		Ok(n)
+	}
+}
+
+impl<R: Read + Seek> Seek for SeekableDecompressor<R> {
+	/// Seeks to a position in the decompressed content. Seeking beyond the end is allowed,
+	/// reading from there returns no data.
+	fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+		let pos = match pos {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::End(offset) if offset >= 0 => self.content_size.checked_add(offset as u64),
+			SeekFrom::End(offset) => self.content_size.checked_sub(offset.wrapping_neg() as u64),
+			SeekFrom::Current(offset) if offset >= 0 => self.pos.checked_add(offset as u64),
+			SeekFrom::Current(offset) => self.pos.checked_sub(offset.wrapping_neg() as u64),
+		};
+
+		match pos {
+			Some(pos) => {
+				self.pos = pos;
+
+				Ok(pos)
+			},
+			None => Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek to a negative or overflowing position")),
+		}
+	}
+}
+
+fn u32_to_le(n: u32) -> [u8; 4] {
+	[n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+fn le_to_u32(bytes: &[u8]) -> u32 {
+	bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u32)
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_read_seek_table() {
+		use super::{ SeekableCompressor, SeekableDecompressor };
+		use std::io::{ Cursor, Write };
+
+		let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 1000);
+		compressor.write_all(&vec![b'a'; 2500]).unwrap();
+		let file = compressor.finish().unwrap();
+
+		assert_eq!(&[b'B', b'R', b'S', b'T', 0x03], &file[(file.len() - 5)..]);
+
+		let decompressor = SeekableDecompressor::new(Cursor::new(file)).unwrap();
+
+		assert_eq!(3, decompressor.chunk_count());
+		assert_eq!(2500, decompressor.content_size());
+		assert_eq!(vec![0, 1000, 2000], decompressor.chunks.iter().map(|chunk| chunk.uncompressed_offset).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn should_decode_as_concatenated_streams() {
+		use super::{ SeekableCompressor, SeekableDecompressor };
+		use ::{ concat, Decompressor };
+		use std::io::{ Cursor, Read, Write };
+
+		let input = (0..3000).map(|i| format!("{} bottles of beer on the wall\n", i)).collect::<String>().into_bytes();
+
+		let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 20000);
+		compressor.write_all(&input).unwrap();
+		let file = compressor.finish().unwrap();
+
+		let chunks = SeekableDecompressor::new(Cursor::new(&file[..])).unwrap().chunks;
+		let end = chunks.iter().fold(0, |acc, chunk| acc + chunk.compressed_size as usize);
+		let mut streams = Vec::new();
+		let mut decompressed = Vec::new();
+
+		for chunk in &chunks {
+			let stream = &file[(chunk.compressed_offset as usize)..((chunk.compressed_offset + chunk.compressed_size) as usize)];
+
+			// every chunk is a complete stream
+			Decompressor::new(stream).read_to_end(&mut decompressed).unwrap();
+			streams.push(&stream[..(stream.len() - 1)]);
+		}
+
+		// the seek table is a complete stream without content
+		Decompressor::new(&file[end..]).read_to_end(&mut decompressed).unwrap();
+
+		assert_eq!(input, decompressed);
+
+		let brotli_stream = concat(&streams).unwrap();
+		let mut decompressed = Vec::new();
+		Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed).unwrap();
+
+		assert_eq!(input, decompressed);
+	}
+
+	#[test]
+	fn should_reject_missing_seek_table() {
+		use super::SeekableDecompressor;
+		use ::Compressor;
+		use std::io::{ Cursor, Write };
+
+		let mut compressor = Compressor::new(Vec::new());
+		compressor.write_all(b"no seek table").unwrap();
+		let brotli_stream = compressor.finish().unwrap();
+
+		assert!(SeekableDecompressor::new(Cursor::new(brotli_stream)).is_err());
+		assert!(SeekableDecompressor::new(Cursor::new(Vec::new())).is_err());
+	}
+
+	#[test]
+	fn should_return_output_of_corrupt_chunk_before_error() {
+		use super::{ SeekableCompressor, SeekableDecompressor };
+		use ::{ Decompressor, StreamError };
+		use std::io::{ Cursor, Read, Write };
+
+		let input = (0..20000).map(|i| format!("{} ", i * 31 % 977)).collect::<String>().into_bytes();
+
+		let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 40000);
+		compressor.write_all(&input).unwrap();
+		let mut file = compressor.finish().unwrap();
+
+		let chunk = SeekableDecompressor::new(Cursor::new(&file[..])).unwrap().chunks[1];
+		let middle = (chunk.compressed_offset + chunk.compressed_size / 2) as usize;
+
+		for b in &mut file[middle..(middle + 8)] {
+			*b ^= 0xff;
+		}
+
+		let end = (chunk.compressed_offset + chunk.compressed_size) as usize;
+		let mut expected = input[..40000].to_vec();
+		let expected_error = Decompressor::new(&file[(chunk.compressed_offset as usize)..end]).read_to_end(&mut expected).unwrap_err();
+
+		// small reads, so that the error does not come with the first read of the chunk
+		let mut decompressor = SeekableDecompressor::new(Cursor::new(&file[..])).unwrap();
+		let mut decompressed = Vec::new();
+		let mut buf = [0; 7];
+
+		let e = loop {
+			match decompressor.read(&mut buf) {
+				Ok(n) => decompressed.extend_from_slice(&buf[..n]),
+				Err(e) => break e,
+			}
+		};
+
+		assert!(decompressed.len() > 40000);
+		assert_eq!(expected, decompressed);
+		assert_eq!(expected_error.to_string(), e.to_string());
+
+		let stream_error = e.get_ref().and_then(|e| e.downcast_ref::<StreamError>()).unwrap();
+
+		// every later read returns the same error
+		assert_eq!(Some(stream_error), decompressor.read(&mut buf).unwrap_err().get_ref().and_then(|e| e.downcast_ref::<StreamError>()));
+	}
+
+	#[test]
+	fn should_check_chunk_against_its_size_in_seek_table() {
+		use super::{ decompress_chunk, Chunk };
+		use ::Compressor;
+		use std::io::{ ErrorKind, Write };
+
+		let mut compressor = Compressor::new(Vec::new());
+		compressor.write_all(&vec![b'a'; 100000]).unwrap();
+		let brotli_stream = compressor.finish().unwrap();
+
+		let chunk = |uncompressed_size| Chunk{
+			compressed_offset: 0,
+			compressed_size: brotli_stream.len() as u64,
+			uncompressed_offset: 0,
+			uncompressed_size,
+		};
+
+		let (content, e) = decompress_chunk(&chunk(100000), &brotli_stream);
+
+		assert_eq!(vec![b'a'; 100000], content);
+		assert!(e.is_none());
+
+		// decompression stops right after the size in the seek table
+		let (content, e) = decompress_chunk(&chunk(10), &brotli_stream);
+
+		assert_eq!(vec![b'a'; 10], content);
+		assert_eq!(ErrorKind::InvalidData, e.unwrap().kind());
+
+		// an implausible size is not allocated up front
+		let (content, e) = decompress_chunk(&chunk(u32::MAX as u64), &brotli_stream);
+
+		assert_eq!(vec![b'a'; 100000], content);
+		assert!(content.capacity() < 1 << 20);
+		assert_eq!(ErrorKind::InvalidData, e.unwrap().kind());
+	}
+
+	#[test]
+	fn should_seek_and_read_ranges() {
+		use super::{ SeekableCompressor, SeekableDecompressor };
+		use std::io::{ Cursor, Read, Seek, SeekFrom, Write };
+
+		let input = (0..50000).map(|i| format!("{} ", i * 31 % 977)).collect::<String>().into_bytes();
+
+		let mut compressor = SeekableCompressor::with_chunk_size(Vec::new(), 10000);
+		compressor.write_all(&input[..25000]).unwrap();
+		compressor.flush().unwrap();
+		compressor.write_all(&input[25000..]).unwrap();
+		let file = compressor.finish().unwrap();
+
+		let mut decompressor = SeekableDecompressor::new(Cursor::new(file)).unwrap();
+		assert_eq!(input.len() as u64, decompressor.content_size());
+
+		for &(pos, len) in &[(0, 10), (9995, 10), (24999, 2), (70000, 5000), (input.len() - 3, 3), (12345, 54321)] {
+			let mut range = vec![0; len];
+
+			assert_eq!(pos as u64, decompressor.seek(SeekFrom::Start(pos as u64)).unwrap());
+			decompressor.read_exact(&mut range).unwrap();
+
+			assert_eq!(&input[pos..(pos + len)], &range[..]);
+		}
+
+		assert_eq!(input.len() as u64 - 7, decompressor.seek(SeekFrom::End(-7)).unwrap());
+		assert_eq!(input.len() as u64 - 10, decompressor.seek(SeekFrom::Current(-3)).unwrap());
+		assert!(decompressor.seek(SeekFrom::Current(-(input.len() as i64))).is_err());
+
+		decompressor.seek(SeekFrom::Start(0)).unwrap();
+		let mut decompressed = Vec::new();
+		decompressor.read_to_end(&mut decompressed).unwrap();
+
+		assert_eq!(input, decompressed);
+
+		decompressor.seek(SeekFrom::End(10)).unwrap();
+		assert_eq!(0, decompressor.read(&mut [0; 10]).unwrap());
+	}
+}
@@ -0,0 +1,542 @@
+use ::dictionary::{ BROTLI_DICTIONARY_OFFSETS_BY_LENGTH, BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH, BROTLI_DICTIONARY };
+use ::transformation::{ TransformType, Transforms };
+use ::DecompressorError;
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{ Display, Formatter };
+use std::io;
+
+/// Signature byte and version byte at the start of a serialized dictionary.
+const MAGIC: [u8; 2] = [0x91, 0x00];
+
+const MIN_WORD_LENGTH: usize = 4;
+const MAX_WORD_LENGTH: usize = 31;
+const MAX_SIZE_BITS: usize = 15;
+const NUM_CONTEXTS: usize = 64;
+
+/// Error types that can occur while parsing a serialized dictionary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SharedDictionaryError {
+	InvalidContextMap,
+	InvalidListIndex,
+	InvalidMagic,
+	InvalidNumberOfDictionaries,
+	InvalidPrefixSuffixId,
+	InvalidPrefixSuffixList,
+	InvalidSizeBits,
+	InvalidTransformType,
+	TrailingBytes,
+	UnexpectedEOF,
+}
+
+impl Display for SharedDictionaryError {
+	fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+		fmt.write_str(match *self {
+			SharedDictionaryError::InvalidContextMap => "Encountered dictionary id out of range in dictionary context map",
+			SharedDictionaryError::InvalidListIndex => "Encountered word list or transform list index out of range",
+			SharedDictionaryError::InvalidMagic => "Serialized dictionary does not start with signature 0x91 and version 0",
+			SharedDictionaryError::InvalidNumberOfDictionaries => "Encountered invalid number of dictionaries, expected 1 to 64",
+			SharedDictionaryError::InvalidPrefixSuffixId => "Encountered transform with prefix or suffix id out of range",
+			SharedDictionaryError::InvalidPrefixSuffixList => "Encountered invalid list of transform prefixes and suffixes",
+			SharedDictionaryError::InvalidSizeBits => "Encountered word list with more than 15 size bits for a word length",
+			SharedDictionaryError::InvalidTransformType => "Encountered invalid transform type",
+			SharedDictionaryError::TrailingBytes => "Encountered trailing bytes after serialized dictionary",
+			SharedDictionaryError::UnexpectedEOF => "Encountered unexpected end of serialized dictionary",
+		})
+	}
+}
+
+impl Error for SharedDictionaryError {}
+
+/// Words of one static dictionary, grouped by length, with 1 << size_bits_by_length[l]
+/// words of length l, and no words of a length with 0 size bits.
+#[derive(Debug, Clone, PartialEq)]
+struct WordList {
+	size_bits_by_length: [usize; MAX_WORD_LENGTH + 1],
+	offsets_by_length: [usize; MAX_WORD_LENGTH + 1],
+	data: Cow<'static, [u8]>,
+}
+
+impl WordList {
+	/// Returns the word list of RFC 7932, Appendix A.
+	fn rfc() -> WordList {
+		let mut size_bits_by_length = [0; MAX_WORD_LENGTH + 1];
+		let mut offsets_by_length = [0; MAX_WORD_LENGTH + 1];
+
+		size_bits_by_length[..25].copy_from_slice(&BROTLI_DICTIONARY_SIZE_BITS_BY_LENGTH);
+		offsets_by_length[..25].copy_from_slice(&BROTLI_DICTIONARY_OFFSETS_BY_LENGTH);
+
+		WordList{
+			size_bits_by_length,
+			offsets_by_length,
+			data: Cow::Borrowed(&BROTLI_DICTIONARY),
+		}
+	}
+}
+
+/// A static dictionary to decompress streams with, in place of the static dictionary of RFC 7932.
+///
+/// A shared dictionary, as specified by the shared Brotli draft, consists of custom word lists
+/// and custom transform lists, which may replace or extend the built-in ones. If it holds more
+/// than one pair of word list and transform list, the pair for a reference is selected by the
+/// literal context at the position of the reference.
+///
+/// A shared dictionary may also hold a raw dictionary, which is referred to like the one given
+/// to Decompressor::with_dictionary().
+///
+/// # Examples
+/// ```
+/// use brotli::SharedDictionary;
+///
+/// // no prefix, no custom word lists or transform lists, one dictionary of built-in lists
+/// let dictionary = SharedDictionary::from_bytes(&[0x91, 0x00, 0, 0, 0, 1, 0, 0]).unwrap();
+///
+/// assert_eq!(SharedDictionary::default(), dictionary);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedDictionary {
+	prefix: Vec<u8>,
+	/// custom word lists, followed by the built-in word list
+	word_lists: Vec<WordList>,
+	/// custom transform lists, followed by the built-in transform list
+	transform_lists: Vec<Transforms>,
+	/// (word list index, transform list index) by dictionary id
+	dictionaries: Vec<(usize, usize)>,
+	/// dictionary id by literal context id
+	context_map: Vec<u8>,
+}
+
+impl Default for SharedDictionary {
+	/// Returns the static dictionary of RFC 7932.
+	fn default() -> SharedDictionary {
+		SharedDictionary{
+			prefix: Vec::new(),
+			word_lists: vec![WordList::rfc()],
+			transform_lists: vec![Transforms::rfc()],
+			dictionaries: vec![(0, 0)],
+			context_map: vec![0; NUM_CONTEXTS],
+		}
+	}
+}
+
+impl SharedDictionary {
+	/// Parses a serialized dictionary. Returns an error with kind InvalidData, if the bytes
+	/// are not a valid serialized dictionary.
+	pub fn from_bytes(encoded: &[u8]) -> io::Result<SharedDictionary> {
+		match parse(encoded) {
+			Ok(dictionary) => Ok(dictionary),
+			Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+		}
+	}
+
+	/// Returns the raw dictionary, which is empty if there is none.
+	pub fn prefix(&self) -> &[u8] {
+		&self.prefix
+	}
+
+	/// Returns the transformed word for a reference to the static dictionary, where address is
+	/// the distance beyond the largest allowed backward distance, minus 1, and context_id is the
+	/// literal context id at the position of the reference.
+	pub(crate) fn word(&self, context_id: usize, copy_length: usize, address: usize) -> Result<Vec<u8>, DecompressorError> {
+		let (word_id, transform_id) = match self.reference(context_id, copy_length, address) {
+			Ok(reference) => reference,
+			Err(e) => return Err(e),
+		};
+		let (words_index, transforms_index) = self.dictionaries[self.context_map[context_id] as usize];
+		let words = &self.word_lists[words_index];
+		let offset = words.offsets_by_length[copy_length] + word_id * copy_length;
+
+		Ok(self.transform_lists[transforms_index].transform(transform_id, &words.data[offset..(offset + copy_length)]))
+	}
+
+	/// Splits a reference to the static dictionary into (word_id, transform_id), where word_id
+	/// is the index of the word among the words of length copy_length.
+	pub(crate) fn reference(&self, context_id: usize, copy_length: usize, address: usize) -> Result<(usize, usize), DecompressorError> {
+		let (words_index, transforms_index) = self.dictionaries[self.context_map[context_id] as usize];
+		let words = &self.word_lists[words_index];
+		let transforms = &self.transform_lists[transforms_index];
+
+		if !(MIN_WORD_LENGTH..=MAX_WORD_LENGTH).contains(&copy_length) || words.size_bits_by_length[copy_length] == 0 {
+			return Err(DecompressorError::InvalidLengthInStaticDictionary);
+		}
+
+		let size_bits = words.size_bits_by_length[copy_length];
+		let word_id = address & ((1 << size_bits) - 1);
+		let transform_id = address >> size_bits;
+
+		if transform_id >= transforms.len() {
+			return Err(DecompressorError::InvalidTransformId);
+		}
+
+		Ok((word_id, transform_id))
+	}
+}
+
+fn read_u8(encoded: &[u8], pos: &mut usize) -> Result<u8, SharedDictionaryError> {
+	match encoded.get(*pos) {
+		Some(&byte) => {
+			*pos += 1;
+
+			Ok(byte)
+		},
+		None => Err(SharedDictionaryError::UnexpectedEOF),
+	}
+}
+
+fn read_bytes<'a>(encoded: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8], SharedDictionaryError> {
+	if encoded.len() - *pos < n {
+		return Err(SharedDictionaryError::UnexpectedEOF);
+	}
+
+	*pos += n;
+
+	Ok(&encoded[(*pos - n)..*pos])
+}
+
+/// Reads a little endian base 128 variable length integer of at most 32 bits.
+fn read_varint(encoded: &[u8], pos: &mut usize) -> Result<usize, SharedDictionaryError> {
+	let mut value = 0;
+
+	for i in 0..5 {
+		let byte = match read_u8(encoded, pos) {
+			Ok(byte) => byte,
+			Err(e) => return Err(e),
+		};
+
+		value |= ((byte & 0x7f) as usize) << (7 * i);
+
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+
+	Err(SharedDictionaryError::UnexpectedEOF)
+}
+
+fn parse_word_list(encoded: &[u8], pos: &mut usize) -> Result<WordList, SharedDictionaryError> {
+	let mut size_bits_by_length = [0; MAX_WORD_LENGTH + 1];
+	let mut offsets_by_length = [0; MAX_WORD_LENGTH + 1];
+	let mut offset = 0;
+
+	for length in MIN_WORD_LENGTH..=MAX_WORD_LENGTH {
+		let size_bits = match read_u8(encoded, pos) {
+			Ok(size_bits) => size_bits as usize,
+			Err(e) => return Err(e),
+		};
+
+		if size_bits > MAX_SIZE_BITS {
+			return Err(SharedDictionaryError::InvalidSizeBits);
+		}
+
+		size_bits_by_length[length] = size_bits;
+		offsets_by_length[length] = offset;
+
+		if size_bits > 0 {
+			offset += length << size_bits;
+		}
+	}
+
+	match read_bytes(encoded, pos, offset) {
+		Ok(data) => Ok(WordList{
+			size_bits_by_length,
+			offsets_by_length,
+			data: Cow::Owned(data.to_vec()),
+		}),
+		Err(e) => Err(e),
+	}
+}
+
+fn parse_transforms(encoded: &[u8], pos: &mut usize) -> Result<Transforms, SharedDictionaryError> {
+	// PREFIX_SUFFIX_LENGTH, followed by prefixes and suffixes, each preceded by its length
+	let prefix_suffix_length = match read_bytes(encoded, pos, 2) {
+		Ok(bytes) => bytes[0] as usize | (bytes[1] as usize) << 8,
+		Err(e) => return Err(e),
+	};
+	let prefix_suffix_list = match read_bytes(encoded, pos, prefix_suffix_length) {
+		Ok(bytes) => bytes,
+		Err(e) => return Err(e),
+	};
+
+	let mut prefixes_suffixes = Vec::new();
+	let mut i = 0;
+
+	while i < prefix_suffix_list.len() {
+		match read_u8(prefix_suffix_list, &mut i).and_then(|l| read_bytes(prefix_suffix_list, &mut i, l as usize)) {
+			Ok(bytes) => prefixes_suffixes.push(bytes),
+			Err(_) => return Err(SharedDictionaryError::InvalidPrefixSuffixList),
+		}
+	}
+
+	if prefixes_suffixes.len() > 256 {
+		return Err(SharedDictionaryError::InvalidPrefixSuffixList);
+	}
+
+	// NUM_TRANSFORMS, followed by (prefix id, transform type, suffix id) for each transform
+	let n_transforms = match read_u8(encoded, pos) {
+		Ok(n) => n as usize,
+		Err(e) => return Err(e),
+	};
+	let triplets = match read_bytes(encoded, pos, 3 * n_transforms) {
+		Ok(bytes) => bytes,
+		Err(e) => return Err(e),
+	};
+
+	// a 16 bit parameter for each transform, only if there are shift transforms
+	let has_parameters = triplets.chunks(3).any(|t| t[1] == 21 || t[1] == 22);
+	let parameters = if has_parameters {
+		match read_bytes(encoded, pos, 2 * n_transforms) {
+			Ok(bytes) => bytes.chunks(2).map(|p| p[0] as u16 | (p[1] as u16) << 8).collect::<Vec<_>>(),
+			Err(e) => return Err(e),
+		}
+	} else {
+		vec![0; n_transforms]
+	};
+
+	let mut transforms = Vec::with_capacity(n_transforms);
+
+	for (t, &parameter) in triplets.chunks(3).zip(parameters.iter()) {
+		let transform_type = match TransformType::from_code(t[1], parameter) {
+			Some(transform_type) => transform_type,
+			None => return Err(SharedDictionaryError::InvalidTransformType),
+		};
+
+		match (prefixes_suffixes.get(t[0] as usize), prefixes_suffixes.get(t[2] as usize)) {
+			(Some(prefix), Some(suffix)) => transforms.push((prefix.to_vec(), transform_type, suffix.to_vec())),
+			_ => return Err(SharedDictionaryError::InvalidPrefixSuffixId),
+		}
+	}
+
+	Ok(Transforms::new(transforms))
+}
+
+fn parse(encoded: &[u8]) -> Result<SharedDictionary, SharedDictionaryError> {
+	if !encoded.starts_with(&MAGIC) {
+		return Err(SharedDictionaryError::InvalidMagic);
+	}
+
+	let mut pos = MAGIC.len();
+
+	// LZ77_DICTIONARY_LENGTH, followed by the prefix
+	let prefix = match read_varint(encoded, &mut pos).and_then(|l| read_bytes(encoded, &mut pos, l)) {
+		Ok(bytes) => bytes.to_vec(),
+		Err(e) => return Err(e),
+	};
+
+	// NUM_WORD_LISTS, followed by the word lists
+	let n_word_lists = match read_u8(encoded, &mut pos) {
+		Ok(n) => n as usize,
+		Err(e) => return Err(e),
+	};
+	let mut word_lists = Vec::with_capacity(n_word_lists + 1);
+
+	for _ in 0..n_word_lists {
+		match parse_word_list(encoded, &mut pos) {
+			Ok(words) => word_lists.push(words),
+			Err(e) => return Err(e),
+		}
+	}
+
+	word_lists.push(WordList::rfc());
+
+	// NUM_TRANSFORM_LISTS, followed by the transform lists
+	let n_transform_lists = match read_u8(encoded, &mut pos) {
+		Ok(n) => n as usize,
+		Err(e) => return Err(e),
+	};
+	let mut transform_lists = Vec::with_capacity(n_transform_lists + 1);
+
+	for _ in 0..n_transform_lists {
+		match parse_transforms(encoded, &mut pos) {
+			Ok(transforms) => transform_lists.push(transforms),
+			Err(e) => return Err(e),
+		}
+	}
+
+	transform_lists.push(Transforms::rfc());
+
+	// NUM_DICTIONARIES, followed by the word list indices, and the transform list indices,
+	// where an index equal to the number of custom lists stands for the built-in list
+	let n_dictionaries = match read_u8(encoded, &mut pos) {
+		Ok(n) => n as usize,
+		Err(e) => return Err(e),
+	};
+
+	if !(1..=NUM_CONTEXTS).contains(&n_dictionaries) {
+		return Err(SharedDictionaryError::InvalidNumberOfDictionaries);
+	}
+
+	let indices = match read_bytes(encoded, &mut pos, 2 * n_dictionaries) {
+		Ok(bytes) => bytes,
+		Err(e) => return Err(e),
+	};
+
+	if indices[..n_dictionaries].iter().any(|&i| i as usize > n_word_lists)
+		|| indices[n_dictionaries..].iter().any(|&i| i as usize > n_transform_lists) {
+		return Err(SharedDictionaryError::InvalidListIndex);
+	}
+
+	let dictionaries = indices[..n_dictionaries].iter()
+		.zip(indices[n_dictionaries..].iter())
+		.map(|(&w, &t)| (w as usize, t as usize))
+		.collect::<Vec<_>>();
+
+	// the context map, only if there is more than one dictionary
+	let context_map = if n_dictionaries > 1 {
+		match read_bytes(encoded, &mut pos, NUM_CONTEXTS) {
+			Ok(bytes) if bytes.iter().all(|&id| (id as usize) < n_dictionaries) => bytes.to_vec(),
+			Ok(_) => return Err(SharedDictionaryError::InvalidContextMap),
+			Err(e) => return Err(e),
+		}
+	} else {
+		vec![0; NUM_CONTEXTS]
+	};
+
+	if pos < encoded.len() {
+		return Err(SharedDictionaryError::TrailingBytes);
+	}
+
+	Ok(SharedDictionary{
+		prefix,
+		word_lists,
+		transform_lists,
+		dictionaries,
+		context_map,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	/// Returns a serialized dictionary with a custom word list of two words of length 5, and a
+	/// custom transform list, which are selected for literal context 33, i.e. after an "a" in
+	/// context mode LSB6, while the built-in lists are selected for all other contexts.
+	fn serialized_dictionary() -> Vec<u8> {
+		let mut encoded = vec![0x91, 0x00, 0];
+
+		// NUM_WORD_LISTS, size bits for lengths 4 to 31, words
+		encoded.push(1);
+		encoded.extend_from_slice(&[0, 1]);
+		encoded.extend_from_slice(&[0; 26]);
+		encoded.extend_from_slice(b"helloworld");
+
+		// NUM_TRANSFORM_LISTS, prefixes and suffixes "", " ", "!", transforms, parameters
+		encoded.push(1);
+		encoded.extend_from_slice(&[5, 0, 0, 1, b' ', 1, b'!']);
+		encoded.extend_from_slice(&[3, 0, 0, 0, 1, 10, 2, 0, 22, 0]);
+		encoded.extend_from_slice(&[0, 0, 0, 0, 1, 0]);
+
+		// NUM_DICTIONARIES, word list indices, transform list indices, context map
+		encoded.extend_from_slice(&[2, 1, 0, 1, 0]);
+		encoded.extend((0..64).map(|cid| if cid == 33 { 1 } else { 0 }));
+
+		encoded
+	}
+
+	/// Returns a stream that inserts the given literal, if any, followed by a copy of copy_length bytes
+	/// from the given address in the static dictionary, which is expected to produce m_len bytes.
+	fn stream_with_reference(literal: Option<u8>, copy_length: usize, address: usize, m_len: usize) -> Vec<u8> {
+		use ::bitwriter::BitWriter;
+
+		let mut bw = BitWriter::new();
+		let insert_length = if literal.is_some() { 1 } else { 0 };
+
+		// WBITS 16, ISLAST, not ISLASTEMPTY, MNIBBLES 4, MLEN - 1
+		bw.write_bit(false);
+		bw.write_bit(true);
+		bw.write_bit(false);
+		bw.write_u32_to_n_bits(0, 2);
+		bw.write_u32_to_n_bits(m_len as u32 - 1, 16);
+
+		// NBLTYPESL, NBLTYPESI, NBLTYPESD, NPOSTFIX, NDIRECT, context mode LSB6, NTREESL, NTREESD
+		bw.write_u32_to_n_bits(0, 3);
+		bw.write_u32_to_n_bits(0, 6);
+		bw.write_u32_to_n_bits(0, 2);
+		bw.write_u32_to_n_bits(0, 2);
+
+		// distance code, along with its extra bits, for NPOSTFIX 0 and NDIRECT 0
+		let d = address + insert_length + 1 - 1 + 4;
+		let n_extra = 31 - (d as u32).leading_zeros() as usize - 1;
+		let distance_code = 16 + 2 * (n_extra - 1) + ((d >> n_extra) - 2);
+
+		// simple prefix codes with one symbol each, for literals, insert-and-copy lengths, distances
+		bw.write_u32_to_n_bits(1, 2);
+		bw.write_u32_to_n_bits(0, 2);
+		bw.write_u32_to_n_bits(literal.unwrap_or(0) as u32, 8);
+		bw.write_u32_to_n_bits(1, 2);
+		bw.write_u32_to_n_bits(0, 2);
+		bw.write_u32_to_n_bits(128 + (insert_length << 3) as u32 + copy_length as u32 - 2, 10);
+		bw.write_u32_to_n_bits(1, 2);
+		bw.write_u32_to_n_bits(0, 2);
+		bw.write_u32_to_n_bits(distance_code as u32, 6);
+
+		// the command, where symbols of one-symbol codes take up no bits
+		bw.write_u32_to_n_bits((d & ((1 << n_extra) - 1)) as u32, n_extra);
+		bw.write_u8_to_byte_tail(0);
+
+		bw.take_bytes()
+	}
+
+	fn decompress(stream: &[u8], dictionary: &super::SharedDictionary) -> ::std::io::Result<Vec<u8>> {
+		use ::Decompressor;
+		use std::io::Read;
+
+		let mut decompressed = Vec::new();
+
+		Decompressor::with_shared_dictionary(stream, dictionary).read_to_end(&mut decompressed).map(|_| decompressed)
+	}
+
+	#[test]
+	fn should_parse_serialized_dictionary() {
+		use super::SharedDictionary;
+
+		let dictionary = SharedDictionary::from_bytes(&serialized_dictionary()).unwrap();
+
+		assert_eq!(2, dictionary.word_lists.len());
+		assert_eq!(2, dictionary.transform_lists.len());
+		assert_eq!(vec![(1, 1), (0, 0)], dictionary.dictionaries);
+		assert_eq!(3, dictionary.transform_lists[0].len());
+		assert_eq!(1, dictionary.context_map[33]);
+	}
+
+	#[test]
+	fn should_reject_invalid_dictionaries() {
+		use super::SharedDictionary;
+
+		let valid = serialized_dictionary();
+		let mut bad_magic = valid.clone();
+		bad_magic[1] = 1;
+		let mut trailing = valid.clone();
+		trailing.push(0);
+		let mut bad_transform_type = valid.clone();
+		bad_transform_type[52] = 23;
+		let mut bad_context_map = valid.clone();
+		*bad_context_map.last_mut().unwrap() = 2;
+
+		for encoded in &[bad_magic, trailing, bad_transform_type, bad_context_map, valid[..40].to_vec()] {
+			assert!(SharedDictionary::from_bytes(encoded).is_err());
+		}
+	}
+
+	#[test]
+	fn should_decode_custom_words() {
+		use super::SharedDictionary;
+
+		let dictionary = SharedDictionary::from_bytes(&serialized_dictionary()).unwrap();
+
+		// after an "a", the custom lists apply: "world", " Hello!", "ifmmp"
+		assert_eq!(b"aworld".to_vec(), decompress(&stream_with_reference(Some(b'a'), 5, 1, 6), &dictionary).unwrap());
+		assert_eq!(b"a Hello!".to_vec(), decompress(&stream_with_reference(Some(b'a'), 5, 2, 8), &dictionary).unwrap());
+		assert_eq!(b"aifmmp".to_vec(), decompress(&stream_with_reference(Some(b'a'), 5, 4, 6), &dictionary).unwrap());
+		assert!(decompress(&stream_with_reference(Some(b'a'), 5, 6, 6), &dictionary).is_err());
+		assert!(decompress(&stream_with_reference(Some(b'a'), 4, 0, 5), &dictionary).is_err());
+
+		// otherwise, the built-in lists apply
+		let default = SharedDictionary::default();
+		let expected = decompress(&stream_with_reference(Some(b'b'), 4, 0, 5), &default).unwrap();
+
+		assert_eq!(b"btime".to_vec(), expected);
+		assert_eq!(expected, decompress(&stream_with_reference(Some(b'b'), 4, 0, 5), &dictionary).unwrap());
+		assert_eq!(b"time".to_vec(), decompress(&stream_with_reference(None, 4, 0, 4), &dictionary).unwrap());
+	}
+}
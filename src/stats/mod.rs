@@ -0,0 +1,298 @@
+use ::{ Decompressor, State };
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{ Display, Formatter };
+use std::io;
+use std::io::Read;
+
+/// Statistics of a meta-block, which show how a stream spends its bits. Bits are attributed
+/// to the part of the meta-block, that they have been read for; block switch commands count
+/// for the literals, commands or distances that they precede.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MetaBlockStats {
+	/// number of bytes the meta-block decompresses to
+	pub mlen: usize,
+	/// whether the meta-block holds the bytes as they are
+	pub uncompressed: bool,
+	/// number of bytes of metadata, for metadata meta-blocks
+	pub metadata: usize,
+	/// NBLTYPESL, NBLTYPESI and NBLTYPESD
+	pub n_bltypes: [usize; 3],
+	/// NTREESL and NTREESD
+	pub n_trees: [usize; 2],
+	/// context mode of each literal block type
+	pub context_modes: Vec<usize>,
+	/// number of literals that have been inserted
+	pub literals: usize,
+	/// number of backward references
+	pub copies: usize,
+	/// number of references to the static dictionary, by transform id
+	pub dictionary_hits: BTreeMap<usize, usize>,
+	/// number of explicit distance codes, by distance code
+	pub distance_codes: BTreeMap<usize, usize>,
+	/// bits of the meta-block header, including context maps and metadata
+	pub header_bits: usize,
+	/// bits of prefix codes
+	pub prefix_code_bits: usize,
+	/// bits of literals, including uncompressed bytes
+	pub literal_bits: usize,
+	/// bits of insert-and-copy length codes and their extra bits
+	pub command_bits: usize,
+	/// bits of distance codes and their extra bits
+	pub distance_bits: usize,
+}
+
+impl MetaBlockStats {
+	/// Returns the number of bits of the meta-block.
+	pub fn total_bits(&self) -> usize {
+		self.header_bits + self.prefix_code_bits + self.literal_bits + self.command_bits + self.distance_bits
+	}
+
+	/// Returns the statistics as a JSON object.
+	pub fn to_json(&self) -> String {
+		let map = |m: &BTreeMap<usize, usize>| m.iter().map(|(k, v)| format!("\"{}\":{}", k, v)).collect::<Vec<_>>().join(",");
+
+		format!(concat!("{{\"mlen\":{},\"uncompressed\":{},\"metadata\":{},\"n_bltypes\":[{},{},{}],\"n_trees\":[{},{}],",
+			"\"context_modes\":[{}],\"literals\":{},\"copies\":{},\"dictionary_hits\":{{{}}},\"distance_codes\":{{{}}},",
+			"\"header_bits\":{},\"prefix_code_bits\":{},\"literal_bits\":{},\"command_bits\":{},\"distance_bits\":{}}}"),
+			self.mlen, self.uncompressed, self.metadata, self.n_bltypes[0], self.n_bltypes[1], self.n_bltypes[2],
+			self.n_trees[0], self.n_trees[1], self.context_modes.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(","),
+			self.literals, self.copies, map(&self.dictionary_hits), map(&self.distance_codes),
+			self.header_bits, self.prefix_code_bits, self.literal_bits, self.command_bits, self.distance_bits)
+	}
+}
+
+impl Display for MetaBlockStats {
+	fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+		let total = ::std::cmp::max(1, self.total_bits()) as f64;
+		let bits = [("header", self.header_bits), ("prefix codes", self.prefix_code_bits), ("literals", self.literal_bits),
+			("commands", self.command_bits), ("distances", self.distance_bits)];
+
+		let mut text = format!("MLEN = {}", self.mlen);
+
+		if self.uncompressed {
+			text.push_str(", uncompressed");
+		}
+
+		if self.metadata > 0 {
+			text.push_str(&format!(", {} bytes of metadata", self.metadata));
+		}
+
+		text.push_str(&format!("\nNBLTYPES = {:?}, NTREES = {:?}, context modes = {:?}\n", self.n_bltypes, self.n_trees, self.context_modes));
+		text.push_str(&format!("literals = {}, copies = {}, dictionary hits by transform id = {:?}\n", self.literals, self.copies, self.dictionary_hits));
+		text.push_str(&format!("distance codes = {:?}\n", self.distance_codes));
+
+		for &(name, n) in &bits {
+			text.push_str(&format!("{:>14}: {:>10} bits {:>6.2}%\n", name, n, 100.0 * n as f64 / total));
+		}
+
+		fmt.write_str(&text)
+	}
+}
+
+/// Statistics, which a Decompressor collects while it decodes a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Collector {
+	/// position of the first bit that has not been attributed yet
+	pos: usize,
+	meta_blocks: Vec<MetaBlockStats>,
+}
+
+impl Collector {
+	pub(crate) fn new() -> Collector {
+		Collector{
+			pos: 0,
+			meta_blocks: Vec::new(),
+		}
+	}
+}
+
+impl<R: Read> Decompressor<R> {
+	/// Collects statistics for each meta-block, from now on, which stats() returns.
+	pub fn collect_stats(&mut self) {
+		if self.collector.is_none() {
+			let mut collector = Collector::new();
+			collector.pos = self.in_stream.global_bit_pos();
+
+			self.collector = Some(collector);
+		}
+	}
+
+	/// Returns the statistics of the meta-blocks, which have been decoded since
+	/// collect_stats(). The statistics of the current meta-block are incomplete.
+	pub fn stats(&self) -> &[MetaBlockStats] {
+		match self.collector {
+			Some(ref collector) => &collector.meta_blocks,
+			None => &[],
+		}
+	}
+
+	/// Attributes the bits, which the decompressor has read to enter a state, to the current
+	/// meta-block, and counts what the state holds.
+	pub(crate) fn collect_state(&mut self, state: &State) {
+		let dictionary_reference = match *state {
+			State::CopyLiterals(_) => self.dictionary_reference(),
+			_ => None,
+		};
+		let implicit_distance = self.meta_block.distance == Some(0);
+		let end = self.in_stream.global_bit_pos();
+		let collector = self.collector.as_mut().unwrap();
+		let bits = end - collector.pos;
+
+		collector.pos = end;
+
+		if *state == State::HeaderMetaBlockBegin {
+			collector.meta_blocks.push(MetaBlockStats::default());
+		}
+
+		let stats = match collector.meta_blocks.last_mut() {
+			Some(stats) => stats,
+			None => return,
+		};
+
+		match *state {
+			State::PrefixCodeBlockTypesLiterals(_)
+			| State::PrefixCodeBlockTypesInsertAndCopyLengths(_)
+			| State::PrefixCodeBlockTypesDistances(_)
+			| State::PrefixCodeBlockCountsLiterals(_)
+			| State::PrefixCodeBlockCountsInsertAndCopyLengths(_)
+			| State::PrefixCodeBlockCountsDistances(_)
+			| State::PrefixCodesLiterals(_)
+			| State::PrefixCodesInsertAndCopyLengths(_)
+			| State::PrefixCodesDistances(_) => stats.prefix_code_bits += bits,
+			State::MLenLiterals(ref literals) | State::InsertLiterals(ref literals) => {
+				stats.literals += literals.len();
+				stats.literal_bits += bits;
+			},
+			State::InsertAndCopyLength(_) | State::InsertLengthAndCopyLength(_) => stats.command_bits += bits,
+			State::DistanceCode(distance_code) => {
+				if !implicit_distance {
+					*stats.distance_codes.entry(distance_code as usize).or_insert(0) += 1;
+				}
+
+				stats.distance_bits += bits;
+			},
+			State::Distance(_) => stats.distance_bits += bits,
+			State::CopyLiterals(_) => match dictionary_reference {
+				Some((_, transform_id)) => *stats.dictionary_hits.entry(transform_id).or_insert(0) += 1,
+				None => stats.copies += 1,
+			},
+			State::MLen(m_len) => {
+				stats.mlen = m_len as usize;
+				stats.header_bits += bits;
+			},
+			State::IsUncompressed(is_uncompressed) => {
+				stats.uncompressed = is_uncompressed;
+				stats.header_bits += bits;
+			},
+			State::MSkipLen(m_skip_len) => {
+				stats.metadata = m_skip_len as usize;
+				stats.header_bits += bits;
+			},
+			State::NBltypesL(n_bltypes) => {
+				stats.n_bltypes[0] = n_bltypes as usize;
+				stats.header_bits += bits;
+			},
+			State::NBltypesI(n_bltypes) => {
+				stats.n_bltypes[1] = n_bltypes as usize;
+				stats.header_bits += bits;
+			},
+			State::NBltypesD(n_bltypes) => {
+				stats.n_bltypes[2] = n_bltypes as usize;
+				stats.header_bits += bits;
+			},
+			State::NTreesL(n_trees) => {
+				stats.n_trees[0] = n_trees as usize;
+				stats.header_bits += bits;
+			},
+			State::NTreesD(n_trees) => {
+				stats.n_trees[1] = n_trees as usize;
+				stats.header_bits += bits;
+			},
+			State::ContextModesLiterals(ref context_modes) => {
+				stats.context_modes = context_modes.iter().map(|&m| m as usize).collect();
+				stats.header_bits += bits;
+			},
+			_ => stats.header_bits += bits,
+		}
+	}
+}
+
+/// Decodes a stream, and returns the statistics of each of its meta-blocks.
+/// Returns an error, if the stream is invalid.
+///
+/// # Examples
+/// ```
+/// use brotli::stats;
+///
+/// let stats = stats(&[0x0b, 0x00, 0x80, 0x58, 0x03][..]).unwrap();
+///
+/// assert_eq!(2, stats.len());
+/// assert_eq!(1, stats[0].mlen);
+/// assert!(stats[0].uncompressed);
+/// assert_eq!(8, stats[0].literal_bits);
+/// ```
+pub fn stats<R: Read>(r: R) -> io::Result<Vec<MetaBlockStats>> {
+	let mut decompressor = Decompressor::new(r);
+	decompressor.collect_stats();
+
+	match io::copy(&mut decompressor, &mut io::sink()) {
+		Ok(_) => Ok(decompressor.collector.take().unwrap().meta_blocks),
+		Err(e) => Err(e),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_account_for_every_bit_of_meta_blocks() {
+		use super::stats;
+		use std::fs::File;
+		use std::io::Read;
+
+		let mut data = Vec::new();
+		File::open("data/alice29.txt.compressed").unwrap().read_to_end(&mut data).unwrap();
+
+		let stats = stats(&data[..]).unwrap();
+		let total = stats.iter().fold(0, |acc, s| acc + s.total_bits());
+
+		assert_eq!(152089, stats.iter().fold(0, |acc, s| acc + s.mlen));
+		assert!(stats[0].n_bltypes[0] >= 2);
+		// WBITS, and the trailing bits after the last meta-block
+		assert!(total + 4 <= data.len() * 8 && data.len() * 8 < total + 4 + 8);
+	}
+
+	#[test]
+	fn should_count_commands_and_dictionary_hits() {
+		use super::stats;
+		use std::fs::File;
+
+		let stats = stats(File::open("data/quickfox_repeated.compressed").unwrap()).unwrap();
+
+		assert_eq!(1, stats.len());
+		assert_eq!(176128, stats[0].mlen);
+		assert_eq!(4 + 1 + 5 + 2 + 8, stats[0].literals);
+		assert_eq!(1, stats[0].copies);
+		assert_eq!(Some(&4), stats[0].dictionary_hits.get(&0));
+		assert_eq!(5, stats[0].distance_codes.values().sum::<usize>());
+		assert_eq!(vec![2], stats[0].context_modes);
+		assert_eq!([5, 1], stats[0].n_trees);
+	}
+
+	#[test]
+	fn should_serialize_to_json() {
+		use super::MetaBlockStats;
+
+		let mut stats = MetaBlockStats{
+			mlen: 3,
+			context_modes: vec![2, 0],
+			..MetaBlockStats::default()
+		};
+		stats.dictionary_hits.insert(0, 2);
+
+		assert_eq!(concat!("{\"mlen\":3,\"uncompressed\":false,\"metadata\":0,\"n_bltypes\":[0,0,0],\"n_trees\":[0,0],",
+			"\"context_modes\":[2,0],\"literals\":0,\"copies\":0,\"dictionary_hits\":{\"0\":2},\"distance_codes\":{},",
+			"\"header_bits\":0,\"prefix_code_bits\":0,\"literal_bits\":0,\"command_bits\":0,\"distance_bits\":0}"), stats.to_json());
+	}
+}
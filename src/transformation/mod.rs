@@ -1,4 +1,197 @@
-use std::cmp::{ max, min };
+use std::cmp::min;
+
+/// The operation of a transform on a dictionary word, which happens between
+/// prepending the prefix and appending the suffix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransformType {
+	Identity,
+	/// omits the last n bytes, for n from 1 to 9
+	OmitLast(usize),
+	UppercaseFirst,
+	UppercaseAll,
+	/// omits the first n bytes, for n from 1 to 9
+	OmitFirst(usize),
+	/// adds a parameter to the scalar value of the first UTF-8 code point
+	ShiftFirst(u16),
+	/// adds a parameter to the scalar values of all UTF-8 code points
+	ShiftAll(u16),
+}
+
+impl TransformType {
+	/// Returns the transform type for its code in a serialized dictionary, along with its parameter,
+	/// which only the shift transforms make use of.
+	pub fn from_code(code: u8, parameter: u16) -> Option<TransformType> {
+		match code {
+			0 => Some(TransformType::Identity),
+			1..=9 => Some(TransformType::OmitLast(code as usize)),
+			10 => Some(TransformType::UppercaseFirst),
+			11 => Some(TransformType::UppercaseAll),
+			12..=20 => Some(TransformType::OmitFirst(code as usize - 11)),
+			21 => Some(TransformType::ShiftFirst(parameter)),
+			22 => Some(TransformType::ShiftAll(parameter)),
+			_ => None,
+		}
+	}
+}
+
+/// The transforms of RFC 7932, Appendix B, as (prefix, transform type, suffix).
+const RFC_TRANSFORMS: [(&[u8], TransformType, &[u8]); 121] = [
+	(b"", TransformType::Identity, b""),
+	(b"", TransformType::Identity, b" "),
+	(b" ", TransformType::Identity, b" "),
+	(b"", TransformType::OmitFirst(1), b""),
+	(b"", TransformType::UppercaseFirst, b" "),
+	(b"", TransformType::Identity, b" the "),
+	(b" ", TransformType::Identity, b""),
+	(b"s ", TransformType::Identity, b" "),
+	(b"", TransformType::Identity, b" of "),
+	(b"", TransformType::UppercaseFirst, b""),
+	(b"", TransformType::Identity, b" and "),
+	(b"", TransformType::OmitFirst(2), b""),
+	(b"", TransformType::OmitLast(1), b""),
+	(b", ", TransformType::Identity, b" "),
+	(b"", TransformType::Identity, b", "),
+	(b" ", TransformType::UppercaseFirst, b" "),
+	(b"", TransformType::Identity, b" in "),
+	(b"", TransformType::Identity, b" to "),
+	(b"e ", TransformType::Identity, b" "),
+	(b"", TransformType::Identity, b"\""),
+	(b"", TransformType::Identity, b"."),
+	(b"", TransformType::Identity, b"\">"),
+	(b"", TransformType::Identity, b"\n"),
+	(b"", TransformType::OmitLast(3), b""),
+	(b"", TransformType::Identity, b"]"),
+	(b"", TransformType::Identity, b" for "),
+	(b"", TransformType::OmitFirst(3), b""),
+	(b"", TransformType::OmitLast(2), b""),
+	(b"", TransformType::Identity, b" a "),
+	(b"", TransformType::Identity, b" that "),
+	(b" ", TransformType::UppercaseFirst, b""),
+	(b"", TransformType::Identity, b". "),
+	(b".", TransformType::Identity, b""),
+	(b" ", TransformType::Identity, b", "),
+	(b"", TransformType::OmitFirst(4), b""),
+	(b"", TransformType::Identity, b" with "),
+	(b"", TransformType::Identity, b"'"),
+	(b"", TransformType::Identity, b" from "),
+	(b"", TransformType::Identity, b" by "),
+	(b"", TransformType::OmitFirst(5), b""),
+	(b"", TransformType::OmitFirst(6), b""),
+	(b" the ", TransformType::Identity, b""),
+	(b"", TransformType::OmitLast(4), b""),
+	(b"", TransformType::Identity, b". The "),
+	(b"", TransformType::UppercaseAll, b""),
+	(b"", TransformType::Identity, b" on "),
+	(b"", TransformType::Identity, b" as "),
+	(b"", TransformType::Identity, b" is "),
+	(b"", TransformType::OmitLast(7), b""),
+	(b"", TransformType::OmitLast(1), b"ing "),
+	(b"", TransformType::Identity, b"\n\t"),
+	(b"", TransformType::Identity, b":"),
+	(b" ", TransformType::Identity, b". "),
+	(b"", TransformType::Identity, b"ed "),
+	(b"", TransformType::OmitFirst(9), b""),
+	(b"", TransformType::OmitFirst(7), b""),
+	(b"", TransformType::OmitLast(6), b""),
+	(b"", TransformType::Identity, b"("),
+	(b"", TransformType::UppercaseFirst, b", "),
+	(b"", TransformType::OmitLast(8), b""),
+	(b"", TransformType::Identity, b" at "),
+	(b"", TransformType::Identity, b"ly "),
+	(b" the ", TransformType::Identity, b" of "),
+	(b"", TransformType::OmitLast(5), b""),
+	(b"", TransformType::OmitLast(9), b""),
+	(b" ", TransformType::UppercaseFirst, b", "),
+	(b"", TransformType::UppercaseFirst, b"\""),
+	(b".", TransformType::Identity, b"("),
+	(b"", TransformType::UppercaseAll, b" "),
+	(b"", TransformType::UppercaseFirst, b"\">"),
+	(b"", TransformType::Identity, b"=\""),
+	(b" ", TransformType::Identity, b"."),
+	(b".com/", TransformType::Identity, b""),
+	(b" the ", TransformType::Identity, b" of the "),
+	(b"", TransformType::UppercaseFirst, b"'"),
+	(b"", TransformType::Identity, b". This "),
+	(b"", TransformType::Identity, b","),
+	(b".", TransformType::Identity, b" "),
+	(b"", TransformType::UppercaseFirst, b"("),
+	(b"", TransformType::UppercaseFirst, b"."),
+	(b"", TransformType::Identity, b" not "),
+	(b" ", TransformType::Identity, b"=\""),
+	(b"", TransformType::Identity, b"er "),
+	(b" ", TransformType::UppercaseAll, b" "),
+	(b"", TransformType::Identity, b"al "),
+	(b" ", TransformType::UppercaseAll, b""),
+	(b"", TransformType::Identity, b"='"),
+	(b"", TransformType::UppercaseAll, b"\""),
+	(b"", TransformType::UppercaseFirst, b". "),
+	(b" ", TransformType::Identity, b"("),
+	(b"", TransformType::Identity, b"ful "),
+	(b" ", TransformType::UppercaseFirst, b". "),
+	(b"", TransformType::Identity, b"ive "),
+	(b"", TransformType::Identity, b"less "),
+	(b"", TransformType::UppercaseAll, b"'"),
+	(b"", TransformType::Identity, b"est "),
+	(b" ", TransformType::UppercaseFirst, b"."),
+	(b"", TransformType::UppercaseAll, b"\">"),
+	(b" ", TransformType::Identity, b"='"),
+	(b"", TransformType::UppercaseFirst, b","),
+	(b"", TransformType::Identity, b"ize "),
+	(b"", TransformType::UppercaseAll, b"."),
+	(b"\xc2\xa0", TransformType::Identity, b""),
+	(b" ", TransformType::Identity, b","),
+	(b"", TransformType::UppercaseFirst, b"=\""),
+	(b"", TransformType::UppercaseAll, b"=\""),
+	(b"", TransformType::Identity, b"ous "),
+	(b"", TransformType::UppercaseAll, b", "),
+	(b"", TransformType::UppercaseFirst, b"='"),
+	(b" ", TransformType::UppercaseFirst, b","),
+	(b" ", TransformType::UppercaseAll, b"=\""),
+	(b" ", TransformType::UppercaseAll, b", "),
+	(b"", TransformType::UppercaseAll, b","),
+	(b"", TransformType::UppercaseAll, b"("),
+	(b"", TransformType::UppercaseAll, b". "),
+	(b" ", TransformType::UppercaseAll, b"."),
+	(b"", TransformType::UppercaseAll, b"='"),
+	(b" ", TransformType::UppercaseAll, b". "),
+	(b" ", TransformType::UppercaseFirst, b"=\""),
+	(b" ", TransformType::UppercaseAll, b"='"),
+	(b" ", TransformType::UppercaseFirst, b"='"),
+];
+
+/// A list of transforms, each of which turns a dictionary word into a sequence of bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transforms {
+	transforms: Vec<(Vec<u8>, TransformType, Vec<u8>)>,
+}
+
+impl Transforms {
+	/// Creates Transforms from a list of (prefix, transform type, suffix).
+	pub fn new(transforms: Vec<(Vec<u8>, TransformType, Vec<u8>)>) -> Transforms {
+		Transforms{
+			transforms,
+		}
+	}
+
+	/// Returns the 121 transforms of RFC 7932.
+	pub fn rfc() -> Transforms {
+		Transforms::new(RFC_TRANSFORMS.iter().map(|&(prefix, transform_type, suffix)| {
+			(prefix.to_vec(), transform_type, suffix.to_vec())
+		}).collect())
+	}
+
+	/// Returns the number of transforms.
+	pub fn len(&self) -> usize {
+		self.transforms.len()
+	}
+
+	/// Applies the transform with the given id to a dictionary word.
+	pub fn transform(&self, id: usize, base_word: &[u8]) -> Vec<u8> {
+		let (ref prefix, transform_type, ref suffix) = self.transforms[id];
+
+		transform(prefix, transform_type, suffix, base_word)
+	}
+}
 
 fn uppercase_all(base_word: &[u8]) -> Vec<u8> {
 	let l = base_word.len();
@@ -7,22 +200,22 @@ fn uppercase_all(base_word: &[u8]) -> Vec<u8> {
 
 	while i < l {
 		match base_word[i] {
-			0...96|123...191 => {
+			0..=96|123..=191 => {
 				v.push(base_word[i]);
 				i += 1;
 			},
-			97...122 => {
+			97..=122 => {
 				v.push(base_word[i] ^ 32);
 				i += 1;
 			},
-			192...223 => {
+			192..=223 => {
 				v.push(base_word[i]);
 				if i + 1 < l {
 					v.push(base_word[i + 1] ^ 32);
 				}
 				i += 2;
 			},
-			224...255 => {
+			224..=255 => {
 				v.push(base_word[i]);
 				if i + 1 < l {
 					v.push(base_word[i + 1]);
@@ -30,9 +223,8 @@ fn uppercase_all(base_word: &[u8]) -> Vec<u8> {
 				if i + 2 < l {
 					v.push(base_word[i + 2] ^ 5);
 				}
-				i = i + 3;
+				i += 3;
 			},
-			_ => unreachable!(), // confirmed unreachable, all byte values covered
 		}
 	}
 
@@ -47,25 +239,24 @@ fn uppercase_first(base_word: &[u8]) -> Vec<u8> {
 	}
 
 	let mut v = Vec::with_capacity(l);
-	let i;
 
-	match base_word[0] {
-		1...96|123...191 => {
+	let i = match base_word[0] {
+		0..=96|123..=191 => {
 			v.push(base_word[0]);
-			i = 1;
+			1
 		},
-		97...122 => {
+		97..=122 => {
 			v.push(base_word[0] ^ 32);
-			i = 1;
+			1
 		},
-		192...223 => {
+		192..=223 => {
 			v.push(base_word[0]);
 			if 1 < l {
 				v.push(base_word[1] ^ 32);
 			}
-			i = 2;
+			2
 		},
-		224...255 => {
+		224..=255 => {
 			v.push(base_word[0]);
 			if 1 < l {
 				v.push(base_word[1]);
@@ -73,144 +264,106 @@ fn uppercase_first(base_word: &[u8]) -> Vec<u8> {
 			if 2 < l {
 				v.push(base_word[2] ^ 5);
 			}
-			i = 3;
+			3
 		},
-		_ => unreachable!(), // confirmed unreachable, all byte values covered
-	}
+	};
 
-	[v, Vec::from(&base_word[i..])].concat()
+	[v, Vec::from(&base_word[min(i, l)..])].concat()
 }
 
-pub fn transformation(id: usize, base_word: &[u8]) -> Vec<u8> {
-	match id {
-		  0 => Vec::from(base_word),
-		  1 => [Vec::from(base_word), vec![0x20]].concat(),
-		  2 => [vec![0x20], Vec::from(base_word), vec![0x20]].concat(),
-		  3 => Vec::from(&base_word[min(1, base_word.len() - 1)..]),
-		  4 => [uppercase_first(base_word), vec![0x20]].concat(),
-		  5 => [Vec::from(base_word), vec![0x20, 0x74, 0x68, 0x65, 0x20]].concat(),
-		  6 => [vec![0x20], Vec::from(base_word)].concat(),
-		  7 => [vec![0x73, 0x20], Vec::from(base_word), vec![0x20]].concat(),
-		  8 => [Vec::from(base_word), vec![0x20, 0x6f, 0x66, 0x20]].concat(),
-		  9 => uppercase_first(base_word),
-		 10 => [Vec::from(base_word), vec![0x20, 0x61, 0x6e, 0x64, 0x20]].concat(),
-		 11 => Vec::from(&base_word[min(2, base_word.len() - 1)..]),
-		 12 => Vec::from(&base_word[..max(1, base_word.len()) - 1]),
-		 13 => [vec![0x2c, 0x20], Vec::from(base_word), vec![0x20]].concat(),
-		 14 => [Vec::from(base_word), vec![0x2c, 0x20]].concat(),
-		 15 => [vec![0x20], uppercase_first(base_word), vec![0x20]].concat(),
-		 16 => [Vec::from(base_word), vec![0x20, 0x69, 0x6e, 0x20]].concat(),
-		 17 => [Vec::from(base_word), vec![0x20, 0x74, 0x6f, 0x20]].concat(),
-		 18 => [vec![0x65, 0x20], Vec::from(base_word), vec![0x20]].concat(),
-		 19 => [Vec::from(base_word), vec![0x22]].concat(),
-		 20 => [Vec::from(base_word), vec![0x2e]].concat(),
-		 21 => [Vec::from(base_word), vec![0x22, 0x3e]].concat(),
-		 22 => [Vec::from(base_word), vec![0x0a]].concat(),
-		 23 => Vec::from(&base_word[..max(3, base_word.len()) - 3]),
-		 24 => [Vec::from(base_word), vec![0x5d]].concat(),
-		 25 => [Vec::from(base_word), vec![0x20, 0x66, 0x6f, 0x72, 0x20]].concat(),
-		 26 => Vec::from(&base_word[min(3, base_word.len() - 1)..]),
-		 27 => Vec::from(&base_word[..max(2, base_word.len()) - 2]),
-		 28 => [Vec::from(base_word), vec![0x20, 0x61, 0x20]].concat(),
-		 29 => [Vec::from(base_word), vec![0x20, 0x74, 0x68, 0x61, 0x74, 0x20]].concat(),
-		 30 => [vec![0x20], uppercase_first(base_word)].concat(),
-		 31 => [Vec::from(base_word), vec![0x2e, 0x20]].concat(),
-		 32 => [vec![0x2e], Vec::from(base_word)].concat(),
-		 33 => [vec![0x20], Vec::from(base_word), vec![0x2c, 0x20]].concat(),
-		 34 => Vec::from(&base_word[min(4, base_word.len() - 1)..]),
-		 35 => [Vec::from(base_word), vec![0x20, 0x77, 0x69, 0x74, 0x68, 0x20]].concat(),
-		 36 => [Vec::from(base_word), vec![0x27]].concat(),
-		 37 => [Vec::from(base_word), vec![0x20, 0x66, 0x72, 0x6f, 0x6d, 0x20]].concat(),
-		 38 => [Vec::from(base_word), vec![0x20, 0x62, 0x79, 0x20]].concat(),
-		 39 => Vec::from(&base_word[min(5, base_word.len() - 1)..]),
-		 40 => Vec::from(&base_word[min(6, base_word.len() - 1)..]),
-		 41 => [vec![0x20, 0x74, 0x68, 0x65, 0x20], Vec::from(base_word)].concat(),
-		 42 => Vec::from(&base_word[..max(4, base_word.len()) - 4]),
-		 43 => [Vec::from(base_word), vec![0x2e, 0x20, 0x54, 0x68, 0x65, 0x20]].concat(),
-		 44 => uppercase_all(base_word),
-		 45 => [Vec::from(base_word), vec![0x20, 0x6f, 0x6e, 0x20]].concat(),
-		 46 => [Vec::from(base_word), vec![0x20, 0x61, 0x73, 0x20]].concat(),
-		 47 => [Vec::from(base_word), vec![0x20, 0x69, 0x73, 0x20]].concat(),
-		 48 => Vec::from(&base_word[..max(7, base_word.len()) - 7]),
-		 49 => [Vec::from(&base_word[..max(1, base_word.len()) - 1]), vec![0x69, 0x6e, 0x67, 0x20]].concat(),
-		 50 => [Vec::from(base_word), vec![0x0a, 0x09]].concat(),
-		 51 => [Vec::from(base_word), vec![0x3a]].concat(),
-		 52 => [vec![0x20], Vec::from(base_word), vec![0x2e, 0x20]].concat(),
-		 53 => [Vec::from(base_word), vec![0x65, 0x64, 0x20]].concat(),
-		 54 => Vec::from(&base_word[min(9, base_word.len() - 1)..]),
-		 55 => Vec::from(&base_word[min(7, base_word.len() - 1)..]),
-		 56 => Vec::from(&base_word[..max(6, base_word.len()) - 6]),
-		 57 => [Vec::from(base_word), vec![0x28]].concat(),
-		 58 => [uppercase_first(base_word), vec![0x2c, 0x20]].concat(),
-		 59 => Vec::from(&base_word[..max(8, base_word.len()) - 8]),
-		 60 => [Vec::from(base_word), vec![0x20, 0x61, 0x74, 0x20]].concat(),
-		 61 => [Vec::from(base_word), vec![0x6c, 0x79, 0x20]].concat(),
-		 62 => [vec![0x20, 0x74, 0x68, 0x65, 0x20], Vec::from(base_word), vec![0x20, 0x6f, 0x66, 0x20]].concat(),
-		 63 => Vec::from(&base_word[..max(5, base_word.len()) - 5]),
-		 64 => Vec::from(&base_word[..max(base_word.len(), 9) - 9]),
-		 65 => [vec![0x20], uppercase_first(base_word), vec![0x2c, 0x20]].concat(),
-		 66 => [uppercase_first(base_word), vec![0x22]].concat(),
-		 67 => [vec![0x2e], Vec::from(base_word), vec![0x28]].concat(),
-		 68 => [uppercase_all(base_word), vec![0x20]].concat(),
-		 69 => [uppercase_first(base_word), vec![0x22, 0x3e]].concat(),
-		 70 => [Vec::from(base_word), vec![0x3d, 0x22]].concat(),
-		 71 => [vec![0x20], Vec::from(base_word), vec![0x2e]].concat(),
-		 72 => [vec![0x2e, 0x63, 0x6f, 0x6d, 0x2f], Vec::from(base_word)].concat(),
-		 73 => [vec![0x20, 0x74, 0x68, 0x65, 0x20], Vec::from(base_word), vec![0x20, 0x6f, 0x66, 0x20, 0x74, 0x68, 0x65, 0x20]].concat(),
-		 74 => [uppercase_first(base_word), vec![0x27]].concat(),
-		 75 => [Vec::from(base_word), vec![0x2e, 0x20, 0x54, 0x68, 0x69, 0x73, 0x20]].concat(),
-		 76 => [Vec::from(base_word), vec![0x2c]].concat(),
-		 77 => [vec![0x2e], Vec::from(base_word), vec![0x20]].concat(),
-		 78 => [uppercase_first(base_word), vec![0x28]].concat(),
-		 79 => [uppercase_first(base_word), vec![0x2e]].concat(),
-		 80 => [Vec::from(base_word), vec![0x20, 0x6e, 0x6f, 0x74, 0x20]].concat(),
-		 81 => [vec![0x20], Vec::from(base_word), vec![0x3d, 0x22]].concat(),
-		 82 => [Vec::from(base_word), vec![0x65, 0x72, 0x20]].concat(),
-		 83 => [vec![0x20], uppercase_all(base_word), vec![0x20]].concat(),
-		 84 => [Vec::from(base_word), vec![0x61, 0x6c, 0x20]].concat(),
-		 85 => [vec![0x20], uppercase_all(base_word)].concat(),
-		 86 => [Vec::from(base_word), vec![0x3d, 0x27]].concat(),
-		 87 => [uppercase_all(base_word), vec![0x22]].concat(),
-		 88 => [uppercase_first(base_word), vec![0x2e, 0x20]].concat(),
-		 89 => [vec![0x20], Vec::from(base_word), vec![0x28]].concat(),
-		 90 => [Vec::from(base_word), vec![0x66, 0x75, 0x6c, 0x20]].concat(),
-		 91 => [vec![0x20], uppercase_first(base_word), vec![0x2e, 0x20]].concat(),
-		 92 => [Vec::from(base_word), vec![0x69, 0x76, 0x65, 0x20]].concat(),
-		 93 => [Vec::from(base_word), vec![0x6c, 0x65, 0x73, 0x73, 0x20]].concat(),
-		 94 => [uppercase_all(base_word), vec![0x27]].concat(),
-		 95 => [Vec::from(base_word), vec![0x65, 0x73, 0x74, 0x20]].concat(),
-		 96 => [vec![0x20], uppercase_first(base_word), vec![0x2e]].concat(),
-		 97 => [uppercase_all(base_word), vec![0x22, 0x3e]].concat(),
-		 98 => [vec![0x20], Vec::from(base_word), vec![0x3d, 0x27]].concat(),
-		 99 => [uppercase_first(base_word), vec![0x2c]].concat(),
-		100 => [Vec::from(base_word), vec![0x69, 0x7a, 0x65, 0x20]].concat(),
-		101 => [uppercase_all(base_word), vec![0x2e]].concat(),
-		102 => [vec![0xc2, 0xa0], Vec::from(base_word)].concat(),
-		103 => [vec![0x20], Vec::from(base_word), vec![0x2c]].concat(),
-		104 => [uppercase_first(base_word), vec![0x3d, 0x22]].concat(),
-		105 => [uppercase_all(base_word), vec![0x3d, 0x22]].concat(),
-		106 => [Vec::from(base_word), vec![0x6f, 0x75, 0x73, 0x20]].concat(),
-		107 => [uppercase_all(base_word), vec![0x2c, 0x20]].concat(),
-		108 => [uppercase_first(base_word), vec![0x3d, 0x27]].concat(),
-		109 => [vec![0x20], uppercase_first(base_word), vec![0x2c]].concat(),
-		110 => [vec![0x20], uppercase_all(base_word), vec![0x3d, 0x22]].concat(),
-		111 => [vec![0x20], uppercase_all(base_word), vec![0x2c, 0x20]].concat(),
-		112 => [uppercase_all(base_word), vec![0x2c]].concat(),
-		113 => [uppercase_all(base_word), vec![0x28]].concat(),
-		114 => [uppercase_all(base_word), vec![0x2e, 0x20]].concat(),
-		115 => [vec![0x20], uppercase_all(base_word), vec![0x2e]].concat(),
-		116 => [uppercase_all(base_word), vec![0x3d, 0x27]].concat(),
-		117 => [vec![0x20], uppercase_all(base_word), vec![0x2e, 0x20]].concat(),
-		118 => [vec![0x20], uppercase_first(base_word), vec![0x3d, 0x22]].concat(),
-		119 => [vec![0x20], uppercase_all(base_word), vec![0x3d, 0x27]].concat(),
-		120 => [vec![0x20], uppercase_first(base_word), vec![0x3d, 0x27]].concat(),
-		_ => unreachable!(), // confirmed unreachable, transform_id is checked prior to call
+/// Adds a parameter to the scalar value of the UTF-8 code point at the start of word,
+/// in place, and returns the number of bytes the code point takes up.
+/// The parameter is a 16 bit two's complement of a 24 bit value, as in the shared Brotli
+/// dictionary format, so that 0x8000 and above subtract from the scalar value.
+fn shift(word: &mut [u8], parameter: u16) -> usize {
+	let l = word.len();
+	let mut scalar = (parameter as u32 & 0x7fff).wrapping_add(0x1000000 - (parameter as u32 & 0x8000));
+
+	match word[0] {
+		0x00..=0x7f => {
+			scalar = scalar.wrapping_add(word[0] as u32);
+			word[0] = (scalar & 0x7f) as u8;
+
+			1
+		},
+		0x80..=0xbf => 1,
+		0xc0..=0xdf => {
+			if l < 2 {
+				return 1;
+			}
+
+			scalar = scalar.wrapping_add((word[1] as u32 & 0x3f) | (word[0] as u32 & 0x1f) << 6);
+			word[0] = (0xc0 | (scalar >> 6) & 0x1f) as u8;
+			word[1] = (word[1] as u32 & 0xc0 | scalar & 0x3f) as u8;
+
+			2
+		},
+		0xe0..=0xef => {
+			if l < 3 {
+				return l;
+			}
+
+			scalar = scalar.wrapping_add((word[2] as u32 & 0x3f) | (word[1] as u32 & 0x3f) << 6 | (word[0] as u32 & 0x0f) << 12);
+			word[0] = (0xe0 | (scalar >> 12) & 0x0f) as u8;
+			word[1] = (word[1] as u32 & 0xc0 | (scalar >> 6) & 0x3f) as u8;
+			word[2] = (word[2] as u32 & 0xc0 | scalar & 0x3f) as u8;
+
+			3
+		},
+		0xf0..=0xf7 => {
+			if l < 4 {
+				return l;
+			}
+
+			scalar = scalar.wrapping_add((word[3] as u32 & 0x3f) | (word[2] as u32 & 0x3f) << 6 | (word[1] as u32 & 0x3f) << 12 | (word[0] as u32 & 0x07) << 18);
+			word[0] = (0xf0 | (scalar >> 18) & 0x07) as u8;
+			word[1] = (word[1] as u32 & 0xc0 | (scalar >> 12) & 0x3f) as u8;
+			word[2] = (word[2] as u32 & 0xc0 | (scalar >> 6) & 0x3f) as u8;
+			word[3] = (word[3] as u32 & 0xc0 | scalar & 0x3f) as u8;
+
+			4
+		},
+		_ => 1,
 	}
 }
 
+fn transform(prefix: &[u8], transform_type: TransformType, suffix: &[u8], base_word: &[u8]) -> Vec<u8> {
+	let l = base_word.len();
+	let word = match transform_type {
+		TransformType::Identity => Vec::from(base_word),
+		TransformType::OmitLast(n) => Vec::from(&base_word[..l - min(n, l)]),
+		TransformType::UppercaseFirst => uppercase_first(base_word),
+		TransformType::UppercaseAll => uppercase_all(base_word),
+		TransformType::OmitFirst(n) => Vec::from(&base_word[min(n, l)..]),
+		TransformType::ShiftFirst(parameter) => {
+			let mut word = Vec::from(base_word);
+			if l > 0 {
+				shift(&mut word, parameter);
+			}
+
+			word
+		},
+		TransformType::ShiftAll(parameter) => {
+			let mut word = Vec::from(base_word);
+			let mut i = 0;
+			while i < l {
+				i += shift(&mut word[i..], parameter);
+			}
+
+			word
+		},
+	};
+
+	[prefix, &word[..], suffix].concat()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::transformation;
+	use super::Transforms;
+
+	fn transformation(id: usize, base_word: &[u8]) -> Vec<u8> {
+		Transforms::rfc().transform(id, base_word)
+	}
 
 	#[test]
 	fn should_transform_0 () {
@@ -1299,4 +1452,22 @@ mod tests {
 
 		assert_eq!(String::from_utf8(transformation(120, &base_word)).unwrap(), String::from(expected));
 	}
+
+	#[test]
+	fn should_omit_whole_short_word () {
+		assert_eq!(Vec::<u8>::new(), transformation(54, b"time"));
+	}
+
+	#[test]
+	fn should_shift_code_points () {
+		use super::TransformType;
+
+		let transforms = Transforms::new(vec![
+			(Vec::new(), TransformType::ShiftAll(1), Vec::new()),
+			(Vec::new(), TransformType::ShiftFirst(0xffff), Vec::new()),
+		]);
+
+		assert_eq!(String::from("bê₭"), String::from_utf8(transforms.transform(0, "aé€".as_bytes())).unwrap());
+		assert_eq!(String::from("aé€"), String::from_utf8(transforms.transform(1, "bé€".as_bytes())).unwrap());
+	}
 }
@@ -0,0 +1,100 @@
+use ::{ Decompressor, State };
+
+use std::io;
+use std::io::Read;
+
+/// Properties of a valid stream, as validate() returns them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamInfo {
+	/// number of bytes the stream decompresses to
+	pub decompressed_size: u64,
+	/// number of bytes of the stream
+	pub compressed_size: u64,
+	/// size of the sliding window, from WBITS
+	pub window_size: usize,
+}
+
+/// Checks a stream, as Decompressor would decompress it, with all of its syntax checks: fill
+/// bits and trailing bits, backward distances, references to the static dictionary with their
+/// transform ids, and the end of the stream after the last meta-block. The decoded bytes are
+/// counted, but not kept, except for the sliding window, which backward references, and the
+/// context of literals, depend on. Returns an error with kind InvalidData, which carries a
+/// StreamError, if the stream is invalid.
+///
+/// # Examples
+/// ```
+/// use brotli::validate;
+///
+/// let info = validate(&[0x0b, 0x00, 0x80, 0x58, 0x03][..]).unwrap();
+///
+/// assert_eq!(1, info.decompressed_size);
+/// assert_eq!(5, info.compressed_size);
+///
+/// assert!(validate(&[0x0b, 0x00, 0x80, 0x58, 0x0b][..]).is_err());
+/// ```
+pub fn validate<R: Read>(r: R) -> io::Result<StreamInfo> {
+	let mut decompressor = Decompressor::new(r);
+	decompressor.discard_output = true;
+
+	// Without output, decompress() returns after each command, and at the end of the stream,
+	// which it has reached, only if it is still in its final state.
+	loop {
+		match decompressor.decompress(&mut []) {
+			Ok(_) if decompressor.state == State::StreamEnd => break,
+			Ok(_) => {},
+			Err(e) => return Err(decompressor.stream_error(e)),
+		}
+	}
+
+	Ok(StreamInfo{
+		decompressed_size: decompressor.count_output as u64,
+		compressed_size: (decompressor.in_stream.global_bit_pos() / 8) as u64,
+		window_size: decompressor.header.window_size.unwrap(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn should_return_decompressed_size() {
+		use super::validate;
+		use std::fs::File;
+
+		for &(name, size) in &[("data/empty.compressed", 0), ("data/alice29.txt.compressed", 152089), ("data/quickfox_repeated.compressed", 176128)] {
+			let info = validate(File::open(name).unwrap()).unwrap();
+
+			assert_eq!(size, info.decompressed_size);
+			assert_eq!(File::open(name).unwrap().metadata().unwrap().len(), info.compressed_size);
+		}
+	}
+
+	#[test]
+	fn should_reject_what_decompressor_rejects() {
+		use super::validate;
+		use ::Decompressor;
+		use std::fs::File;
+		use std::io::Read;
+
+		let mut data = Vec::new();
+		File::open("data/alice29.txt.compressed").unwrap().read_to_end(&mut data).unwrap();
+
+		let truncated = &data[..(data.len() - 1)];
+		let mut trailing = data.clone();
+		trailing.push(0);
+
+		for stream in &[truncated, &trailing[..]] {
+			assert!(validate(*stream).is_err());
+			assert!(Decompressor::new(*stream).read_to_end(&mut Vec::new()).is_err());
+		}
+
+		let mut corrupt = data.clone();
+
+		for i in 0..64 {
+			corrupt[20 + i * 700] ^= 0x10;
+
+			let expected = Decompressor::new(&corrupt[..]).read_to_end(&mut Vec::new()).map_err(|e| e.to_string());
+
+			assert_eq!(expected, validate(&corrupt[..]).map(|info| info.decompressed_size as usize).map_err(|e| e.to_string()));
+		}
+	}
+}
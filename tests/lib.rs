@@ -604,6 +604,418 @@ fn should_reject_afl_01() {
 	}
 }
 
+fn compress_and_decompress(input: &[u8]) -> Vec<u8> {
+	use std::io::{ Read, Write };
+	use brotli::{ Compressor, Decompressor };
+
+	let mut compressor = Compressor::new(Vec::new());
+	compressor.write_all(input).unwrap();
+	let brotli_stream = compressor.finish().unwrap();
+
+	let mut decompressed = Vec::new();
+	Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed).unwrap();
+
+	decompressed
+}
+
+#[test]
+/// Compressor: round trip of an empty input
+fn should_compress_empty_input() {
+	assert_eq!(Vec::<u8>::new(), compress_and_decompress(&[]));
+}
+
+#[test]
+/// Compressor: round trip of a single byte
+fn should_compress_x() {
+	assert_eq!(b"X".to_vec(), compress_and_decompress(b"X"));
+}
+
+#[test]
+/// Compressor: round trip of text with many distinct contexts
+fn should_compress_alice29() {
+	use std::io::Read;
+
+	let mut expected = Vec::new();
+	let _ = std::fs::File::open("data/alice29.txt").unwrap().read_to_end(&mut expected);
+
+	assert_eq!(expected, compress_and_decompress(&expected));
+}
+
+#[test]
+/// Compressor: round trip of an input spanning several meta-blocks
+fn should_compress_multiple_meta_blocks() {
+	use std::io::Read;
+
+	let mut expected = Vec::new();
+	let _ = std::fs::File::open("data/lcet10.txt").unwrap().read_to_end(&mut expected);
+	let _ = std::fs::File::open("data/random_org_10k.bin").unwrap().read_to_end(&mut expected);
+	let _ = std::fs::File::open("data/plrabn12.txt").unwrap().read_to_end(&mut expected);
+
+	assert_eq!(expected, compress_and_decompress(&expected));
+}
+
+#[test]
+/// ParallelCompressor: round trip of an input spanning several chunks, with and without priming
+fn should_compress_multiple_chunks_in_parallel() {
+	use std::io::{ Read, Write };
+	use brotli::{ Decompressor, ParallelCompressor };
+
+	let mut expected = Vec::new();
+	let _ = std::fs::File::open("data/lcet10.txt").unwrap().read_to_end(&mut expected);
+	let _ = std::fs::File::open("data/random_org_10k.bin").unwrap().read_to_end(&mut expected);
+	let _ = std::fs::File::open("data/plrabn12.txt").unwrap().read_to_end(&mut expected);
+
+	for &prime in &[false, true] {
+		let mut compressor = ParallelCompressor::with_options(Vec::new(), 4, 100000, prime);
+		compressor.write_all(&expected).unwrap();
+		let brotli_stream = compressor.finish().unwrap();
+
+		let mut decompressed = Vec::new();
+		let _ = Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed);
+
+		assert_eq!(expected, decompressed);
+	}
+}
+
+#[test]
+/// Compressor: round trip of repetitive structured data, with many repeated distances
+fn should_compress_compressed_repeated() {
+	use std::io::Read;
+
+	let mut expected = Vec::new();
+	let _ = std::fs::File::open("data/compressed_repeated").unwrap().read_to_end(&mut expected);
+
+	assert_eq!(expected, compress_and_decompress(&expected));
+}
+
+#[test]
+/// Compressor: incompressible input expands by no more than max_compressed_size() allows
+fn should_compress_random_org_10k_bin() {
+	use std::io::{ Read, Write };
+	use brotli::{ max_compressed_size, Compressor };
+
+	let mut expected = Vec::new();
+	let _ = std::fs::File::open("data/random_org_10k.bin").unwrap().read_to_end(&mut expected);
+
+	let mut compressor = Compressor::new(Vec::new());
+	compressor.write_all(&expected).unwrap();
+	let brotli_stream = compressor.finish().unwrap();
+
+	assert!(brotli_stream.len() <= max_compressed_size(expected.len()));
+	assert!(brotli_stream.len() <= expected.len() + 6);
+	assert_eq!(expected, compress_and_decompress(&expected));
+}
+
+#[test]
+/// Compressor: catable streams of parts of a file concatenate to a stream of the whole file
+fn should_concat_catable_streams() {
+	use std::io::{ Read, Write };
+	use brotli::{ concat, Compressor, Decompressor };
+
+	let mut expected = Vec::new();
+	let _ = std::fs::File::open("data/alice29.txt").unwrap().read_to_end(&mut expected);
+	let _ = std::fs::File::open("data/random_org_10k.bin").unwrap().read_to_end(&mut expected);
+
+	let bounds = [0, 0, 1, 3, 5000, 5001, 90000, 155000, expected.len()];
+	let streams = bounds.windows(2).map(|w| {
+		let mut compressor = Compressor::new_catable(Vec::new());
+		compressor.write_all(&expected[w[0]..w[1]]).unwrap();
+		compressor.finish().unwrap()
+	}).collect::<Vec<_>>();
+
+	let brotli_stream = concat(&streams).unwrap();
+
+	let mut decompressed = Vec::new();
+	let _ = Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed);
+
+	assert_eq!(expected, decompressed);
+}
+
+/// In-memory pipe, the reading end panics if it is asked for data that has not been written yet,
+/// unless the pipe has been closed.
+#[derive(Clone)]
+struct Pipe {
+	buf: std::rc::Rc<std::cell::RefCell<std::collections::VecDeque<u8>>>,
+	closed: std::rc::Rc<std::cell::Cell<bool>>,
+}
+
+impl std::io::Write for Pipe {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.buf.borrow_mut().extend(buf.iter().cloned());
+
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+impl std::io::Read for Pipe {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let mut pipe = self.buf.borrow_mut();
+
+		if pipe.is_empty() && !buf.is_empty() && !self.closed.get() {
+			panic!("read from empty pipe");
+		}
+
+		let l = std::cmp::min(pipe.len(), buf.len());
+		for (b, byte) in buf.iter_mut().zip(pipe.drain(..l)) {
+			*b = byte;
+		}
+
+		Ok(l)
+	}
+}
+
+#[test]
+/// Compressor: everything written before a flush can be decompressed right away
+fn should_decompress_up_to_flush() {
+	use std::io::{ Read, Write };
+	use brotli::{ Compressor, Decompressor };
+
+	let pipe = Pipe{
+		buf: std::rc::Rc::new(std::cell::RefCell::new(std::collections::VecDeque::new())),
+		closed: std::rc::Rc::new(std::cell::Cell::new(false)),
+	};
+	let mut compressor = Compressor::new(pipe.clone());
+	let mut decompressor = Decompressor::new(pipe.clone());
+
+	let messages = vec![
+		b"{\"id\": 1, \"method\": \"subscribe\"}".to_vec(),
+		b"{\"id\": 2, \"method\": \"subscribe\", \"params\": [\"ticker\"]}".to_vec(),
+		vec![b'x'; 100000],
+		b"{\"id\": 3}".to_vec(),
+	];
+
+	for message in &messages {
+		compressor.write_all(message).unwrap();
+		compressor.flush().unwrap();
+
+		let mut decompressed = vec![0; message.len()];
+		decompressor.read_exact(&mut decompressed).unwrap();
+
+		assert_eq!(message, &decompressed);
+	}
+
+	let _ = compressor.finish().unwrap();
+	pipe.closed.set(true);
+
+	let mut rest = Vec::new();
+	let _ = decompressor.read_to_end(&mut rest).unwrap();
+
+	assert_eq!(Vec::<u8>::new(), rest);
+}
+
+#[test]
+/// Compressor: round trip with a raw dictionary, that backward references reach into
+fn should_compress_with_dictionary() {
+	use std::io::{ Read, Write };
+	use brotli::{ Compressor, Decompressor };
+
+	let mut dictionary = Vec::new();
+	let _ = std::fs::File::open("data/alice29.txt").unwrap().read_to_end(&mut dictionary);
+	let expected = dictionary[100000..120000].to_vec();
+
+	let mut compressor = Compressor::with_dictionary(Vec::new(), &dictionary);
+	compressor.write_all(&expected).unwrap();
+	let brotli_stream = compressor.finish().unwrap();
+
+	let mut compressor = Compressor::new(Vec::new());
+	compressor.write_all(&expected).unwrap();
+	let brotli_stream_without_dictionary = compressor.finish().unwrap();
+
+	assert!(brotli_stream.len() * 10 < brotli_stream_without_dictionary.len());
+
+	let mut decompressed = Vec::new();
+	let _ = Decompressor::with_dictionary(&brotli_stream[..], &dictionary).read_to_end(&mut decompressed);
+
+	assert_eq!(expected, decompressed);
+}
+
+#[test]
+/// Compressor: round trip of input that continues a raw dictionary, which matches must not run
+/// beyond, and whose bytes do not count for the literal context
+fn should_compress_continuation_of_dictionary() {
+	use std::io::{ Read, Write };
+	use brotli::{ Compressor, Decompressor };
+
+	let dictionary = b"abcdefgh";
+	let expected = b"efghefghefghabcdabcd, and some literals after the dictionary".to_vec();
+
+	let mut compressor = Compressor::with_dictionary(Vec::new(), dictionary);
+	compressor.write_all(&expected).unwrap();
+	let brotli_stream = compressor.finish().unwrap();
+
+	let mut decompressed = Vec::new();
+	let _ = Decompressor::with_dictionary(&brotli_stream[..], dictionary).read_to_end(&mut decompressed);
+
+	assert_eq!(expected, decompressed);
+}
+
+#[test]
+/// Decompressor: a copy from a compound dictionary, the test vector of CompoundDictionaryTest in
+/// the reference implementation, which addresses the dictionary beyond the output
+fn should_decompress_with_compound_dictionary() {
+	use std::io::Read;
+	use brotli::Decompressor;
+
+	let brotli_stream = [0xa1, 0xa8, 0x00, 0xc0, 0x2f, 0x01, 0x10, 0xc4, 0x44, 0x09, 0x00];
+	let dictionary = b"Kot lomom kolol slona!";
+
+	let mut decompressed = Vec::new();
+	let _ = Decompressor::with_dictionary(&brotli_stream[..], dictionary).read_to_end(&mut decompressed);
+
+	assert_eq!(dictionary.to_vec(), decompressed);
+}
+
+#[test]
+#[should_panic(expected = "no dictionary was given")]
+/// Decompressor: a stream that identifies its dictionary can not be decompressed without it
+fn should_reject_missing_dictionary() {
+	use std::io::{ Read, Write };
+	use brotli::{ Compressor, Decompressor };
+
+	let mut compressor = Compressor::with_dictionary(Vec::new(), b"Hello, World!");
+	compressor.write_all(b"Hello, World! Hello, World!").unwrap();
+	let brotli_stream = compressor.finish().unwrap();
+
+	let mut decompressed = Vec::new();
+	let result = Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed);
+
+	if let Err(e) = result {
+		panic!("{:?}", e);
+	}
+}
+
+#[test]
+#[should_panic(expected = "different dictionary")]
+/// Decompressor: a stream that identifies its dictionary can not be decompressed with another one
+fn should_reject_wrong_dictionary() {
+	use std::io::{ Read, Write };
+	use brotli::{ Compressor, Decompressor };
+
+	let mut compressor = Compressor::with_dictionary(Vec::new(), b"Hello, World!");
+	compressor.write_all(b"Hello, World! Hello, World!").unwrap();
+	let brotli_stream = compressor.finish().unwrap();
+
+	let mut decompressed = Vec::new();
+	let result = Decompressor::with_dictionary(&brotli_stream[..], b"Hello, World?").read_to_end(&mut decompressed);
+
+	if let Err(e) = result {
+		panic!("{:?}", e);
+	}
+}
+
+#[test]
+/// Decompressor: errors carry the position in the stream, at which decoding has failed
+fn should_report_position_of_error() {
+	use std::io::Read;
+	use brotli::{ Decompressor, StreamError };
+
+	let mut brotli_stream = Vec::new();
+	std::fs::File::open("data/alice29.txt.compressed").unwrap().read_to_end(&mut brotli_stream).unwrap();
+	brotli_stream[20000] ^= 0x55;
+
+	let mut decompressed = Vec::new();
+	let e = Decompressor::new(&brotli_stream[..]).read_to_end(&mut decompressed).unwrap_err();
+	let stream_error = e.get_ref().and_then(|e| e.downcast_ref::<StreamError>()).unwrap().clone();
+
+	assert!(stream_error.meta_block.is_some());
+	assert!(stream_error.command.is_some());
+	assert!(stream_error.bit_offset >= 20000 * 8);
+	assert!(stream_error.byte_offset >= decompressed.len() as u64);
+	assert!(e.to_string().starts_with(&stream_error.message));
+	assert!(e.to_string().contains(&format!("bit offset {}", stream_error.bit_offset)));
+}
+
+#[test]
+/// Decompressor: progress is reported at the end of each meta-block, up to the totals
+fn should_report_progress() {
+	use std::io::{ Read, Write };
+	use std::sync::mpsc::channel;
+	use brotli::{ Compressor, Decompressor };
+
+	let input = (0..5000).map(|i| format!("{} bottles of beer on the wall, ", i % 99)).collect::<String>().into_bytes();
+
+	let mut compressor = Compressor::new(Vec::new());
+	for chunk in input.chunks(10000) {
+		compressor.write_all(chunk).unwrap();
+		compressor.flush().unwrap();
+	}
+	let brotli_stream = compressor.finish().unwrap();
+
+	let (sender, receiver) = channel();
+	let mut decompressor = Decompressor::new(&brotli_stream[..]);
+	decompressor.set_progress_callback(move |total_in, total_out| sender.send((total_in, total_out)).unwrap());
+
+	let mut decompressed = Vec::new();
+	decompressor.read_to_end(&mut decompressed).unwrap();
+
+	let progress = receiver.try_iter().collect::<Vec<_>>();
+
+	assert_eq!(input, decompressed);
+	assert!(progress.len() > input.len() / 10000);
+	assert!(progress.windows(2).all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1));
+	assert_eq!(Some(&(brotli_stream.len() as u64, input.len() as u64)), progress.last());
+	assert_eq!((brotli_stream.len() as u64, input.len() as u64), (decompressor.total_in(), decompressor.total_out()));
+}
+
+/// Returns n pseudo-random JSON documents, as they might be returned by an API.
+fn api_payloads(seed: u32, n: usize) -> Vec<Vec<u8>> {
+	let mut state = seed;
+	let mut next = |m: u32| {
+		state = state.wrapping_mul(1103515245).wrapping_add(12345);
+		(state >> 16) % m
+	};
+	let cities = ["Lisbon", "Osaka", "Tromsø", "Valparaíso", "Nairobi", "Winnipeg"];
+	let statuses = ["pending", "shipped", "delivered", "returned"];
+
+	(0..n).map(|_| {
+		let items = (0..(1 + next(3))).map(|_| {
+			format!("{{\"sku\": \"SKU-{:05}\", \"quantity\": {}, \"unit_price\": {{\"amount\": {}.{:02}, \"currency\": \"EUR\"}}}}",
+				next(100000), 1 + next(9), next(500), next(100))
+		}).collect::<Vec<_>>();
+
+		format!("{{\"order_id\": \"ord_{:08x}\", \"status\": \"{}\", \"customer\": {{\"customer_id\": {}, \"shipping_address\": {{\"city\": \"{}\", \"postal_code\": \"{:05}\"}}}}, \"line_items\": [{}], \"metadata\": {{\"source\": \"mobile_app\", \"api_version\": \"2024-06-01\"}}}}",
+			next(1 << 30), statuses[next(4) as usize], next(1000000), cities[next(6) as usize], next(100000), items.join(", ")).into_bytes()
+	}).collect()
+}
+
+#[test]
+/// dictionary::train(): a trained dictionary improves the compression of held-out samples
+fn should_train_dictionary_for_held_out_samples() {
+	use std::io::{ Read, Write };
+	use brotli::{ Compressor, Decompressor };
+	use brotli::dictionary::train;
+
+	let dictionary = train(&api_payloads(1, 500), 4096);
+	let held_out = api_payloads(2, 100);
+
+	assert!(dictionary.len() <= 4096);
+
+	let mut size = 0;
+	let mut size_with_dictionary = 0;
+
+	for sample in &held_out {
+		let mut compressor = Compressor::new(Vec::new());
+		compressor.write_all(sample).unwrap();
+		size += compressor.finish().unwrap().len();
+
+		let mut compressor = Compressor::with_dictionary(Vec::new(), &dictionary);
+		compressor.write_all(sample).unwrap();
+		let brotli_stream = compressor.finish().unwrap();
+		size_with_dictionary += brotli_stream.len();
+
+		let mut decompressed = Vec::new();
+		let _ = Decompressor::with_dictionary(&brotli_stream[..], &dictionary).read_to_end(&mut decompressed);
+
+		assert_eq!(sample, &decompressed);
+	}
+
+	assert!(2 * size_with_dictionary < size, "held-out samples: {} bytes without, {} bytes with dictionary", size, size_with_dictionary);
+}
+
 fn inverse_move_to_front_transform(v: &mut[u8]) {
 	let mut mtf: Vec<u8> = vec![0; 256];
 	let v_len = v.len();